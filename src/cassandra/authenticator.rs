@@ -0,0 +1,410 @@
+use std::ffi::CString;
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::raw::{c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use cassandra::credentials::{Credentials, CredentialsProvider};
+
+use cassandra_sys::CassAuthenticator as _Authenticator;
+use cassandra_sys::CassAuthenticatorCallbacks as _AuthenticatorCallbacks;
+use cassandra_sys::CassInet as _Inet;
+use cassandra_sys::cass_authenticator_address;
+use cassandra_sys::cass_authenticator_class_name;
+use cassandra_sys::cass_authenticator_exchange_data;
+use cassandra_sys::cass_authenticator_hostname;
+use cassandra_sys::cass_authenticator_set_error;
+use cassandra_sys::cass_authenticator_set_exchange_data;
+use cassandra_sys::cass_authenticator_set_response;
+
+///The node a connection is authenticating with
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct AuthHost {
+    ///The node's address, if the driver knows it
+    pub address: Option<IpAddr>,
+    ///The node's host name, if reverse DNS lookups are enabled
+    pub hostname: String,
+    ///The node's authenticator class, such as
+    ///`org.apache.cassandra.auth.PasswordAuthenticator`
+    pub class_name: String,
+}
+
+///One connection's SASL exchange with a node.
+///
+///Errors are messages; they fail the connection with
+///`CASS_ERROR_SERVER_BAD_CREDENTIALS`.
+pub trait Authenticator: Send {
+    ///The token sent first, when the node asks the connection to
+    ///authenticate.
+    fn initial_response(&mut self) -> Result<Vec<u8>, String>;
+
+    ///The token answering a challenge from the node.
+    fn evaluate_challenge(&mut self, challenge: &[u8]) -> Result<Vec<u8>, String>;
+
+    ///Called with the node's final token once it accepts the connection.
+    fn on_success(&mut self, _token: &[u8]) {}
+
+    ///Called when the connection is closed before the node accepts it. The
+    ///driver does not tell whether the node refused the exchange or the
+    ///connection was lost or timed out.
+    fn on_failure(&mut self) {}
+}
+
+///Creates an `Authenticator` for each connection a cluster opens.
+///
+///Set with `Cluster::set_auth_provider`.
+pub trait AuthProvider: Send + Sync {
+    ///Starts the exchange of a new connection to `host`.
+    fn new_authenticator(&self, host: &AuthHost) -> Box<Authenticator>;
+
+    ///Called when `Cluster::connect` fails with
+    ///`CASS_ERROR_SERVER_BAD_CREDENTIALS`, because a node refused the
+    ///exchange. Connections the driver opens later are refused silently.
+    fn on_refused(&self) {}
+}
+
+///Username and password authentication, for Cassandra's
+///`PasswordAuthenticator` and compatible servers.
+///
+///The credentials come from a `CredentialsProvider`, asked each time a
+///connection authenticates. Connects refused for bad credentials are
+///logged and counted, as they usually mean the credentials are stale.
+#[derive(Clone)]
+pub struct PlainTextAuthProvider {
+    credentials: Arc<CredentialsProvider>,
+    failures: Arc<AtomicUsize>,
+}
+
+impl PlainTextAuthProvider {
+    ///Authenticates as `username`
+    pub fn new(username: &str, password: &str) -> PlainTextAuthProvider {
+        PlainTextAuthProvider::with_provider(Credentials::new(username, password))
+    }
+
+    ///Authenticates with the provider's current credentials
+    pub fn with_provider<P: CredentialsProvider + 'static>(credentials: P) -> PlainTextAuthProvider {
+        PlainTextAuthProvider {
+            credentials: Arc::new(credentials),
+            failures: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    ///The number of `Cluster::connect` calls, with this provider or its
+    ///clones, that a node refused for bad credentials
+    pub fn failures(&self) -> usize {
+        self.failures.load(Ordering::Relaxed)
+    }
+}
+
+impl AuthProvider for PlainTextAuthProvider {
+    fn new_authenticator(&self, host: &AuthHost) -> Box<Authenticator> {
+        Box::new(PlainTextAuthenticator {
+            provider: self.clone(),
+            host: host.clone(),
+            username: None,
+        })
+    }
+
+    fn on_refused(&self) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+        warn!("a node refused the credentials; they may be stale");
+    }
+}
+
+struct PlainTextAuthenticator {
+    provider: PlainTextAuthProvider,
+    host: AuthHost,
+    username: Option<String>,
+}
+
+impl Authenticator for PlainTextAuthenticator {
+    fn initial_response(&mut self) -> Result<Vec<u8>, String> {
+        let credentials = try!(self.provider.credentials.credentials());
+        // SASL PLAIN: authorization id, authentication id and password
+        let mut response = vec![0];
+        response.extend(credentials.username.as_bytes());
+        response.push(0);
+        response.extend(credentials.password.as_bytes());
+        self.username = Some(credentials.username);
+        Ok(response)
+    }
+
+    fn evaluate_challenge(&mut self, _challenge: &[u8]) -> Result<Vec<u8>, String> {
+        self.initial_response()
+    }
+
+    fn on_failure(&mut self) {
+        if let Some(ref username) = self.username {
+            debug!("connection authenticating as {} with {:?} closed before it was accepted",
+                   username,
+                   self.host.address);
+        }
+    }
+}
+
+///The callbacks that run an `AuthProvider` for the driver
+pub fn authenticator_callbacks() -> _AuthenticatorCallbacks {
+    _AuthenticatorCallbacks {
+        initial_callback: Some(initial_callback),
+        challenge_callback: Some(challenge_callback),
+        success_callback: Some(success_callback),
+        cleanup_callback: Some(cleanup_callback),
+    }
+}
+
+///The callback data owning a shared `AuthProvider`
+pub fn provider_data(provider: Arc<AuthProvider>) -> *mut c_void {
+    Box::into_raw(Box::new(provider)) as *mut c_void
+}
+
+///Frees the data from `provider_data` once the driver is done with it
+pub unsafe extern "C" fn provider_data_cleanup(data: *mut c_void) {
+    drop(Box::from_raw(data as *mut Arc<AuthProvider>));
+}
+
+unsafe extern "C" fn initial_callback(auth: *mut _Authenticator, data: *mut c_void) {
+    let provider = &*(data as *const Arc<AuthProvider>);
+    let host = auth_host(auth);
+    respond(auth, || {
+        let mut exchange = Exchange {
+            authenticator: provider.new_authenticator(&host),
+            succeeded: false,
+        };
+        let response = exchange.authenticator.initial_response();
+        cass_authenticator_set_exchange_data(auth, Box::into_raw(Box::new(exchange)) as *mut c_void);
+        response
+    });
+}
+
+unsafe extern "C" fn challenge_callback(auth: *mut _Authenticator,
+                                        _data: *mut c_void,
+                                        token: *const c_char,
+                                        token_size: u64) {
+    respond(auth, || {
+        match exchange(auth) {
+            Some(exchange) => exchange.authenticator.evaluate_challenge(token_slice(token, token_size)),
+            None => Err("challenge without an exchange".to_owned()),
+        }
+    });
+}
+
+unsafe extern "C" fn success_callback(auth: *mut _Authenticator,
+                                      _data: *mut c_void,
+                                      token: *const c_char,
+                                      token_size: u64) {
+    if let Some(exchange) = exchange(auth) {
+        exchange.succeeded = true;
+        // a panic must not unwind into the driver, and there is no error to report it with
+        let authenticator = &mut exchange.authenticator;
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| authenticator.on_success(token_slice(token, token_size))));
+    }
+}
+
+unsafe extern "C" fn cleanup_callback(auth: *mut _Authenticator, _data: *mut c_void) {
+    let exchange_data = cass_authenticator_exchange_data(auth) as *mut Exchange;
+    if !exchange_data.is_null() {
+        cass_authenticator_set_exchange_data(auth, ptr::null_mut());
+        let mut exchange = Box::from_raw(exchange_data);
+        if !exchange.succeeded {
+            let authenticator = &mut exchange.authenticator;
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| authenticator.on_failure()));
+        }
+    }
+}
+
+///A connection's authenticator and whether the node accepted it
+struct Exchange {
+    authenticator: Box<Authenticator>,
+    succeeded: bool,
+}
+
+unsafe fn exchange<'a>(auth: *mut _Authenticator) -> Option<&'a mut Exchange> {
+    (cass_authenticator_exchange_data(auth) as *mut Exchange).as_mut()
+}
+
+unsafe fn token_slice<'a>(token: *const c_char, token_size: u64) -> &'a [u8] {
+    if token.is_null() { &[] } else { slice::from_raw_parts(token as *const u8, token_size as usize) }
+}
+
+///Sends the authenticator's response, or fails the exchange with its error
+///or panic.
+unsafe fn respond<F: FnOnce() -> Result<Vec<u8>, String>>(auth: *mut _Authenticator, f: F) {
+    let result = panic::catch_unwind(AssertUnwindSafe(f))
+                     .unwrap_or_else(|_| Err("the authenticator panicked".to_owned()));
+    match result {
+        Ok(response) => {
+            cass_authenticator_set_response(auth, response.as_ptr() as *const c_char, response.len() as u64)
+        }
+        Err(message) => {
+            let message = CString::new(message.replace('\0', " ")).expect("NUL bytes were replaced");
+            cass_authenticator_set_error(auth, message.as_ptr())
+        }
+    }
+}
+
+unsafe fn auth_host(auth: *mut _Authenticator) -> AuthHost {
+    let mut inet: _Inet = mem::zeroed();
+    cass_authenticator_address(auth, &mut inet);
+    let address = match inet.address_length {
+        4 => {
+            let a = inet.address;
+            Some(IpAddr::V4(Ipv4Addr::new(a[0], a[1], a[2], a[3])))
+        }
+        16 => Some(IpAddr::V6(Ipv6Addr::from(inet.address))),
+        _ => None,
+    };
+    let mut length = 0;
+    let hostname = cass_authenticator_hostname(auth, &mut length);
+    let hostname = String::from_utf8_lossy(token_slice(hostname, length)).into_owned();
+    let class_name = cass_authenticator_class_name(auth, &mut length);
+    let class_name = String::from_utf8_lossy(token_slice(class_name, length)).into_owned();
+    AuthHost {
+        address: address,
+        hostname: hostname,
+        class_name: class_name,
+    }
+}
+
+#[test]
+fn test_authenticator_exchange() {
+    use std::io::{self, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::str::FromStr;
+    use std::sync::Mutex;
+    use std::thread;
+    use cassandra::cluster::Cluster;
+    use cassandra::contact_points::ContactPoints;
+    use time::Duration;
+
+    // a stand-in node that asks for authentication, runs a two step exchange
+    // and then fails everything else
+    fn serve(stream: &mut TcpStream, responses: &Mutex<Vec<Vec<u8>>>) -> io::Result<()> {
+        fn int(n: usize) -> Vec<u8> {
+            vec![(n >> 24) as u8, (n >> 16) as u8, (n >> 8) as u8, n as u8]
+        }
+        fn string(s: &str) -> Vec<u8> {
+            let mut body = vec![(s.len() >> 8) as u8, s.len() as u8];
+            body.extend(s.as_bytes());
+            body
+        }
+        fn bytes(b: &[u8]) -> Vec<u8> {
+            let mut body = int(b.len());
+            body.extend(b);
+            body
+        }
+        fn error(code: usize, message: &str) -> Vec<u8> {
+            let mut body = int(code);
+            body.extend(string(message));
+            body
+        }
+        loop {
+            let mut version = [0];
+            try!(stream.read_exact(&mut version));
+            // flags, stream id (one byte before protocol v3), opcode and length
+            let mut header = vec![0; if version[0] >= 3 { 8 } else { 7 }];
+            try!(stream.read_exact(&mut header));
+            let opcode_at = header.len() - 5;
+            let length = header[opcode_at + 1..].iter().fold(0, |length, b| length << 8 | *b as usize);
+            let mut body = vec![0; length];
+            try!(stream.read_exact(&mut body));
+            let (opcode, reply) = match header[opcode_at] {
+                // OPTIONS: SUPPORTED, with no options
+                0x05 => (0x06, vec![0, 0]),
+                // STARTUP: AUTHENTICATE
+                0x01 => (0x03, string("org.example.TokenAuthenticator")),
+                // AUTH_RESPONSE
+                0x0F => {
+                    let token = body[4..].to_vec();
+                    responses.lock().unwrap().push(token.clone());
+                    match &token[..] {
+                        b"step one" => (0x0E, bytes(b"challenge")),
+                        b"step two" => (0x10, bytes(b"welcome")),
+                        _ => (0x00, error(0x0100, "bad credentials")),
+                    }
+                }
+                _ => (0x00, error(0x000A, "not a real node")),
+            };
+            let mut frame = vec![version[0] | 0x80, 0];
+            frame.extend(&header[1..opcode_at]);
+            frame.push(opcode);
+            frame.extend(int(reply.len()));
+            frame.extend(reply);
+            try!(stream.write_all(&frame));
+        }
+    }
+
+    struct Steps(Arc<Mutex<Vec<Vec<u8>>>>);
+
+    impl AuthProvider for Steps {
+        fn new_authenticator(&self, host: &AuthHost) -> Box<Authenticator> {
+            assert_eq!(host.class_name, "org.example.TokenAuthenticator");
+            Box::new(Steps(self.0.clone()))
+        }
+    }
+
+    impl Authenticator for Steps {
+        fn initial_response(&mut self) -> Result<Vec<u8>, String> {
+            Ok(b"step one".to_vec())
+        }
+
+        fn evaluate_challenge(&mut self, challenge: &[u8]) -> Result<Vec<u8>, String> {
+            match challenge {
+                b"challenge" => Ok(b"step two".to_vec()),
+                _ => Err("unexpected challenge".to_owned()),
+            }
+        }
+
+        fn on_success(&mut self, token: &[u8]) {
+            self.0.lock().unwrap().push(token.to_vec());
+        }
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let responses = Arc::new(Mutex::new(vec![]));
+    {
+        let responses = responses.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let responses = responses.clone();
+                thread::spawn(move || serve(&mut stream.unwrap(), &responses));
+            }
+        });
+    }
+    let cluster = || {
+        let mut cluster = Cluster::new();
+        cluster.set_contact_points(ContactPoints::from_str("127.0.0.1").unwrap()).unwrap();
+        cluster.set_port(port).unwrap().set_connect_timeout(Duration::seconds(2));
+        cluster
+    };
+
+    // the stand-in node cannot answer queries, so connecting fails once
+    // authenticated
+    let successes = Arc::new(Mutex::new(vec![]));
+    assert!(cluster().set_auth_provider(Steps(successes.clone())).unwrap().connect().is_err());
+    assert_eq!(responses.lock().unwrap()[..2].to_vec(), vec![b"step one".to_vec(), b"step two".to_vec()]);
+    assert_eq!(successes.lock().unwrap()[0], b"welcome".to_vec());
+
+    responses.lock().unwrap().clear();
+    let plain_text = PlainTextAuthProvider::new("cassandra", "secret");
+    assert!(cluster().set_auth_provider(plain_text.clone()).unwrap().connect().is_err());
+    assert_eq!(responses.lock().unwrap()[0], b"\0cassandra\0secret".to_vec());
+    assert_eq!(plain_text.failures(), 1);
+
+    // a connection the node drops is not a refusal
+    let silent = TcpListener::bind("127.0.0.1:0").unwrap();
+    let silent_port = silent.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in silent.incoming() {
+            drop(stream);
+        }
+    });
+    let mut dropped = cluster();
+    dropped.set_port(silent_port).unwrap();
+    assert!(dropped.set_auth_provider(plain_text.clone()).unwrap().connect().is_err());
+    assert_eq!(plain_text.failures(), 1);
+}
@@ -5,7 +5,6 @@ use cassandra::statement::Statement;
 
 use cassandra_sys::CASS_OK;
 use cassandra_sys::CassError;
-use cassandra_sys::CassConsistency;
 use cassandra_sys::cass_batch_set_consistency;
 use cassandra_sys::cass_batch_add_statement;
 use cassandra_sys::cass_batch_set_custom_payload;
@@ -16,7 +15,7 @@ use cassandra_sys::cass_batch_free;
 use cassandra_sys::cass_batch_new;
 use cassandra_sys::CassCustomPayload as _CassCustomPayload;
 use cassandra::policy::retry::RetryPolicy;
-use cassandra::consistency::Consistency;
+use cassandra::consistency::{Consistency, raw_consistency};
 use cassandra_sys::cass_custom_payload_free;
 use cassandra_sys::cass_custom_payload_new;
 use cassandra_sys::cass_custom_payload_set;
@@ -26,17 +25,23 @@ use cassandra::util::Protected;
 
 ///A group of statements that are executed as a single batch.
 ///<b>Note:</b> Batches are not supported by the binary protocol version 1.
-pub struct Batch(*mut _Batch);
+pub struct Batch(*mut _Batch, bool);
 
 impl Protected<*mut _Batch> for Batch {
     fn inner(&self) -> *mut _Batch {
         self.0
     }
     fn build(inner: *mut _Batch) -> Self {
-        Batch(inner)
+        Batch(inner, false)
     }
 }
 
+///Whether the batch's consistency level was set explicitly, rather than
+///left to the session default.
+pub fn batch_has_consistency(batch: &Batch) -> bool {
+    batch.1
+}
+
 ///Custom payloads not fully supported yet
 pub struct CustomPayload(*mut _CassCustomPayload);
 
@@ -92,14 +97,20 @@ impl Drop for Batch {
 impl Batch {
     ///Creates a new batch statement with batch type.
     pub fn new(batch_type: BatchType) -> Batch {
-        unsafe { Batch(cass_batch_new(batch_type)) }
+        unsafe { Batch(cass_batch_new(batch_type), false) }
     }
 
     ///Sets the batch's consistency level
-    pub fn set_consistency(&mut self, consistency: CassConsistency) -> Result<&Self, CassError> {
+    ///
+    ///<b>Default:</b> the session's write consistency (see
+    ///`Cluster::set_write_consistency`), or `LOCAL_ONE`
+    pub fn set_consistency(&mut self, consistency: Consistency) -> Result<&Self, CassError> {
         unsafe {
-            match cass_batch_set_consistency(self.0, consistency) {
-                CASS_OK => Ok(self),
+            match cass_batch_set_consistency(self.0, raw_consistency(consistency)) {
+                CASS_OK => {
+                    self.1 = true;
+                    Ok(self)
+                }
                 err => Err(err),
             }
         }
@@ -110,7 +121,7 @@ impl Batch {
     ///<b>Default:</b> Not set
     pub fn set_serial_consistency(&mut self, consistency: Consistency) -> Result<&Self, CassError> {
         unsafe {
-            match cass_batch_set_serial_consistency(self.0, consistency.inner()) {
+            match cass_batch_set_serial_consistency(self.0, raw_consistency(consistency)) {
                 CASS_OK => Ok(self),
                 err => Err(err),
             }
@@ -0,0 +1,231 @@
+use std::collections::{HashMap, VecDeque};
+
+use cassandra::batch::{Batch, BatchType};
+use cassandra::error::CassError;
+use cassandra::future::ResultFuture;
+use cassandra::result::CassResult;
+use cassandra::session::Session;
+use cassandra::statement::{Statement, key_values};
+use cassandra::util::Protected;
+
+use cassandra_sys::CASS_BATCH_TYPE_UNLOGGED;
+
+///A batch produced by a `BatchPlanner`
+pub struct PlannedBatch {
+    statements: Vec<Statement>,
+    estimated_size: usize,
+}
+
+impl PlannedBatch {
+    ///The statements in this batch
+    pub fn statements(&self) -> &[Statement] {
+        &self.statements
+    }
+
+    ///The sum of the estimated sizes of the statements in this batch
+    pub fn estimated_size(&self) -> usize {
+        self.estimated_size
+    }
+
+    ///Creates a driver batch holding this batch's statements.
+    pub fn to_batch(&self, batch_type: BatchType) -> Result<Batch, CassError> {
+        let mut batch = Batch::new(batch_type);
+        for statement in &self.statements {
+            try!(batch.add_statement(statement).map_err(CassError::build));
+        }
+        Ok(batch)
+    }
+}
+
+///The outcome of executing one of the planned batches
+pub struct BatchOutcome {
+    ///Number of statements in the batch
+    pub statements: usize,
+    ///Estimated size of the batch in bytes
+    pub estimated_size: usize,
+    ///The result of executing the batch
+    pub result: Result<CassResult, CassError>,
+}
+
+///Splits a list of statements into batches that stay below a size and a
+///statement count threshold.
+///
+///Unlogged batches can also be grouped by partition, so that each batch
+///only touches the replicas of a single partition. Statements are grouped
+///by the values bound at their key indices (see `Statement::add_key_index`
+///and `PreparedStatement::partition_key_indices`); statements without key
+///values are batched together.
+pub struct BatchPlanner {
+    batch_type: BatchType,
+    max_bytes: usize,
+    max_statements: usize,
+    group_by_partition: bool,
+    concurrency: usize,
+    statements: Vec<Statement>,
+}
+
+impl BatchPlanner {
+    ///Creates a planner for batches of the given type.
+    pub fn new(batch_type: BatchType) -> Self {
+        BatchPlanner {
+            batch_type: batch_type,
+            max_bytes: 5 * 1024,
+            max_statements: 100,
+            group_by_partition: false,
+            concurrency: 8,
+            statements: vec![],
+        }
+    }
+
+    ///Sets the estimated size above which a batch is split. A single
+    ///statement larger than this is sent in a batch of its own.
+    ///
+    ///<b>Default:</b> 5 KiB, Cassandra's default `batch_size_warn_threshold_in_kb`
+    pub fn set_max_bytes(&mut self, max_bytes: usize) -> &mut Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    ///Sets the maximum number of statements in a batch.
+    ///
+    ///<b>Default:</b> 100
+    pub fn set_max_statements(&mut self, max_statements: usize) -> &mut Self {
+        self.max_statements = max_statements;
+        self
+    }
+
+    ///Groups unlogged statements into one batch per partition. Has no effect
+    ///on logged and counter batches.
+    ///
+    ///<b>Default:</b> false
+    pub fn set_group_by_partition(&mut self, enabled: bool) -> &mut Self {
+        self.group_by_partition = enabled;
+        self
+    }
+
+    ///Sets how many batches `execute` keeps in flight at once.
+    ///
+    ///<b>Default:</b> 8
+    pub fn set_concurrency(&mut self, concurrency: usize) -> &mut Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    ///Adds a statement to be planned.
+    pub fn add_statement(&mut self, statement: Statement) -> &mut Self {
+        self.statements.push(statement);
+        self
+    }
+
+    ///Splits the added statements into batches.
+    pub fn plan(self) -> Vec<PlannedBatch> {
+        let group = self.group_by_partition && is_unlogged(self.batch_type);
+        let mut groups: Vec<Vec<Statement>> = vec![];
+        let mut group_of_key: HashMap<Option<Vec<Vec<u8>>>, usize> = HashMap::new();
+        for statement in self.statements {
+            let key = if group {
+                key_values(&statement).map(|values| values.iter().map(|value| value.to_vec()).collect())
+            } else {
+                None
+            };
+            let next = groups.len();
+            let index = *group_of_key.entry(key).or_insert(next);
+            if index == next {
+                groups.push(vec![]);
+            }
+            groups[index].push(statement);
+        }
+
+        let mut batches = vec![];
+        for statements in groups {
+            let mut current = PlannedBatch {
+                statements: vec![],
+                estimated_size: 0,
+            };
+            for statement in statements {
+                let size = statement.estimated_size();
+                if !current.statements.is_empty() &&
+                   (current.statements.len() >= self.max_statements || current.estimated_size + size > self.max_bytes) {
+                    batches.push(current);
+                    current = PlannedBatch {
+                        statements: vec![],
+                        estimated_size: 0,
+                    };
+                }
+                current.estimated_size += size;
+                current.statements.push(statement);
+            }
+            if !current.statements.is_empty() {
+                batches.push(current);
+            }
+        }
+        batches
+    }
+
+    ///Plans the batches and executes them, keeping up to the configured
+    ///number of batches in flight: the next batch is sent as soon as the
+    ///oldest one in flight has finished. Returns one outcome per batch, in
+    ///plan order.
+    pub fn execute(self, session: &Session) -> Vec<BatchOutcome> {
+        let batch_type = self.batch_type;
+        let concurrency = if self.concurrency == 0 { 1 } else { self.concurrency };
+        let batches = self.plan();
+        let mut outcomes = Vec::with_capacity(batches.len());
+        let mut in_flight: VecDeque<(&PlannedBatch, Result<ResultFuture, CassError>)> = VecDeque::new();
+        let mut pending = batches.iter();
+        loop {
+            while in_flight.len() < concurrency {
+                match pending.next() {
+                    Some(planned) => {
+                        let future = planned.to_batch(batch_type).map(|batch| session.execute_batch(batch));
+                        in_flight.push_back((planned, future));
+                    }
+                    None => break,
+                }
+            }
+            let (planned, future) = match in_flight.pop_front() {
+                Some(oldest) => oldest,
+                None => return outcomes,
+            };
+            outcomes.push(BatchOutcome {
+                statements: planned.statements.len(),
+                estimated_size: planned.estimated_size,
+                result: future.and_then(|mut future| future.wait()),
+            });
+        }
+    }
+}
+
+fn is_unlogged(batch_type: BatchType) -> bool {
+    match batch_type {
+        CASS_BATCH_TYPE_UNLOGGED => true,
+        _ => false,
+    }
+}
+
+#[test]
+fn test_batch_planner_splits_and_groups() {
+    let statement = |id: i32| {
+        let mut statement = Statement::new("INSERT INTO t (id, v) VALUES (?, ?)", 2);
+        statement.add_key_index(0).unwrap();
+        statement.bind_int32(0, id).unwrap();
+        statement.bind_string(1, "value").unwrap();
+        statement
+    };
+
+    let mut planner = BatchPlanner::new(CASS_BATCH_TYPE_UNLOGGED);
+    planner.set_max_statements(2);
+    for id in &[1, 1, 1, 2, 2] {
+        planner.add_statement(statement(*id));
+    }
+    let sizes: Vec<usize> = planner.plan().iter().map(|batch| batch.statements().len()).collect();
+    assert_eq!(sizes, vec![2, 2, 1]);
+
+    let mut planner = BatchPlanner::new(CASS_BATCH_TYPE_UNLOGGED);
+    planner.set_group_by_partition(true);
+    for id in &[1, 2, 1, 2, 1] {
+        planner.add_statement(statement(*id));
+    }
+    let sizes: Vec<usize> = planner.plan().iter().map(|batch| batch.statements().len()).collect();
+    assert_eq!(sizes, vec![3, 2]);
+}
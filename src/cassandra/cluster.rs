@@ -1,13 +1,10 @@
-use std::str::FromStr;
-use cassandra::ssl::Ssl;
+use cassandra::ssl::{Ssl, SslConfig, SslError, VerifyMode};
 use cassandra_sys::CassCluster as _Cluster;
 use std::ffi::CString;
-use std::net::AddrParseError;
-use std::net::Ipv4Addr;
 // use ip::IpAddr;
 use time::Duration;
 use std::ffi::NulError;
-use std::fmt;
+use std::sync::Arc;
 use cassandra_sys::cass_bool_t;
 use cassandra_sys::cass_false;
 use cassandra_sys::cass_true;
@@ -16,6 +13,7 @@ use cassandra_sys::cass_cluster_free;
 use cassandra_sys::cass_cluster_set_contact_points;
 use cassandra_sys::cass_cluster_set_port;
 use cassandra_sys::cass_cluster_set_ssl;
+use cassandra_sys::cass_cluster_set_use_hostname_resolution;
 use cassandra_sys::cass_cluster_set_protocol_version;
 use cassandra_sys::cass_cluster_set_num_threads_io;
 use cassandra_sys::cass_cluster_set_queue_size_io;
@@ -37,6 +35,7 @@ use cassandra_sys::cass_cluster_set_token_aware_routing;
 use cassandra_sys::cass_cluster_set_load_balance_dc_aware;
 use cassandra_sys::cass_cluster_set_load_balance_round_robin;
 use cassandra_sys::cass_cluster_set_credentials;
+use cassandra_sys::cass_cluster_set_authenticator_callbacks;
 use cassandra_sys::cass_cluster_set_request_timeout;
 use cassandra_sys::cass_future_error_code;
 use cassandra_sys::cass_cluster_set_connect_timeout;
@@ -51,13 +50,23 @@ use cassandra_sys::cass_cluster_set_use_schema;
 use cassandra_sys::cass_cluster_set_whitelist_filtering;
 use cassandra_sys::cass_session_new;
 use cassandra_sys::cass_session_connect;
+use cassandra_sys::cass_session_connect_keyspace;
 use cassandra::error::CassError;
 use cassandra::future::ConnectFuture;
 use cassandra::time::TimestampGen;
 use cassandra::policy::retry::RetryPolicy;
+use cassandra::policy::retry_strategy::RetryStrategy;
+use cassandra::authenticator::{AuthProvider, PlainTextAuthProvider, authenticator_callbacks, provider_data,
+                               provider_data_cleanup};
+use cassandra::credentials::CredentialsProvider;
 use cassandra_sys::CASS_OK;
+use cassandra_sys::CASS_ERROR_LIB_BAD_PARAMS;
+use cassandra_sys::CASS_ERROR_SERVER_BAD_CREDENTIALS;
 use cassandra::util::Protected;
-use cassandra::session::Session;
+use cassandra::session::{Session, SessionOptions, new_session};
+use cassandra::codec::CodecRegistry;
+use cassandra::contact_points::ContactPoints;
+use cassandra::consistency::Consistency;
 
 ///Possible Cql Protocol versions
 #[allow(missing_docs)]
@@ -68,28 +77,6 @@ pub enum CqlProtocol {
     FOUR = 4,
 }
 
-///A set of cassandra contact points
-pub struct ContactPoints(Vec<Ipv4Addr>);
-
-impl fmt::Display for ContactPoints {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let contact_points: Vec<String> = self.0.iter().map(|ip| format!("{}", ip)).collect();
-        write!(f, "{} ", contact_points.join(","))
-    }
-}
-
-impl FromStr for ContactPoints {
-    type Err = AddrParseError;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let points: Vec<&str> = s.split(',').collect();
-
-        let contact_points = points.iter()
-                                   .map(|addr| Ipv4Addr::from_str(addr))
-                                   .collect();
-        Ok(ContactPoints(try!(contact_points)))
-    }
-}
-
 ///
 /// The main class to use when interacting with a Cassandra cluster.
 /// Typically, one instance of this class will be created for each separate
@@ -102,7 +89,8 @@ impl FromStr for ContactPoints {
 /// let mut session = cluster.connect().unwrap();
 /// ```
 
-pub struct Cluster(pub *mut _Cluster);
+pub struct Cluster(pub *mut _Cluster, SessionOptions, Option<Arc<AuthProvider>>);
+unsafe impl Send for Cluster {}
 
 impl Drop for Cluster {
     ///Frees a cluster instance.
@@ -116,31 +104,112 @@ impl Protected<*mut _Cluster> for Cluster {
         self.0
     }
     fn build(inner: *mut _Cluster) -> Self {
-        Cluster(inner)
+        Cluster(inner, SessionOptions::default(), None)
+    }
+}
+
+///A connection to a cluster, started by `start_connect`
+pub struct PendingSession {
+    future: ConnectFuture,
+    session: Session,
+    auth_provider: Option<Arc<AuthProvider>>,
+}
+
+impl PendingSession {
+    ///Blocks until the session has connected or failed to
+    pub fn wait(self) -> Result<Session, CassError> {
+        match unsafe { cass_future_error_code(self.future.inner()) } {
+            CASS_OK => Ok(self.session),
+            CASS_ERROR_SERVER_BAD_CREDENTIALS => {
+                if let Some(ref provider) = self.auth_provider {
+                    provider.on_refused();
+                }
+                Err(CassError::build(CASS_ERROR_SERVER_BAD_CREDENTIALS))
+            }
+            err => Err(CassError::build(err)),
+        }
+    }
+}
+
+///Starts connecting to the cluster, on `keyspace` if given or else on the
+///cluster's keyspace. The driver takes a copy of the cluster's settings, so
+///the cluster is no longer needed once this returns.
+pub fn start_connect(cluster: &Cluster, keyspace: Option<&str>) -> Result<PendingSession, CassError> {
+    let keyspace = keyspace.map(ToOwned::to_owned).or_else(|| cluster.1.keyspace.clone());
+    let keyspace_name = match keyspace {
+        Some(ref keyspace) => {
+            Some(try!(CString::new(&keyspace[..]).map_err(|_| CassError::build(CASS_ERROR_LIB_BAD_PARAMS))))
+        }
+        None => None,
+    };
+    let mut options = cluster.1.clone();
+    options.keyspace = keyspace;
+    unsafe {
+        let session = new_session(cass_session_new(), options);
+        let future = match keyspace_name {
+            Some(keyspace) => {
+                ConnectFuture::build(cass_session_connect_keyspace(session.inner(), cluster.0, keyspace.as_ptr()))
+            }
+            None => ConnectFuture::build(cass_session_connect(session.inner(), cluster.0)),
+        };
+        Ok(PendingSession {
+            future: future,
+            session: session,
+            auth_provider: cluster.2.clone(),
+        })
+    }
+}
+
+///Sets a retry strategy already shared with other clusters.
+pub fn set_shared_retry_strategy(cluster: &mut Cluster, retry_strategy: Arc<RetryStrategy>) {
+    cluster.1.retry_strategy = Some(retry_strategy);
+}
+
+///Sets an authentication provider already shared with other clusters.
+pub fn set_shared_auth_provider(cluster: &mut Cluster,
+                                provider: Arc<AuthProvider>)
+                                -> Result<&mut Cluster, CassError> {
+    let callbacks = authenticator_callbacks();
+    unsafe {
+        let data = provider_data(provider.clone());
+        let result =
+            cass_cluster_set_authenticator_callbacks(cluster.0, &callbacks, Some(provider_data_cleanup), data);
+        match result {
+            CASS_OK => cluster.2 = Some(provider),
+            _ => provider_data_cleanup(data),
+        }
+        CassError::build(result).wrap(cluster)
     }
 }
 
 impl Cluster {
     ///Creates a new cluster
     pub fn new() -> Cluster {
-        unsafe { Cluster(cass_cluster_new()) }
+        unsafe { Cluster::build(cass_cluster_new()) }
     }
 
 
     ///Sets/Appends contact points. This *MUST* be set. The first call sets
     ///the contact points and any subsequent calls appends additional contact
-    ///points. Passing an empty string will clear the contact points. White space
-    ///is striped from the contact points.
+    ///points. Passing an empty set will clear the contact points.
     ///
     /// ```
-    /// Example contact points: "127.0.0.1" "127.0.0.1,127.0.0.2", "server1.domain.com"
+    /// Example contact points: "127.0.0.1" "127.0.0.1,127.0.0.2", "server1.domain.com", "[::1]:9042"
     /// ```
     ///
+    ///The driver connects to every node on the same port, so contact points
+    ///that name a port set the cluster's port, and contact points naming
+    ///different ports are rejected with `CASS_ERROR_LIB_BAD_PARAMS`.
     pub fn set_contact_points<T: Into<ContactPoints>>(&mut self, contact_points: T) -> Result<&mut Self, CassError> {
+        let contact_points = contact_points.into();
+        let port = try!(contact_points.port().map_err(|_| CassError::build(CASS_ERROR_LIB_BAD_PARAMS)));
         unsafe {
-            let s = CString::new(contact_points.into().to_string()).expect("must be utf8");
-            let err = CassError::build(cass_cluster_set_contact_points(self.0, s.as_ptr()));
-            err.wrap(self)
+            let s = CString::new(contact_points.hosts()).expect("must be utf8");
+            try!(CassError::build(cass_cluster_set_contact_points(self.0, s.as_ptr())).wrap(()));
+        }
+        match port {
+            Some(port) => self.set_port(port),
+            None => Ok(self),
         }
     }
 
@@ -154,24 +223,86 @@ impl Cluster {
     }
 
 
+    ///Sets the codecs shared with every session created from this cluster.
+    ///
+    ///Default: codecs for the built-in Rust types
+    pub fn set_codec_registry(&mut self, codecs: CodecRegistry) -> &mut Self {
+        self.1.codecs = Arc::new(codecs);
+        self
+    }
+
+    ///The codecs shared with every session created from this cluster.
+    pub fn codec_registry(&self) -> &CodecRegistry {
+        &self.1.codecs
+    }
+
+    ///Sets the maximum number of statements each session keeps in its
+    ///prepared statement cache.
+    ///
+    ///Default: 1000
+    pub fn set_prepared_cache_capacity(&mut self, capacity: usize) -> &mut Self {
+        self.1.prepared_cache_capacity = capacity;
+        self
+    }
+
+    ///Sets the keyspace sessions connect to.
+    pub fn set_keyspace(&mut self, keyspace: &str) -> &mut Self {
+        self.1.keyspace = Some(keyspace.to_owned());
+        self
+    }
+
+    ///Sets the consistency level of statements and batches that do not set
+    ///their own, for both reads and writes.
+    ///
+    ///Default: the driver's default, `LOCAL_ONE`
+    pub fn set_default_consistency(&mut self, consistency: Consistency) -> &mut Self {
+        self.set_read_consistency(consistency).set_write_consistency(consistency)
+    }
+
+    ///Sets the consistency level of `SELECT` statements that do not set
+    ///their own.
+    pub fn set_read_consistency(&mut self, consistency: Consistency) -> &mut Self {
+        self.1.read_consistency = Some(consistency);
+        self
+    }
+
+    ///Sets the consistency level of other statements, and of batches, that
+    ///do not set their own.
+    pub fn set_write_consistency(&mut self, consistency: Consistency) -> &mut Self {
+        self.1.write_consistency = Some(consistency);
+        self
+    }
+
     /// Sets the SSL context and enables SSL
-    pub fn set_ssl(&mut self, ssl: &mut Ssl) -> &Self {
+    pub fn set_ssl(&mut self, ssl: &mut Ssl) -> &mut Self {
         unsafe {
             cass_cluster_set_ssl(self.0, ssl.inner());
             self
         }
     }
 
+    ///Sets the SSL context built from the settings and enables SSL.
+    ///
+    ///`VerifyMode::PeerIdentityDns` also turns on the driver's reverse DNS
+    ///lookups of nodes' addresses, which it needs to know their host names.
+    pub fn set_ssl_config(&mut self, config: &SslConfig) -> Result<&mut Self, SslError> {
+        let mut ssl = try!(config.build());
+        if config.verify_mode() == VerifyMode::PeerIdentityDns {
+            try!(unsafe { CassError::build(cass_cluster_set_use_hostname_resolution(self.0, cass_true)).wrap(()) }
+                     .map_err(|err| SslError::Driver("hostname resolution", err)));
+        }
+        Ok(self.set_ssl(&mut ssl))
+    }
+
     /// Performs a blocking call to connect to Cassandra cluster
     pub fn connect(&mut self) -> Result<Session, CassError> {
-        unsafe {
-            let session = Session(cass_session_new());
-            let connect_future = ConnectFuture::build(cass_session_connect(session.0, self.0));
-            match cass_future_error_code(connect_future.inner()) {
-                CASS_OK => Ok(session),
-                err => Err(CassError::build(err)),
-            }
-        }
+        start_connect(self, None).and_then(PendingSession::wait)
+    }
+
+    ///Performs a blocking call to connect to the cluster, using `keyspace`
+    ///instead of the cluster's keyspace.
+    pub fn connect_keyspace(&mut self, keyspace: &str) -> Result<Session, CassError> {
+        start_connect(self, Some(keyspace)).and_then(PendingSession::wait)
     }
 
     ///Sets the protocol version. This will automatically downgrade to the lowest
@@ -190,7 +321,7 @@ impl Cluster {
     /// ```
     /// Default: 1
     /// ```
-    pub fn set_num_threads_io(&mut self, num_threads: u32) -> Result<&Self, CassError> {
+    pub fn set_num_threads_io(&mut self, num_threads: u32) -> Result<&mut Self, CassError> {
         unsafe { CassError::build(cass_cluster_set_num_threads_io(self.0, num_threads)).wrap(self) }
     }
 
@@ -246,7 +377,7 @@ impl Cluster {
     /// ```
     /// Default: 1000ms
     /// ```
-    pub fn set_reconnect_wait_time(&mut self, wait_time: u32) -> &Self {
+    pub fn set_reconnect_wait_time(&mut self, wait_time: u32) -> &mut Self {
         unsafe {
             cass_cluster_set_reconnect_wait_time(self.0, wait_time);
         }
@@ -329,7 +460,7 @@ impl Cluster {
     /// ```
     /// Default: 5000ms
     #[allow(cast_possible_truncation,cast_sign_loss)]
-    pub fn set_connect_timeout(&mut self, timeout: Duration) -> &Self {
+    pub fn set_connect_timeout(&mut self, timeout: Duration) -> &mut Self {
         unsafe {
             cass_cluster_set_connect_timeout(self.0, timeout.num_milliseconds() as u32);
         }
@@ -341,7 +472,7 @@ impl Cluster {
     /// ```
     /// Default: 12000ms
     #[allow(cast_possible_truncation,cast_sign_loss)]
-    pub fn set_request_timeout(&mut self, timeout: Duration) -> &Self {
+    pub fn set_request_timeout(&mut self, timeout: Duration) -> &mut Self {
         unsafe {
             cass_cluster_set_request_timeout(self.0, timeout.num_milliseconds() as u32);
         }
@@ -349,20 +480,42 @@ impl Cluster {
     }
 
     ///Sets credentials for plain text authentication.
-    pub fn set_credentials(&mut self, username: &str, password: &str) -> Result<&Self, NulError> {
+    ///
+    ///Fails with `CASS_ERROR_LIB_BAD_PARAMS` if either contains a NUL byte.
+    pub fn set_credentials(&mut self, username: &str, password: &str) -> Result<&mut Self, CassError> {
+        let bad_params = |_: NulError| CassError::build(CASS_ERROR_LIB_BAD_PARAMS);
+        let username = try!(CString::new(username).map_err(&bad_params));
+        let password = try!(CString::new(password).map_err(&bad_params));
         unsafe {
-            cass_cluster_set_credentials(self.0,
-                                         try!(CString::new(username)).as_ptr(),
-                                         try!(CString::new(password)).as_ptr());
+            cass_cluster_set_credentials(self.0, username.as_ptr(), password.as_ptr());
         }
         Ok(self)
     }
 
+    ///Sets a provider of SASL authenticators, for servers using
+    ///authentication schemes other than username and password, such as
+    ///LDAP proxies or tokens. Replaces `set_credentials`.
+    pub fn set_auth_provider<P: AuthProvider + 'static>(&mut self, provider: P) -> Result<&mut Self, CassError> {
+        set_shared_auth_provider(self, Arc::new(provider))
+    }
+
+    ///Sets a provider of the username and password, asked each time a new
+    ///connection authenticates, so that passwords can be rotated while
+    ///sessions stay open. Replaces `set_credentials`.
+    ///
+    ///To count authentication failures, set a `PlainTextAuthProvider` with
+    ///`set_auth_provider` instead and keep a clone of it.
+    pub fn set_credentials_provider<P: CredentialsProvider + 'static>(&mut self,
+                                                                     provider: P)
+                                                                     -> Result<&mut Self, CassError> {
+        self.set_auth_provider(PlainTextAuthProvider::with_provider(provider))
+    }
+
     ///Configures the cluster to use round-robin load balancing.
     ///
     ///The driver discovers all nodes in a cluster and cycles through
     ///them per request. All are considered 'local'.
-    pub fn set_load_balance_round_robin(&mut self) -> &Self {
+    pub fn set_load_balance_round_robin(&mut self) -> &mut Self {
         unsafe {
             cass_cluster_set_load_balance_round_robin(self.0);
             self
@@ -382,7 +535,7 @@ impl Cluster {
     ///points from the local DC.
     pub fn set_load_balance_dc_aware<S>(&mut self, local_dc: &str, used_hosts_per_remote_dc: u32,
         allow_remote_dcs_for_local_cl: cass_bool_t)
-                                        -> Result<&Self, CassError> {
+                                        -> Result<&mut Self, CassError> {
         unsafe {
             CassError::build({
                 let local_dc = CString::new(local_dc).expect("must be utf8");
@@ -408,7 +561,7 @@ impl Cluster {
     ///This routing policy composes the base routing policy, routing
     ///requests first to replicas on nodes considered 'local' by
     ///the base load balancing policy.
-    pub fn set_token_aware_routing(&mut self, enabled: bool) -> &Self {
+    pub fn set_token_aware_routing(&mut self, enabled: bool) -> &mut Self {
         unsafe {
             cass_cluster_set_token_aware_routing(self.0, if enabled { cass_true } else { cass_false });
         }
@@ -424,7 +577,7 @@ impl Cluster {
     /// This routing policy is a top-level routing policy. It uses the
     /// base routing policy to determine locality (dc-aware) and/or
     /// placement (token-aware) before considering the latency.
-    pub fn set_latency_aware_routing(&mut self, enabled: bool) -> &Self {
+    pub fn set_latency_aware_routing(&mut self, enabled: bool) -> &mut Self {
         unsafe {
             cass_cluster_set_latency_aware_routing(self.0, if enabled { cass_true } else { cass_false });
         }
@@ -447,7 +600,7 @@ impl Cluster {
     #[allow(cast_sign_loss)]
     pub fn set_latency_aware_routing_settings(&mut self, exclusion_threshold: f64, scale: Duration,
         retry_period: Duration, update_rate: Duration, min_measured: u64)
-                                              -> &Self {
+                                              -> &mut Self {
         unsafe {
             cass_cluster_set_latency_aware_routing_settings(self.0,
                                                             exclusion_threshold,
@@ -470,10 +623,11 @@ impl Cluster {
     ///
     ///```
     ///Examples: "127.0.0.1" "127.0.0.1,127.0.0.2", "server1.domain.com"
-    pub fn set_whitelist_filtering(&mut self, hosts: Vec<String>) -> &Self {
+    pub fn set_whitelist_filtering(&mut self, hosts: Vec<String>) -> &mut Self {
         // FIXME replace host strings with InetSomethings
+        let hosts = CString::new(hosts.join(",")).expect("must be utf8");
         unsafe {
-            cass_cluster_set_whitelist_filtering(self.0, hosts.join(",").as_ptr() as *const i8);
+            cass_cluster_set_whitelist_filtering(self.0, hosts.as_ptr());
         }
         self
     }
@@ -482,7 +636,7 @@ impl Cluster {
     ///
     /// ```
     ///<b>Default:</b> true (disables Nagel's algorithm).
-    pub fn set_tcp_nodelay(&mut self, enable: bool) -> &Self {
+    pub fn set_tcp_nodelay(&mut self, enable: bool) -> &mut Self {
         unsafe {
             cass_cluster_set_tcp_nodelay(self.0, if enable { cass_true } else { cass_false });
         }
@@ -494,7 +648,7 @@ impl Cluster {
     /// ```
     ///Default: false (disabled).
     #[allow(cast_possible_truncation,cast_sign_loss)]
-    pub fn set_tcp_keepalive(&mut self, enable: bool, delay: Duration) -> &Self {
+    pub fn set_tcp_keepalive(&mut self, enable: bool, delay: Duration) -> &mut Self {
         unsafe {
             cass_cluster_set_tcp_keepalive(self.0,
                                            if enable { cass_true } else { cass_false },
@@ -560,6 +714,16 @@ impl Cluster {
         }
     }
 
+    ///Sets the strategy deciding whether statements executed by sessions of
+    ///this cluster are executed again after failing. It applies on top of
+    ///the driver's retry policy, and not to batches.
+    ///
+    ///Default: none; failures are returned
+    pub fn set_retry_strategy<S: RetryStrategy + 'static>(&mut self, retry_strategy: S) -> &mut Self {
+        self.1.retry_strategy = Some(Arc::new(retry_strategy));
+        self
+    }
+
     ///Enable/Disable retrieving and updating schema metadata. If disabled
     ///this is allows the driver to skip over retrieving and updating schema
     ///metadata, but it also disables the usage of token-aware routing and
@@ -568,7 +732,7 @@ impl Cluster {
     ///
     /// ```
     ///Default: true (enabled).
-    pub fn set_use_schema(&mut self, enabled: bool) -> &Self {
+    pub fn set_use_schema(&mut self, enabled: bool) -> &mut Self {
         unsafe {
             cass_cluster_set_use_schema(self.0, if enabled { cass_true } else { cass_false });
         }
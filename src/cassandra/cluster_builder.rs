@@ -0,0 +1,564 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::fmt;
+use std::sync::Arc;
+
+use time::Duration;
+
+use cassandra::authenticator::AuthProvider;
+use cassandra::cluster::{Cluster, CqlProtocol, set_shared_auth_provider, set_shared_retry_strategy};
+use cassandra::cluster_config::ConfigError;
+use cassandra::codec::CodecRegistry;
+use cassandra::consistency::Consistency;
+use cassandra::contact_points::ContactPoints;
+use cassandra::error::CassError;
+use cassandra::policy::retry::RetryPolicy;
+use cassandra::policy::retry_strategy::RetryStrategy;
+use cassandra::ssl::{Ssl, SslConfig};
+use cassandra::time::TimestampGen;
+
+use cassandra_sys::{cass_false, cass_true};
+
+// the driver's defaults, which cross-field checks compare against when
+// only one side of a constraint is set
+const DEFAULT_CORE_CONNECTIONS: u32 = 1;
+const DEFAULT_MAX_CONNECTIONS: u32 = 2;
+const DEFAULT_WRITE_BYTES_LOW: u32 = 32 * 1024;
+const DEFAULT_WRITE_BYTES_HIGH: u32 = 64 * 1024;
+const DEFAULT_PENDING_REQUESTS_LOW: u32 = 128;
+const DEFAULT_PENDING_REQUESTS_HIGH: u32 = 256;
+
+///How requests are spread over the nodes of the cluster
+#[derive(Clone,Debug,PartialEq)]
+pub enum LoadBalancing {
+    ///Cycles through every node; all are considered local
+    RoundRobin,
+    ///Tries the nodes of the local data center first
+    DcAware {
+        ///The local data center
+        local_dc: String,
+        ///How many nodes of each remote data center are used after the
+        ///local ones
+        used_hosts_per_remote_dc: u32,
+        ///Whether remote nodes are used for `LOCAL_*` consistency levels
+        allow_remote_dcs_for_local_cl: bool,
+    },
+}
+
+///Every problem found building a cluster, each naming the setting at fault
+#[derive(Clone,PartialEq,Eq)]
+pub struct BuildError(Vec<ConfigError>);
+
+impl BuildError {
+    ///The problems, in the order the settings were checked
+    pub fn problems(&self) -> &[ConfigError] {
+        &self.0
+    }
+}
+
+///Fails with the problems found, if there are any
+pub fn check(problems: Vec<ConfigError>) -> Result<(), BuildError> {
+    if problems.is_empty() { Ok(()) } else { Err(BuildError(problems)) }
+}
+
+impl From<ConfigError> for BuildError {
+    fn from(err: ConfigError) -> BuildError {
+        BuildError(vec![err])
+    }
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let problems: Vec<String> = self.0.iter().map(|problem| problem.to_string()).collect();
+        write!(f, "{}", problems.join("; "))
+    }
+}
+
+impl Debug for BuildError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Error for BuildError {
+    fn description(&self) -> &str {
+        "invalid cluster settings"
+    }
+}
+
+///Collects the settings of a `Cluster`, checks them together and applies
+///them once.
+///
+///Settings that are not given keep the driver's defaults.
+///
+/// # Examples
+/// ```no_run
+/// use cassandra::ClusterBuilder;
+///
+/// let cluster = ClusterBuilder::new()
+///                   .contact_points("10.0.0.1,10.0.0.2".parse().unwrap())
+///                   .core_connections_per_host(2)
+///                   .max_connections_per_host(4)
+///                   .build()
+///                   .unwrap();
+/// ```
+#[derive(Default)]
+pub struct ClusterBuilder {
+    contact_points: Option<ContactPoints>,
+    port: Option<u16>,
+    keyspace: Option<String>,
+    credentials: Option<(String, String)>,
+    auth_provider: Option<Arc<AuthProvider>>,
+    consistency: Option<Consistency>,
+    read_consistency: Option<Consistency>,
+    write_consistency: Option<Consistency>,
+    protocol_version: Option<CqlProtocol>,
+    num_threads_io: Option<u32>,
+    queue_size_io: Option<u32>,
+    queue_size_event: Option<u32>,
+    queue_size_log: Option<u32>,
+    core_connections_per_host: Option<u32>,
+    max_connections_per_host: Option<u32>,
+    reconnect_wait_time: Option<u32>,
+    max_concurrent_creation: Option<u32>,
+    max_concurrent_requests_threshold: Option<u32>,
+    max_requests_per_flush: Option<u32>,
+    write_bytes_high_water_mark: Option<u32>,
+    write_bytes_low_water_mark: Option<u32>,
+    pending_requests_high_water_mark: Option<u32>,
+    pending_requests_low_water_mark: Option<u32>,
+    connect_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    load_balancing: Option<LoadBalancing>,
+    token_aware_routing: Option<bool>,
+    latency_aware_routing: Option<bool>,
+    whitelist: Option<Vec<String>>,
+    tcp_nodelay: Option<bool>,
+    tcp_keepalive: Option<(bool, Duration)>,
+    timestamp_gen: Option<TimestampGen>,
+    heartbeat_interval: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    retry_policy: Option<RetryPolicy>,
+    retry_strategy: Option<Arc<RetryStrategy>>,
+    ssl: Option<Ssl>,
+    ssl_config: Option<SslConfig>,
+    use_schema: Option<bool>,
+    prepared_cache_capacity: Option<usize>,
+    codecs: Option<CodecRegistry>,
+}
+
+macro_rules! setting {
+    ($(#[$doc:meta])* $name:ident: $ty:ty) => {
+        $(#[$doc])*
+        pub fn $name(mut self, $name: $ty) -> Self {
+            self.$name = Some($name);
+            self
+        }
+    }
+}
+
+impl ClusterBuilder {
+    ///A builder with no settings
+    pub fn new() -> ClusterBuilder {
+        ClusterBuilder::default()
+    }
+
+    setting!(///The nodes to connect to first. Required.
+             contact_points: ContactPoints);
+    setting!(///The native protocol port of every node
+             port: u16);
+    setting!(///The keyspace sessions connect to
+             keyspace: String);
+    setting!(///The consistency level of statements that do not set their own
+             consistency: Consistency);
+    setting!(///The consistency level of reads that do not set their own,
+             ///overriding `consistency`
+             read_consistency: Consistency);
+    setting!(///The consistency level of writes that do not set their own,
+             ///overriding `consistency`
+             write_consistency: Consistency);
+    setting!(///The protocol version
+             protocol_version: CqlProtocol);
+    setting!(///The number of IO threads
+             num_threads_io: u32);
+    setting!(///The size of the queue of pending requests
+             queue_size_io: u32);
+    setting!(///The size of the queue of events
+             queue_size_event: u32);
+    setting!(///The size of the queue of log messages
+             queue_size_log: u32);
+    setting!(///The number of connections to each node per IO thread
+             core_connections_per_host: u32);
+    setting!(///The maximum number of connections to each node per IO thread
+             max_connections_per_host: u32);
+    setting!(///The milliseconds to wait before reconnecting
+             reconnect_wait_time: u32);
+    setting!(///The maximum number of connections created at once
+             max_concurrent_creation: u32);
+    setting!(///The in-flight requests on a connection before another is opened
+             max_concurrent_requests_threshold: u32);
+    setting!(///The maximum number of requests an IO worker sends per flush
+             max_requests_per_flush: u32);
+    setting!(///The outstanding bytes above which writes to a connection stop
+             write_bytes_high_water_mark: u32);
+    setting!(///The outstanding bytes below which writes to a connection resume
+             write_bytes_low_water_mark: u32);
+    setting!(///The queued requests above which writes to a node stop
+             pending_requests_high_water_mark: u32);
+    setting!(///The queued requests below which writes to a node resume
+             pending_requests_low_water_mark: u32);
+    setting!(///The timeout for connecting to a node
+             connect_timeout: Duration);
+    setting!(///The timeout for a response from a node
+             request_timeout: Duration);
+    setting!(///The load balancing policy
+             load_balancing: LoadBalancing);
+    setting!(///Whether requests are routed to the replicas of their partition
+             token_aware_routing: bool);
+    setting!(///Whether requests avoid nodes with high latency
+             latency_aware_routing: bool);
+    setting!(///The only hosts the driver may connect to
+             whitelist: Vec<String>);
+    setting!(///Whether Nagle's algorithm is disabled
+             tcp_nodelay: bool);
+    setting!(///The timestamp generator of requests
+             timestamp_gen: TimestampGen);
+    setting!(///The time between heartbeats on an idle connection
+             heartbeat_interval: Duration);
+    setting!(///The time without a heartbeat response before a connection is dropped
+             idle_timeout: Duration);
+    setting!(///The default retry policy
+             retry_policy: RetryPolicy);
+    setting!(///The SSL context; enables SSL
+             ssl: Ssl);
+    setting!(///SSL settings, checked when built; enables SSL
+             ssl_config: SslConfig);
+    setting!(///Whether schema metadata is retrieved
+             use_schema: bool);
+    setting!(///The size of each session's prepared statement cache
+             prepared_cache_capacity: usize);
+    setting!(///The codecs shared with every session
+             codecs: CodecRegistry);
+
+    ///Credentials for plain text authentication
+    pub fn credentials(mut self, username: &str, password: &str) -> Self {
+        self.credentials = Some((username.to_owned(), password.to_owned()));
+        self
+    }
+
+    ///A provider of SASL authenticators, instead of credentials
+    pub fn auth_provider<P: AuthProvider + 'static>(mut self, auth_provider: P) -> Self {
+        self.auth_provider = Some(Arc::new(auth_provider));
+        self
+    }
+
+    ///The strategy deciding whether failed statements are executed again
+    pub fn retry_strategy<S: RetryStrategy + 'static>(mut self, retry_strategy: S) -> Self {
+        self.retry_strategy = Some(Arc::new(retry_strategy));
+        self
+    }
+
+    ///TCP keep-alive, probing after `delay` of inactivity
+    pub fn tcp_keepalive(mut self, enabled: bool, delay: Duration) -> Self {
+        self.tcp_keepalive = Some((enabled, delay));
+        self
+    }
+
+    ///Checks the settings without creating a cluster.
+    pub fn validate(&self) -> Result<(), BuildError> {
+        let mut problems = vec![];
+        {
+            let mut problem = |key: &str, message: String| problems.push(ConfigError::new(key, message));
+            match self.contact_points {
+                None => problem("contact_points", "must be set".to_owned()),
+                Some(ref points) if points.points().is_empty() => {
+                    problem("contact_points", "must not be empty".to_owned())
+                }
+                Some(ref points) => {
+                    match (points.port(), self.port) {
+                        (Err(err), _) => problem("contact_points", err.to_string()),
+                        (Ok(Some(point_port)), Some(port)) if point_port != port => {
+                            problem("port", format!("{} conflicts with contact point port {}", port, point_port))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            if self.port == Some(0) {
+                problem("port", "must not be 0".to_owned());
+            }
+            if let Some((ref username, ref password)) = self.credentials {
+                if username.contains('\0') || password.contains('\0') {
+                    problem("credentials", "must not contain NUL bytes".to_owned());
+                }
+                if self.auth_provider.is_some() {
+                    problem("credentials", "conflicts with auth_provider".to_owned());
+                }
+            }
+            if self.keyspace.as_ref().map_or(false, |keyspace| keyspace.is_empty() || keyspace.contains('\0')) {
+                problem("keyspace", "must be a keyspace name".to_owned());
+            }
+            for &(key, value) in &[("num_threads_io", self.num_threads_io),
+                                   ("queue_size_io", self.queue_size_io),
+                                   ("queue_size_event", self.queue_size_event),
+                                   ("queue_size_log", self.queue_size_log),
+                                   ("core_connections_per_host", self.core_connections_per_host),
+                                   ("max_connections_per_host", self.max_connections_per_host)] {
+                if value == Some(0) {
+                    problem(key, "must be at least 1".to_owned());
+                }
+            }
+            for &(key, value) in &[("connect_timeout", self.connect_timeout),
+                                   ("request_timeout", self.request_timeout),
+                                   ("heartbeat_interval", self.heartbeat_interval),
+                                   ("idle_timeout", self.idle_timeout),
+                                   ("tcp_keepalive", self.tcp_keepalive.map(|(_, delay)| delay))] {
+                if value.map_or(false, |value| value < Duration::zero()) {
+                    problem(key, "must not be negative".to_owned());
+                }
+            }
+
+            // connection counts may be equal, water marks must differ
+            for &(low_key, low, low_default, high_key, high, high_default, strict) in
+                &[("core_connections_per_host",
+                   self.core_connections_per_host,
+                   DEFAULT_CORE_CONNECTIONS,
+                   "max_connections_per_host",
+                   self.max_connections_per_host,
+                   DEFAULT_MAX_CONNECTIONS,
+                   false),
+                  ("write_bytes_low_water_mark",
+                   self.write_bytes_low_water_mark,
+                   DEFAULT_WRITE_BYTES_LOW,
+                   "write_bytes_high_water_mark",
+                   self.write_bytes_high_water_mark,
+                   DEFAULT_WRITE_BYTES_HIGH,
+                   true),
+                  ("pending_requests_low_water_mark",
+                   self.pending_requests_low_water_mark,
+                   DEFAULT_PENDING_REQUESTS_LOW,
+                   "pending_requests_high_water_mark",
+                   self.pending_requests_high_water_mark,
+                   DEFAULT_PENDING_REQUESTS_HIGH,
+                   true)] {
+                if low.is_none() && high.is_none() {
+                    continue;
+                }
+                let (low, high) = (low.unwrap_or(low_default), high.unwrap_or(high_default));
+                if strict && low >= high {
+                    problem(low_key, format!("{} must be below {} {}", low, high_key, high));
+                } else if low > high {
+                    problem(low_key, format!("{} must be at most {} {}", low, high_key, high));
+                }
+            }
+
+            if let Some(LoadBalancing::DcAware { ref local_dc,
+                                                 used_hosts_per_remote_dc,
+                                                 allow_remote_dcs_for_local_cl }) = self.load_balancing {
+                if local_dc.is_empty() || local_dc.contains('\0') {
+                    problem("local_dc", "must be a data center name".to_owned());
+                }
+                if allow_remote_dcs_for_local_cl && used_hosts_per_remote_dc == 0 {
+                    problem("allow_remote_dcs_for_local_cl",
+                            "needs used_hosts_per_remote_dc to be at least 1".to_owned());
+                }
+            }
+            if self.whitelist.as_ref().map_or(false, |hosts| hosts.iter().any(|host| host.contains('\0'))) {
+                problem("whitelist", "must not contain NUL bytes".to_owned());
+            }
+            if self.ssl.is_some() && self.ssl_config.is_some() {
+                problem("ssl_config", "conflicts with ssl".to_owned());
+            }
+        }
+        check(problems)
+    }
+
+    ///Validates the settings and applies them to a new cluster.
+    pub fn build(self) -> Result<Cluster, BuildError> {
+        try!(self.validate());
+        let mut cluster = Cluster::new();
+        let mut problems = vec![];
+        {
+            let mut applied = |key: &str, result: Result<&mut Cluster, CassError>| {
+                if let Err(err) = result {
+                    problems.push(ConfigError::new(key, err.to_string()));
+                }
+            };
+            if let Some(contact_points) = self.contact_points {
+                applied("contact_points", cluster.set_contact_points(contact_points));
+            }
+            if let Some(port) = self.port {
+                applied("port", cluster.set_port(port));
+            }
+            if let Some(ref keyspace) = self.keyspace {
+                cluster.set_keyspace(keyspace);
+            }
+            if let Some((ref username, ref password)) = self.credentials {
+                applied("credentials", cluster.set_credentials(username, password));
+            }
+            if let Some(auth_provider) = self.auth_provider {
+                applied("auth_provider", set_shared_auth_provider(&mut cluster, auth_provider));
+            }
+            if let Some(consistency) = self.consistency {
+                cluster.set_default_consistency(consistency);
+            }
+            if let Some(consistency) = self.read_consistency {
+                cluster.set_read_consistency(consistency);
+            }
+            if let Some(consistency) = self.write_consistency {
+                cluster.set_write_consistency(consistency);
+            }
+            if let Some(version) = self.protocol_version {
+                applied("protocol_version", cluster.set_protocol_version(version));
+            }
+            if let Some(threads) = self.num_threads_io {
+                applied("num_threads_io", cluster.set_num_threads_io(threads));
+            }
+            if let Some(size) = self.queue_size_io {
+                applied("queue_size_io", cluster.set_queue_size_io(size));
+            }
+            if let Some(size) = self.queue_size_event {
+                applied("queue_size_event", cluster.set_queue_size_event(size));
+            }
+            if let Some(size) = self.queue_size_log {
+                applied("queue_size_log", cluster.set_queue_size_log(size));
+            }
+            // raise the maximum before the core count so that the driver
+            // never sees core > max in between
+            if let Some(connections) = self.max_connections_per_host {
+                applied("max_connections_per_host", cluster.set_max_connections_per_host(connections));
+            }
+            if let Some(connections) = self.core_connections_per_host {
+                applied("core_connections_per_host", cluster.set_core_connections_per_host(connections));
+            }
+            if let Some(wait_time) = self.reconnect_wait_time {
+                cluster.set_reconnect_wait_time(wait_time);
+            }
+            if let Some(connections) = self.max_concurrent_creation {
+                applied("max_concurrent_creation", cluster.set_max_concurrent_creation(connections));
+            }
+            if let Some(requests) = self.max_concurrent_requests_threshold {
+                applied("max_concurrent_requests_threshold",
+                        cluster.set_max_concurrent_requests_threshold(requests));
+            }
+            if let Some(requests) = self.max_requests_per_flush {
+                applied("max_requests_per_flush", cluster.set_max_requests_per_flush(requests));
+            }
+            if let Some(bytes) = self.write_bytes_high_water_mark {
+                applied("write_bytes_high_water_mark", cluster.set_write_bytes_high_water_mark(bytes));
+            }
+            if let Some(bytes) = self.write_bytes_low_water_mark {
+                applied("write_bytes_low_water_mark", cluster.set_write_bytes_low_water_mark(bytes));
+            }
+            if let Some(requests) = self.pending_requests_high_water_mark {
+                applied("pending_requests_high_water_mark",
+                        cluster.set_pending_requests_high_water_mark(requests));
+            }
+            if let Some(requests) = self.pending_requests_low_water_mark {
+                applied("pending_requests_low_water_mark", cluster.set_pending_requests_low_water_mark(requests));
+            }
+            if let Some(timeout) = self.connect_timeout {
+                cluster.set_connect_timeout(timeout);
+            }
+            if let Some(timeout) = self.request_timeout {
+                cluster.set_request_timeout(timeout);
+            }
+            match self.load_balancing {
+                Some(LoadBalancing::RoundRobin) => {
+                    cluster.set_load_balance_round_robin();
+                }
+                Some(LoadBalancing::DcAware { ref local_dc,
+                                              used_hosts_per_remote_dc,
+                                              allow_remote_dcs_for_local_cl }) => {
+                    let allow_remote = if allow_remote_dcs_for_local_cl { cass_true } else { cass_false };
+                    applied("local_dc",
+                            cluster.set_load_balance_dc_aware::<()>(local_dc, used_hosts_per_remote_dc, allow_remote));
+                }
+                None => {}
+            }
+            if let Some(enabled) = self.token_aware_routing {
+                cluster.set_token_aware_routing(enabled);
+            }
+            if let Some(enabled) = self.latency_aware_routing {
+                cluster.set_latency_aware_routing(enabled);
+            }
+            if let Some(hosts) = self.whitelist {
+                cluster.set_whitelist_filtering(hosts);
+            }
+            if let Some(enabled) = self.tcp_nodelay {
+                cluster.set_tcp_nodelay(enabled);
+            }
+            if let Some((enabled, delay)) = self.tcp_keepalive {
+                cluster.set_tcp_keepalive(enabled, delay);
+            }
+            if let Some(ref timestamp_gen) = self.timestamp_gen {
+                cluster.set_timestamp_gen(timestamp_gen);
+            }
+            if let Some(interval) = self.heartbeat_interval {
+                cluster.set_connection_heartbeat_interval(interval);
+            }
+            if let Some(timeout) = self.idle_timeout {
+                cluster.set_connection_idle_timeout(timeout);
+            }
+            if let Some(retry_policy) = self.retry_policy {
+                cluster.set_retry_policy(retry_policy);
+            }
+            if let Some(retry_strategy) = self.retry_strategy {
+                set_shared_retry_strategy(&mut cluster, retry_strategy);
+            }
+            if let Some(mut ssl) = self.ssl {
+                cluster.set_ssl(&mut ssl);
+            }
+            if let Some(enabled) = self.use_schema {
+                cluster.set_use_schema(enabled);
+            }
+            if let Some(capacity) = self.prepared_cache_capacity {
+                cluster.set_prepared_cache_capacity(capacity);
+            }
+            if let Some(codecs) = self.codecs {
+                cluster.set_codec_registry(codecs);
+            }
+        }
+        if let Some(ref ssl_config) = self.ssl_config {
+            if let Err(err) = cluster.set_ssl_config(ssl_config) {
+                problems.push(ConfigError::new("ssl_config", err.to_string()));
+            }
+        }
+        if problems.is_empty() { Ok(cluster) } else { Err(BuildError(problems)) }
+    }
+}
+
+#[test]
+fn test_cluster_builder_validate() {
+    let keys = |builder: ClusterBuilder| -> Vec<String> {
+        builder.validate().unwrap_err().problems().iter().map(|problem| problem.key.clone()).collect()
+    };
+    let points = |points: &str| points.parse::<ContactPoints>().unwrap();
+
+    assert!(ClusterBuilder::new().contact_points(points("127.0.0.1")).validate().is_ok());
+    assert_eq!(keys(ClusterBuilder::new()), vec!["contact_points"]);
+    assert_eq!(keys(ClusterBuilder::new()
+                        .contact_points(points("127.0.0.1:9042"))
+                        .port(9043)
+                        .core_connections_per_host(4)
+                        .write_bytes_low_water_mark(64 * 1024)
+                        .pending_requests_low_water_mark(100)
+                        .pending_requests_high_water_mark(50)
+                        .load_balancing(LoadBalancing::DcAware {
+                            local_dc: "".to_owned(),
+                            used_hosts_per_remote_dc: 0,
+                            allow_remote_dcs_for_local_cl: true,
+                        })),
+               vec!["port",
+                    "core_connections_per_host",
+                    "write_bytes_low_water_mark",
+                    "pending_requests_low_water_mark",
+                    "local_dc",
+                    "allow_remote_dcs_for_local_cl"]);
+    assert!(ClusterBuilder::new()
+                .contact_points(points("127.0.0.1"))
+                .core_connections_per_host(4)
+                .max_connections_per_host(4)
+                .validate()
+                .is_ok());
+}
@@ -0,0 +1,566 @@
+use std::env;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::fmt;
+use std::str::FromStr;
+
+use time::Duration;
+
+use cassandra::cluster::{Cluster, CqlProtocol};
+use cassandra::cluster_builder::{BuildError, ClusterBuilder, LoadBalancing, check};
+use cassandra::contact_points::ContactPoints;
+use cassandra::consistency::Consistency;
+use cassandra::policy::retry::RetryPolicy;
+use cassandra::ssl::{SslConfig, SslError, VerifyMode};
+
+///The settings of a `Cluster`, as read from a configuration file, the
+///environment or a connection URL.
+///
+///Every setting is optional and left at the driver's default when missing.
+///Keys are the field names, both in files (any serde format, such as TOML,
+///YAML or JSON) and in URL query strings; environment variables are the
+///upper-cased keys prefixed with `CASSANDRA_`.
+#[derive(Clone,Debug,Default,PartialEq,Serialize,Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClusterConfig {
+    ///Hosts to connect to first
+    pub contact_points: Vec<String>,
+    ///Port of the contact points
+    pub port: Option<u16>,
+    ///Keyspace sessions connect to
+    pub keyspace: Option<String>,
+    ///Username for plain text authentication
+    pub username: Option<String>,
+    ///Password for plain text authentication
+    pub password: Option<String>,
+    ///Default consistency of requests, such as `local_quorum`
+    pub consistency: Option<Consistency>,
+    ///Default consistency of reads, overriding `consistency`
+    pub read_consistency: Option<Consistency>,
+    ///Default consistency of writes, overriding `consistency`
+    pub write_consistency: Option<Consistency>,
+    ///`round_robin` or `dc_aware`
+    pub load_balancing: Option<String>,
+    ///Local data center of DC-aware load balancing
+    pub local_dc: Option<String>,
+    ///Hosts per remote data center that DC-aware load balancing may use
+    pub used_hosts_per_remote_dc: Option<u32>,
+    ///Whether remote hosts may serve requests at a local consistency level
+    pub allow_remote_dcs_for_local_cl: Option<bool>,
+    ///Whether requests are routed to replicas of their partition
+    pub token_aware_routing: Option<bool>,
+    ///Whether requests avoid hosts that respond slowly
+    pub latency_aware_routing: Option<bool>,
+    ///Timeout for connecting to a node, in milliseconds
+    pub connect_timeout_ms: Option<u32>,
+    ///Timeout for a node's response, in milliseconds
+    pub request_timeout_ms: Option<u32>,
+    ///Number of IO threads
+    pub num_threads_io: Option<u32>,
+    ///Connections opened to each host per IO thread
+    pub core_connections_per_host: Option<u32>,
+    ///Maximum connections to each host per IO thread
+    pub max_connections_per_host: Option<u32>,
+    ///Whether connections use SSL
+    pub ssl: Option<bool>,
+    ///Paths of PEM certificates or bundles trusted to sign the nodes'
+    ///certificates
+    pub ssl_trusted_certs: Vec<String>,
+    ///Path of the PEM client certificate chain
+    pub ssl_cert: Option<String>,
+    ///Path of the PEM client private key
+    pub ssl_private_key: Option<String>,
+    ///Password of the client private key
+    pub ssl_private_key_password: Option<String>,
+    ///`none`, `peer_cert`, `peer_identity` or `peer_identity_dns`.
+    ///
+    ///<b>Default:</b> `peer_cert`, which needs `ssl_trusted_certs`. Without
+    ///them SSL is only accepted with an explicit `none`.
+    pub ssl_verify: Option<VerifyMode>,
+    ///`default`, `downgrading_consistency` or `fallthrough`
+    pub retry_policy: Option<String>,
+    ///Whether retry decisions are logged
+    pub retry_logging: Option<bool>,
+    ///Native protocol version, 1 to 4
+    pub protocol_version: Option<i32>,
+}
+
+///An invalid configuration setting
+#[derive(Clone,PartialEq,Eq)]
+pub struct ConfigError {
+    ///The offending key
+    pub key: String,
+    ///What is wrong with it
+    pub message: String,
+}
+
+impl ConfigError {
+    ///An error in the setting `key`
+    pub fn new<S: Into<String>>(key: &str, message: S) -> ConfigError {
+        ConfigError {
+            key: key.to_owned(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "invalid `{}`: {}", self.key, self.message)
+    }
+}
+
+impl Debug for ConfigError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Error for ConfigError {
+    fn description(&self) -> &str {
+        "invalid cluster configuration"
+    }
+}
+
+fn parse<T: FromStr>(key: &str, value: &str) -> Result<T, ConfigError> {
+    value.trim().parse().map_err(|_| ConfigError::new(key, format!("cannot parse `{}`", value)))
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, ConfigError> {
+    match &value.trim().to_lowercase()[..] {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err(ConfigError::new(key, format!("expected true or false, got `{}`", value))),
+    }
+}
+
+fn parse_consistency(key: &str, value: &str) -> Result<Consistency, ConfigError> {
+    Consistency::from_str(value).map_err(|err| ConfigError::new(key, err.to_string()))
+}
+
+fn parse_verify_mode(key: &str, value: &str) -> Result<VerifyMode, ConfigError> {
+    VerifyMode::from_str(value).map_err(|err| ConfigError::new(key, err.to_string()))
+}
+
+fn list(value: &str) -> Vec<String> {
+    value.split(',').map(|item| item.trim().to_owned()).filter(|item| !item.is_empty()).collect()
+}
+
+fn one_of(key: &str, value: &Option<String>, allowed: &[&str], problems: &mut Vec<ConfigError>) {
+    if let Some(ref value) = *value {
+        if !allowed.contains(&&value[..]) {
+            problems.push(ConfigError::new(key, format!("expected one of {}, got `{}`", allowed.join(", "), value)));
+        }
+    }
+}
+
+fn percent_decode(key: &str, value: &str) -> Result<String, ConfigError> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = if bytes.len() >= i + 3 {
+                    String::from_utf8(bytes[i + 1..i + 3].to_vec()).ok()
+                } else {
+                    None
+                };
+                match hex.and_then(|hex| u8::from_str_radix(&hex, 16).ok()) {
+                    Some(byte) => decoded.push(byte),
+                    None => return Err(ConfigError::new(key, "invalid percent encoding")),
+                }
+                i += 3;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| ConfigError::new(key, "not valid UTF-8"))
+}
+
+const PREFIX: &'static str = "CASSANDRA_";
+const KEYS: &'static [&'static str] = &["contact_points",
+                                        "port",
+                                        "keyspace",
+                                        "username",
+                                        "password",
+                                        "consistency",
+                                        "read_consistency",
+                                        "write_consistency",
+                                        "load_balancing",
+                                        "local_dc",
+                                        "used_hosts_per_remote_dc",
+                                        "allow_remote_dcs_for_local_cl",
+                                        "token_aware_routing",
+                                        "latency_aware_routing",
+                                        "connect_timeout_ms",
+                                        "request_timeout_ms",
+                                        "num_threads_io",
+                                        "core_connections_per_host",
+                                        "max_connections_per_host",
+                                        "ssl",
+                                        "ssl_trusted_certs",
+                                        "ssl_cert",
+                                        "ssl_private_key",
+                                        "ssl_private_key_password",
+                                        "ssl_verify",
+                                        "retry_policy",
+                                        "retry_logging",
+                                        "protocol_version"];
+
+impl ClusterConfig {
+    ///Sets a setting from its string form. Lists are comma separated.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<&mut Self, ConfigError> {
+        let text = || Some(value.to_owned());
+        match key {
+            "contact_points" => self.contact_points = list(value),
+            "port" => self.port = Some(try!(parse(key, value))),
+            "keyspace" => self.keyspace = text(),
+            "username" => self.username = text(),
+            "password" => self.password = text(),
+            "consistency" => self.consistency = Some(try!(parse_consistency(key, value))),
+            "read_consistency" => self.read_consistency = Some(try!(parse_consistency(key, value))),
+            "write_consistency" => self.write_consistency = Some(try!(parse_consistency(key, value))),
+            "load_balancing" => self.load_balancing = Some(value.to_lowercase()),
+            "local_dc" | "dc" => self.local_dc = text(),
+            "used_hosts_per_remote_dc" => self.used_hosts_per_remote_dc = Some(try!(parse(key, value))),
+            "allow_remote_dcs_for_local_cl" => self.allow_remote_dcs_for_local_cl = Some(try!(parse_bool(key, value))),
+            "token_aware_routing" => self.token_aware_routing = Some(try!(parse_bool(key, value))),
+            "latency_aware_routing" => self.latency_aware_routing = Some(try!(parse_bool(key, value))),
+            "connect_timeout_ms" => self.connect_timeout_ms = Some(try!(parse(key, value))),
+            "request_timeout_ms" => self.request_timeout_ms = Some(try!(parse(key, value))),
+            "num_threads_io" => self.num_threads_io = Some(try!(parse(key, value))),
+            "core_connections_per_host" => self.core_connections_per_host = Some(try!(parse(key, value))),
+            "max_connections_per_host" => self.max_connections_per_host = Some(try!(parse(key, value))),
+            "ssl" => self.ssl = Some(try!(parse_bool(key, value))),
+            "ssl_trusted_certs" => self.ssl_trusted_certs = list(value),
+            "ssl_cert" => self.ssl_cert = text(),
+            "ssl_private_key" => self.ssl_private_key = text(),
+            "ssl_private_key_password" => self.ssl_private_key_password = text(),
+            "ssl_verify" => self.ssl_verify = Some(try!(parse_verify_mode(key, value))),
+            "retry_policy" => self.retry_policy = Some(value.to_lowercase()),
+            "retry_logging" => self.retry_logging = Some(try!(parse_bool(key, value))),
+            "protocol_version" => self.protocol_version = Some(try!(parse(key, value))),
+            _ => return Err(ConfigError::new(key, "unknown setting")),
+        }
+        Ok(self)
+    }
+
+    ///Reads the settings from the `CASSANDRA_*` environment variables, such
+    ///as `CASSANDRA_CONTACT_POINTS` and `CASSANDRA_LOCAL_DC`.
+    pub fn from_env() -> Result<ClusterConfig, ConfigError> {
+        let mut config = ClusterConfig::default();
+        try!(config.merge_env());
+        Ok(config)
+    }
+
+    ///Overrides settings with those set in `CASSANDRA_*` environment
+    ///variables.
+    pub fn merge_env(&mut self) -> Result<&mut Self, ConfigError> {
+        for key in KEYS {
+            if let Ok(value) = env::var(format!("{}{}", PREFIX, key.to_uppercase())) {
+                try!(self.set(key, &value));
+            }
+        }
+        Ok(self)
+    }
+
+    ///Parses a connection URL such as
+    ///`cassandra://user:pass@h1,h2:9042/ks?consistency=local_quorum&dc=eu1&ssl=true`.
+    ///
+    ///Query parameters are settings keys; `dc` is short for `local_dc`.
+    pub fn from_url(url: &str) -> Result<ClusterConfig, ConfigError> {
+        let rest = match url.find("://") {
+            Some(index) if &url[..index] == "cassandra" => &url[index + 3..],
+            _ => return Err(ConfigError::new("url", "expected a cassandra:// URL")),
+        };
+        let (rest, query) = match rest.find('?') {
+            Some(index) => (&rest[..index], Some(&rest[index + 1..])),
+            None => (rest, None),
+        };
+        let (authority, keyspace) = match rest.find('/') {
+            Some(index) => (&rest[..index], Some(&rest[index + 1..])),
+            None => (rest, None),
+        };
+        let (credentials, hosts) = match authority.rfind('@') {
+            Some(index) => (Some(&authority[..index]), &authority[index + 1..]),
+            None => (None, authority),
+        };
+
+        let mut config = ClusterConfig::default();
+        if let Some(credentials) = credentials {
+            let (username, password) = match credentials.find(':') {
+                Some(index) => (&credentials[..index], Some(&credentials[index + 1..])),
+                None => (credentials, None),
+            };
+            config.username = Some(try!(percent_decode("username", username)));
+            if let Some(password) = password {
+                config.password = Some(try!(percent_decode("password", password)));
+            }
+        }
+        let hosts = match hosts.rfind(':') {
+            Some(index) if !hosts[index..].contains(']') => {
+                config.port = Some(try!(parse("port", &hosts[index + 1..])));
+                &hosts[..index]
+            }
+            _ => hosts,
+        };
+        config.contact_points = list(hosts);
+        if let Some(keyspace) = keyspace {
+            if !keyspace.is_empty() {
+                config.keyspace = Some(try!(percent_decode("keyspace", keyspace)));
+            }
+        }
+        for pair in query.into_iter().flat_map(|query| query.split('&')).filter(|pair| !pair.is_empty()) {
+            let (key, value) = match pair.find('=') {
+                Some(index) => (&pair[..index], &pair[index + 1..]),
+                None => (pair, "true"),
+            };
+            let value = try!(percent_decode(key, value));
+            try!(config.set(key, &value));
+        }
+        Ok(config)
+    }
+
+    ///Checks the settings that can be checked without connecting, reporting
+    ///every invalid one.
+    pub fn validate(&self) -> Result<(), BuildError> {
+        let mut problems = vec![];
+        if self.contact_points.is_empty() {
+            problems.push(ConfigError::new("contact_points", "at least one contact point is required"));
+        }
+        one_of("load_balancing",
+               &self.load_balancing,
+               &["round_robin", "dc_aware"],
+               &mut problems);
+        one_of("retry_policy",
+               &self.retry_policy,
+               &["default", "downgrading_consistency", "fallthrough"],
+               &mut problems);
+        if self.load_balancing.as_ref().map_or(false, |policy| policy == "round_robin") && self.local_dc.is_some() {
+            problems.push(ConfigError::new("local_dc", "only applies to dc_aware load balancing"));
+        }
+        if let Some(version) = self.protocol_version {
+            if version < 1 || version > 4 {
+                problems.push(ConfigError::new("protocol_version", format!("expected 1 to 4, got {}", version)));
+            }
+        }
+        // the peer's certificate is verified unless that is explicitly turned
+        // off, and cannot be without trusted certificates
+        if self.ssl_enabled() && self.ssl_trusted_certs.is_empty() && self.ssl_verify != Some(VerifyMode::None) {
+            problems.push(ConfigError::new("ssl_trusted_certs",
+                                           "required to verify the nodes' certificates, unless ssl_verify is none"));
+        }
+        check(problems)
+    }
+
+    fn ssl_enabled(&self) -> bool {
+        self.ssl.unwrap_or(!self.ssl_trusted_certs.is_empty() || self.ssl_cert.is_some())
+    }
+}
+
+impl FromStr for ClusterConfig {
+    type Err = ConfigError;
+
+    fn from_str(url: &str) -> Result<ClusterConfig, ConfigError> {
+        ClusterConfig::from_url(url)
+    }
+}
+
+fn ssl_config_of(config: &ClusterConfig, problems: &mut Vec<ConfigError>) -> SslConfig {
+    let mut ssl = SslConfig::new();
+    {
+        let mut invalid = |key: &str, result: Result<&mut SslConfig, SslError>| {
+            if let Err(err) = result {
+                problems.push(ConfigError::new(key, err.to_string()));
+            }
+        };
+        for path in &config.ssl_trusted_certs {
+            invalid("ssl_trusted_certs", ssl.add_trusted_certs_file(path));
+        }
+        if let Some(ref path) = config.ssl_cert {
+            invalid("ssl_cert", ssl.set_cert_chain_file(path));
+        }
+        if let Some(ref path) = config.ssl_private_key {
+            let password = config.ssl_private_key_password.as_ref().map(|password| &password[..]);
+            invalid("ssl_private_key", ssl.set_private_key_file(path, password));
+        }
+    }
+    ssl.set_verify_mode(config.ssl_verify.unwrap_or(VerifyMode::PeerCert));
+    ssl
+}
+
+impl ClusterConfig {
+    ///A cluster builder with these settings, reporting every invalid one.
+    pub fn builder(&self) -> Result<ClusterBuilder, BuildError> {
+        let mut problems = self.validate().err().map_or_else(Vec::new, |err| err.problems().to_vec());
+        let contact_points = match ContactPoints::from_str(&self.contact_points.join(",")) {
+            Ok(contact_points) => Some(contact_points),
+            Err(err) => {
+                problems.push(ConfigError::new("contact_points", err.to_string()));
+                None
+            }
+        };
+        let ssl = if self.ssl_enabled() { Some(ssl_config_of(self, &mut problems)) } else { None };
+        try!(check(problems));
+
+        let mut builder = ClusterBuilder::new();
+        if let Some(contact_points) = contact_points {
+            builder = builder.contact_points(contact_points);
+        }
+        if let Some(port) = self.port {
+            builder = builder.port(port);
+        }
+        if let Some(ref keyspace) = self.keyspace {
+            builder = builder.keyspace(keyspace.clone());
+        }
+        if let Some(ref username) = self.username {
+            builder = builder.credentials(username, self.password.as_ref().map_or("", |password| &password[..]));
+        }
+        if let Some(consistency) = self.consistency {
+            builder = builder.consistency(consistency);
+        }
+        if let Some(consistency) = self.read_consistency {
+            builder = builder.read_consistency(consistency);
+        }
+        if let Some(consistency) = self.write_consistency {
+            builder = builder.write_consistency(consistency);
+        }
+
+        let dc_aware = self.load_balancing.as_ref().map_or(self.local_dc.is_some(), |policy| policy == "dc_aware");
+        if dc_aware {
+            builder = builder.load_balancing(LoadBalancing::DcAware {
+                local_dc: self.local_dc.clone().unwrap_or_default(),
+                used_hosts_per_remote_dc: self.used_hosts_per_remote_dc.unwrap_or(0),
+                allow_remote_dcs_for_local_cl: self.allow_remote_dcs_for_local_cl.unwrap_or(false),
+            });
+        } else if self.load_balancing.is_some() {
+            builder = builder.load_balancing(LoadBalancing::RoundRobin);
+        }
+        if let Some(enabled) = self.token_aware_routing {
+            builder = builder.token_aware_routing(enabled);
+        }
+        if let Some(enabled) = self.latency_aware_routing {
+            builder = builder.latency_aware_routing(enabled);
+        }
+
+        if let Some(timeout) = self.connect_timeout_ms {
+            builder = builder.connect_timeout(Duration::milliseconds(timeout as i64));
+        }
+        if let Some(timeout) = self.request_timeout_ms {
+            builder = builder.request_timeout(Duration::milliseconds(timeout as i64));
+        }
+        if let Some(threads) = self.num_threads_io {
+            builder = builder.num_threads_io(threads);
+        }
+        if let Some(connections) = self.core_connections_per_host {
+            builder = builder.core_connections_per_host(connections);
+        }
+        if let Some(connections) = self.max_connections_per_host {
+            builder = builder.max_connections_per_host(connections);
+        }
+
+        if let Some(ssl) = ssl {
+            builder = builder.ssl_config(ssl);
+        }
+
+        if self.retry_policy.is_some() || self.retry_logging.is_some() {
+            let policy = match self.retry_policy.as_ref().map(|policy| &policy[..]) {
+                Some("downgrading_consistency") => RetryPolicy::downgrading_consistency_new(),
+                Some("fallthrough") => RetryPolicy::fallthrough_new(),
+                _ => RetryPolicy::default_new(),
+            };
+            builder = builder.retry_policy(if self.retry_logging.unwrap_or(false) {
+                RetryPolicy::logging_new(policy)
+            } else {
+                policy
+            });
+        }
+        if let Some(version) = self.protocol_version {
+            builder = builder.protocol_version(match version {
+                1 => CqlProtocol::ONE,
+                2 => CqlProtocol::TWO,
+                3 => CqlProtocol::THREE,
+                _ => CqlProtocol::FOUR,
+            });
+        }
+        Ok(builder)
+    }
+}
+
+impl Cluster {
+    ///Creates a cluster from its settings, reporting every invalid one.
+    pub fn from_config(config: ClusterConfig) -> Result<Cluster, BuildError> {
+        try!(config.builder()).build()
+    }
+}
+
+#[test]
+fn test_cluster_config_from_url() {
+    let url = "cassandra://user:p%40ss@h1,h2:9042/ks?consistency=LOCAL_QUORUM&dc=eu1&ssl=true";
+    let config = ClusterConfig::from_url(url).unwrap();
+    assert_eq!(config.username, Some("user".to_owned()));
+    assert_eq!(config.password, Some("p@ss".to_owned()));
+    assert_eq!(config.contact_points, vec!["h1".to_owned(), "h2".to_owned()]);
+    assert_eq!(config.port, Some(9042));
+    assert_eq!(config.keyspace, Some("ks".to_owned()));
+    assert_eq!(config.consistency, Some(Consistency::LocalQuorum));
+    assert_eq!(config.local_dc, Some("eu1".to_owned()));
+    assert_eq!(config.ssl, Some(true));
+    let problems = config.validate().unwrap_err();
+    assert_eq!(problems.problems().len(), 1);
+    assert_eq!(problems.problems()[0].key, "ssl_trusted_certs");
+    let config = ClusterConfig::from_url(&format!("{}&ssl_verify=none", url)).unwrap();
+    assert_eq!(config.validate(), Ok(()));
+
+    assert_eq!(ClusterConfig::from_url("cassandra://h1:port").unwrap_err().key, "port");
+    assert_eq!(ClusterConfig::from_url("cassandra://h1?pool=3").unwrap_err().key, "pool");
+    assert_eq!(ClusterConfig::from_url("cassandra://h1?consistency=most").unwrap_err().key, "consistency");
+    assert_eq!(ClusterConfig::from_url("http://h1").unwrap_err().key, "url");
+}
+
+#[test]
+fn test_cluster_config_deserialization() {
+    let toml = r#"
+        contact_points = ["h1", "h2"]
+        keyspace = "ks"
+        consistency = "local_quorum"
+        load_balancing = "dc_aware"
+        local_dc = "eu1"
+        request_timeout_ms = 5000
+        ssl = true
+        ssl_trusted_certs = ["/etc/cassandra/ca.pem"]
+        ssl_verify = "peer_identity"
+    "#;
+    let config: ClusterConfig = ::toml::from_str(toml).unwrap();
+    assert_eq!(config.contact_points, vec!["h1".to_owned(), "h2".to_owned()]);
+    assert_eq!(config.consistency, Some(Consistency::LocalQuorum));
+    assert_eq!(config.request_timeout_ms, Some(5000));
+    assert_eq!(config.ssl_verify, Some(VerifyMode::PeerIdentity));
+    assert_eq!(config.validate(), Ok(()));
+
+    let json = ::serde_json::to_string(&config).unwrap();
+    assert_eq!(::serde_json::from_str::<ClusterConfig>(&json).unwrap(), config);
+    let toml = ::toml::to_string(&config).unwrap();
+    assert_eq!(::toml::from_str::<ClusterConfig>(&toml).unwrap(), config);
+
+    assert!(::toml::from_str::<ClusterConfig>("pool_size = 3").is_err());
+    assert!(::toml::from_str::<ClusterConfig>("port = \"ninety\"").is_err());
+    assert!(::serde_json::from_str::<ClusterConfig>(r#"{"consistency": "most"}"#).is_err());
+    assert!(::serde_json::from_str::<ClusterConfig>(r#"{"ssl_verify": "sometimes"}"#).is_err());
+
+    let invalid: ClusterConfig = ::serde_json::from_str(r#"{
+        "load_balancing": "random",
+        "retry_policy": "never",
+        "protocol_version": 7,
+        "ssl": true
+    }"#)
+                                     .unwrap();
+    let keys: Vec<String> = invalid.validate().unwrap_err().problems().iter().map(|err| err.key.clone()).collect();
+    assert_eq!(keys,
+               vec!["contact_points", "load_balancing", "retry_policy", "protocol_version", "ssl_trusted_certs"]);
+    assert_eq!(invalid.builder().unwrap_err().problems().len(), 5);
+}
@@ -0,0 +1,403 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::fmt;
+use std::net::IpAddr;
+
+use cassandra_sys::CASS_ERROR_LIB_INVALID_VALUE_TYPE;
+
+use cassandra::cql_type::CqlType;
+use cassandra::cql_value::CqlValue;
+use cassandra::error::CassError;
+use cassandra::row::Row;
+use cassandra::statement::Statement;
+use cassandra::tuple::Tuple;
+use cassandra::user_type::UserType;
+use cassandra::uuid::Uuid;
+use cassandra::util::Protected;
+
+lazy_static! {
+    static ref DEFAULT_REGISTRY: CodecRegistry = CodecRegistry::new();
+}
+
+///The registry holding only the codecs for the built-in Rust types, used by
+///statements and rows not created through a session.
+pub fn default_registry() -> &'static CodecRegistry {
+    &DEFAULT_REGISTRY
+}
+
+///Maps a Rust type onto a Cassandra type.
+///
+///Implement this for domain types that are stored in blob, text or UDT
+///columns and register the codec with a `CodecRegistry`.
+///
+///Codecs are handed the registry they are used from, so that a codec for a
+///UDT, tuple or collection type can encode and decode its members with the
+///codecs registered for them.
+pub trait TypeCodec: Send + Sync {
+    ///The Rust type handled by this codec
+    type Target: Any;
+
+    ///The Cassandra type values are encoded to
+    fn cql_type(&self) -> CqlType;
+
+    ///Converts a Rust value into an owned Cassandra value
+    fn encode(&self, value: &Self::Target, codecs: &CodecRegistry) -> Result<CqlValue, CassError>;
+
+    ///Converts an owned Cassandra value back into a Rust value
+    fn decode(&self, value: CqlValue, codecs: &CodecRegistry) -> Result<Self::Target, CassError>;
+}
+
+///A decoder for values of a custom marshal class
+pub type CustomDecoder = Box<Fn(&[u8]) -> Result<CqlValue, CassError> + Send + Sync>;
+
+trait ErasedCodec: Send + Sync {
+    fn cql_type(&self) -> CqlType;
+    fn encode_any(&self, value: &Any, codecs: &CodecRegistry) -> Result<CqlValue, CassError>;
+    fn decode_any(&self, value: CqlValue, codecs: &CodecRegistry) -> Result<Box<Any>, CassError>;
+}
+
+impl<C: TypeCodec> ErasedCodec for C {
+    fn cql_type(&self) -> CqlType {
+        TypeCodec::cql_type(self)
+    }
+
+    fn encode_any(&self, value: &Any, codecs: &CodecRegistry) -> Result<CqlValue, CassError> {
+        match value.downcast_ref::<C::Target>() {
+            Some(value) => self.encode(value, codecs),
+            None => Err(invalid_type()),
+        }
+    }
+
+    fn decode_any(&self, value: CqlValue, codecs: &CodecRegistry) -> Result<Box<Any>, CassError> {
+        let decoded = try!(self.decode(value, codecs));
+        Ok(Box::new(decoded))
+    }
+}
+
+fn invalid_type() -> CassError {
+    CassError::build(CASS_ERROR_LIB_INVALID_VALUE_TYPE)
+}
+
+///A set of type codecs, keyed by the Rust type they handle, together with
+///decoders for custom marshal classes.
+///
+///A registry is attached to a `Cluster` and shared with every `Session`
+///it connects, which hands it on to the statements it creates or prepares
+///and the rows it returns: `Statement::bind_custom` and `Row::get_custom`
+///use it.
+pub struct CodecRegistry {
+    codecs: HashMap<TypeId, Box<ErasedCodec>>,
+    custom: HashMap<String, CustomDecoder>,
+}
+
+impl Debug for CodecRegistry {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f,
+               "CodecRegistry {{ codecs: {}, custom decoders: {:?} }}",
+               self.codecs.len(),
+               self.custom.keys().collect::<Vec<_>>())
+    }
+}
+
+impl Default for CodecRegistry {
+    fn default() -> CodecRegistry {
+        CodecRegistry::new()
+    }
+}
+
+impl CodecRegistry {
+    ///Creates a registry holding codecs for the built-in Rust types.
+    pub fn new() -> Self {
+        let mut registry = CodecRegistry::empty();
+        registry.register(BoolCodec)
+                .register(TinyIntCodec)
+                .register(SmallIntCodec)
+                .register(IntCodec)
+                .register(BigintCodec)
+                .register(FloatCodec)
+                .register(DoubleCodec)
+                .register(TextCodec)
+                .register(BlobCodec)
+                .register(UuidCodec)
+                .register(InetCodec);
+        registry
+    }
+
+    ///Creates a registry without any codecs.
+    pub fn empty() -> Self {
+        CodecRegistry {
+            codecs: HashMap::new(),
+            custom: HashMap::new(),
+        }
+    }
+
+    ///Registers a codec, replacing any codec previously registered for the
+    ///same Rust type.
+    pub fn register<C>(&mut self, codec: C) -> &mut Self
+        where C: TypeCodec + 'static {
+        self.codecs.insert(TypeId::of::<C::Target>(), Box::new(codec));
+        self
+    }
+
+    ///Registers a decoder for values of the custom type with the given
+    ///marshal class name (as returned by `CqlType::Custom`).
+    pub fn register_custom<F>(&mut self, class_name: &str, decoder: F) -> &mut Self
+        where F: Fn(&[u8]) -> Result<CqlValue, CassError> + Send + Sync + 'static {
+        self.custom.insert(class_name.to_owned(), Box::new(decoder));
+        self
+    }
+
+    ///The Cassandra type the given Rust type is encoded to, if a codec for it
+    ///is registered.
+    pub fn cql_type_of<T: Any>(&self) -> Option<CqlType> {
+        self.codecs.get(&TypeId::of::<T>()).map(|codec| codec.cql_type())
+    }
+
+    ///Encodes a Rust value with its registered codec.
+    pub fn encode<T: Any>(&self, value: &T) -> Result<CqlValue, CassError> {
+        match self.codecs.get(&TypeId::of::<T>()) {
+            Some(codec) => codec.encode_any(value, self),
+            None => Err(invalid_type()),
+        }
+    }
+
+    ///Decodes a Rust value with its registered codec.
+    pub fn decode<T: Any>(&self, value: CqlValue) -> Result<T, CassError> {
+        match self.codecs.get(&TypeId::of::<T>()) {
+            Some(codec) => {
+                match try!(codec.decode_any(value, self)).downcast::<T>() {
+                    Ok(decoded) => Ok(*decoded),
+                    Err(_) => Err(invalid_type()),
+                }
+            }
+            None => Err(invalid_type()),
+        }
+    }
+
+    ///Encodes Rust values with their registered codec into a "list".
+    pub fn encode_list<T: Any>(&self, items: &[T]) -> Result<CqlValue, CassError> {
+        let mut encoded = Vec::with_capacity(items.len());
+        for item in items {
+            encoded.push(try!(self.encode(item)));
+        }
+        Ok(CqlValue::List(encoded))
+    }
+
+    ///Decodes the items of a "list" or "set" with their registered codec.
+    pub fn decode_list<T: Any>(&self, value: CqlValue) -> Result<Vec<T>, CassError> {
+        match value {
+            CqlValue::List(items) |
+            CqlValue::Set(items) => items.into_iter().map(|item| self.decode(item)).collect(),
+            CqlValue::Null => Ok(vec![]),
+            _ => Err(invalid_type()),
+        }
+    }
+
+    ///Decodes the serialized form of a custom type, if a decoder is
+    ///registered for its marshal class.
+    pub fn decode_custom(&self, class_name: &str, bytes: &[u8]) -> Option<Result<CqlValue, CassError>> {
+        self.custom.get(class_name).map(|decoder| decoder(bytes))
+    }
+}
+
+macro_rules! builtin_codec {
+    ($codec:ident, $target:ty, $variant:ident, $cql_type:expr) => {
+        struct $codec;
+
+        impl TypeCodec for $codec {
+            type Target = $target;
+
+            fn cql_type(&self) -> CqlType {
+                $cql_type
+            }
+
+            fn encode(&self, value: &$target, _: &CodecRegistry) -> Result<CqlValue, CassError> {
+                Ok(CqlValue::$variant(value.clone()))
+            }
+
+            fn decode(&self, value: CqlValue, _: &CodecRegistry) -> Result<$target, CassError> {
+                match value {
+                    CqlValue::$variant(value) => Ok(value),
+                    _ => Err(invalid_type()),
+                }
+            }
+        }
+    };
+}
+
+builtin_codec!(BoolCodec, bool, Boolean, CqlType::Boolean);
+builtin_codec!(TinyIntCodec, i8, TinyInt, CqlType::TinyInt);
+builtin_codec!(SmallIntCodec, i16, SmallInt, CqlType::SmallInt);
+builtin_codec!(IntCodec, i32, Int, CqlType::Int);
+builtin_codec!(BigintCodec, i64, Bigint, CqlType::Bigint);
+builtin_codec!(FloatCodec, f32, Float, CqlType::Float);
+builtin_codec!(DoubleCodec, f64, Double, CqlType::Double);
+builtin_codec!(TextCodec, String, Text, CqlType::Text);
+builtin_codec!(BlobCodec, Vec<u8>, Blob, CqlType::Blob);
+builtin_codec!(UuidCodec, Uuid, Uuid, CqlType::Uuid);
+builtin_codec!(InetCodec, IpAddr, Inet, CqlType::Inet);
+
+impl Statement {
+    ///Binds a Rust value at the specified index using the codec registered
+    ///for its type in the statement's registry.
+    pub fn bind_custom<T: Any>(&mut self, index: u64, value: &T) -> Result<&mut Self, CassError> {
+        let value = try!(self.codec_registry().encode(value));
+        self.bind_value(index, &value)
+    }
+
+    ///Binds a Rust value to all the values with the specified name using
+    ///the codec registered for its type in the statement's registry.
+    pub fn bind_custom_by_name<T: Any>(&mut self, name: &str, value: &T) -> Result<&mut Self, CassError> {
+        let value = try!(self.codec_registry().encode(value));
+        self.bind_value_by_name(name, &value)
+    }
+
+    ///Binds Rust values as a "list" at the specified index, encoding each
+    ///item with the codec registered for its type.
+    pub fn bind_custom_list<T: Any>(&mut self, index: u64, items: &[T]) -> Result<&mut Self, CassError> {
+        let value = try!(self.codec_registry().encode_list(items));
+        self.bind_value(index, &value)
+    }
+}
+
+impl Row {
+    ///Reads the column at the specified index using the codec registered
+    ///for the requested Rust type in the row's registry.
+    pub fn get_custom<T: Any>(&self, index: u64) -> Result<T, CassError> {
+        self.codec_registry().decode(try!(self.get_value(index)))
+    }
+
+    ///Reads the column with the specified name using the codec registered
+    ///for the requested Rust type in the row's registry.
+    pub fn get_custom_by_name<T: Any, S>(&self, name: S) -> Result<T, CassError>
+        where S: Into<String> {
+        self.codec_registry().decode(try!(self.get_value_by_name(name)))
+    }
+
+    ///Reads the "list" or "set" column at the specified index, decoding
+    ///each item with the codec registered for the requested Rust type.
+    pub fn get_custom_list<T: Any>(&self, index: u64) -> Result<Vec<T>, CassError> {
+        self.codec_registry().decode_list(try!(self.get_value(index)))
+    }
+}
+
+impl Tuple {
+    ///Sets a Rust value in a tuple at the specified index using its
+    ///registered codec.
+    pub fn set_with<T: Any>(&mut self, index: u64, value: &T, codecs: &CodecRegistry) -> Result<(), CassError> {
+        let value = try!(codecs.encode(value));
+        self.set_value(index, &value)
+    }
+}
+
+impl UserType {
+    ///Sets a Rust value in a user defined type at the specified name using
+    ///its registered codec.
+    pub fn set_by_name_with<T: Any>(&mut self, name: &str, value: &T, codecs: &CodecRegistry)
+                                    -> Result<(), CassError> {
+        let value = try!(codecs.encode(value));
+        self.set_value_by_name(name, &value)
+    }
+}
+
+#[test]
+fn test_custom_codec_round_trip() {
+    #[derive(Clone,Debug,PartialEq)]
+    struct Cents(i64);
+
+    struct CentsCodec;
+
+    impl TypeCodec for CentsCodec {
+        type Target = Cents;
+
+        fn cql_type(&self) -> CqlType {
+            CqlType::Bigint
+        }
+
+        fn encode(&self, value: &Cents, _: &CodecRegistry) -> Result<CqlValue, CassError> {
+            Ok(CqlValue::Bigint(value.0))
+        }
+
+        fn decode(&self, value: CqlValue, _: &CodecRegistry) -> Result<Cents, CassError> {
+            match value {
+                CqlValue::Bigint(cents) => Ok(Cents(cents)),
+                _ => Err(invalid_type()),
+            }
+        }
+    }
+
+    #[derive(Debug,PartialEq)]
+    struct Price {
+        currency: String,
+        amount: Cents,
+    }
+
+    struct PriceCodec;
+
+    impl TypeCodec for PriceCodec {
+        type Target = Price;
+
+        fn cql_type(&self) -> CqlType {
+            CqlType::Udt {
+                keyspace: "shop".to_owned(),
+                name: "price".to_owned(),
+                fields: vec![("currency".to_owned(), CqlType::Text), ("amount".to_owned(), CqlType::Bigint)],
+            }
+        }
+
+        fn encode(&self, value: &Price, codecs: &CodecRegistry) -> Result<CqlValue, CassError> {
+            Ok(CqlValue::Udt(TypeCodec::cql_type(self),
+                             vec![("currency".to_owned(), try!(codecs.encode(&value.currency))),
+                                  ("amount".to_owned(), try!(codecs.encode(&value.amount)))]))
+        }
+
+        fn decode(&self, value: CqlValue, codecs: &CodecRegistry) -> Result<Price, CassError> {
+            let mut fields = match value {
+                CqlValue::Udt(_, fields) => fields.into_iter(),
+                _ => return Err(invalid_type()),
+            };
+            match (fields.next(), fields.next()) {
+                (Some((_, currency)), Some((_, amount))) => {
+                    Ok(Price {
+                        currency: try!(codecs.decode(currency)),
+                        amount: try!(codecs.decode(amount)),
+                    })
+                }
+                _ => Err(invalid_type()),
+            }
+        }
+    }
+
+    let mut codecs = CodecRegistry::new();
+    codecs.register(CentsCodec).register(PriceCodec);
+
+    // scalar
+    let encoded = codecs.encode(&Cents(1999)).unwrap();
+    assert_eq!(encoded, CqlValue::Bigint(1999));
+    assert_eq!(codecs.decode::<Cents>(encoded).unwrap(), Cents(1999));
+    assert!(codecs.decode::<Cents>(CqlValue::Int(1999)).is_err());
+    assert!(default_registry().encode(&Cents(1)).is_err());
+
+    // collection element
+    let items = vec![Cents(1), Cents(250)];
+    let encoded = codecs.encode_list(&items).unwrap();
+    assert_eq!(encoded, CqlValue::List(vec![CqlValue::Bigint(1), CqlValue::Bigint(250)]));
+    assert_eq!(codecs.decode_list::<Cents>(encoded).unwrap(), items);
+    assert_eq!(codecs.decode_list::<Cents>(CqlValue::Set(vec![CqlValue::Bigint(3)])).unwrap(),
+               vec![Cents(3)]);
+
+    // UDT field
+    let price = Price {
+        currency: "EUR".to_owned(),
+        amount: Cents(4250),
+    };
+    let encoded = codecs.encode(&price).unwrap();
+    match encoded {
+        CqlValue::Udt(_, ref fields) => assert_eq!(fields[1], ("amount".to_owned(), CqlValue::Bigint(4250))),
+        ref other => panic!("{:?}", other),
+    }
+    assert_eq!(codecs.decode::<Price>(encoded).unwrap(), price);
+    assert_eq!(codecs.cql_type_of::<Price>().map(|cql_type| cql_type.to_string()),
+               Some("shop.price".to_owned()));
+}
@@ -1,3 +1,4 @@
+use std::any::Any;
 use std::ffi::CString;
 use cassandra_sys::CASS_COLLECTION_TYPE_LIST;
 use cassandra_sys::CASS_COLLECTION_TYPE_SET;
@@ -35,6 +36,9 @@ use cassandra::data_type::DataType;
 use cassandra::data_type::ConstDataType;
 use cassandra::uuid::Uuid;
 use cassandra::util::Protected;
+use cassandra::codec::CodecRegistry;
+use cassandra::cql_value;
+use cassandra::cql_value::CqlValue;
 
 // #[repr(C)]
 // #[derive(Debug,Copy,Clone)]
@@ -110,6 +114,19 @@ pub trait CassCollection {
     ///Appends a "udt" to the collection.
     fn append_user_type(&mut self, value: &UserType) -> Result<&Self, CassError>;
 
+    ///Appends an owned value to the collection.
+    fn append_value(&mut self, value: &CqlValue) -> Result<&Self, CassError>
+        where Self: Sized {
+        try!(cql_value::append_value(self, value));
+        Ok(self)
+    }
+
+    ///Appends a Rust value to the collection using its registered codec.
+    fn append_with<T: Any>(&mut self, value: &T, codecs: &CodecRegistry) -> Result<&Self, CassError>
+        where Self: Sized {
+        let value = try!(codecs.encode(value));
+        self.append_value(&value)
+    }
 }
 
 ///A cassandra list collection
@@ -1,29 +1,144 @@
 use cassandra_sys::CassConsistency as _CassConsistency;
 
-use std::ffi::CStr;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::fmt;
+use std::str::FromStr;
 
-use cassandra_sys::cass_consistency_string;
-use cassandra::util::Protected;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de;
 
+use cassandra_sys::CASS_CONSISTENCY_ANY;
+use cassandra_sys::CASS_CONSISTENCY_ONE;
+use cassandra_sys::CASS_CONSISTENCY_TWO;
+use cassandra_sys::CASS_CONSISTENCY_THREE;
+use cassandra_sys::CASS_CONSISTENCY_QUORUM;
+use cassandra_sys::CASS_CONSISTENCY_ALL;
+use cassandra_sys::CASS_CONSISTENCY_LOCAL_QUORUM;
+use cassandra_sys::CASS_CONSISTENCY_EACH_QUORUM;
+use cassandra_sys::CASS_CONSISTENCY_SERIAL;
+use cassandra_sys::CASS_CONSISTENCY_LOCAL_SERIAL;
+use cassandra_sys::CASS_CONSISTENCY_LOCAL_ONE;
 
+///A Cassandra consistency level.
+///
+///Parsed from and displayed as its CQL name, such as `LOCAL_QUORUM`; parsing
+///ignores case.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+#[allow(missing_docs)]
+pub enum Consistency {
+    Any,
+    One,
+    Two,
+    Three,
+    Quorum,
+    All,
+    LocalQuorum,
+    EachQuorum,
+    Serial,
+    LocalSerial,
+    LocalOne,
+}
+
+const CONSISTENCIES: [(Consistency, &'static str, _CassConsistency); 11] =
+    [(Consistency::Any, "ANY", CASS_CONSISTENCY_ANY),
+     (Consistency::One, "ONE", CASS_CONSISTENCY_ONE),
+     (Consistency::Two, "TWO", CASS_CONSISTENCY_TWO),
+     (Consistency::Three, "THREE", CASS_CONSISTENCY_THREE),
+     (Consistency::Quorum, "QUORUM", CASS_CONSISTENCY_QUORUM),
+     (Consistency::All, "ALL", CASS_CONSISTENCY_ALL),
+     (Consistency::LocalQuorum, "LOCAL_QUORUM", CASS_CONSISTENCY_LOCAL_QUORUM),
+     (Consistency::EachQuorum, "EACH_QUORUM", CASS_CONSISTENCY_EACH_QUORUM),
+     (Consistency::Serial, "SERIAL", CASS_CONSISTENCY_SERIAL),
+     (Consistency::LocalSerial, "LOCAL_SERIAL", CASS_CONSISTENCY_LOCAL_SERIAL),
+     (Consistency::LocalOne, "LOCAL_ONE", CASS_CONSISTENCY_LOCAL_ONE)];
 
-///A Cassandra consistency level
-pub struct Consistency(_CassConsistency);
+impl Consistency {
+    ///The CQL name of the consistency level
+    pub fn name(&self) -> &'static str {
+        CONSISTENCIES.iter().find(|entry| entry.0 == *self).map(|entry| entry.1).expect("every level is listed")
+    }
 
-impl ToString for Consistency {
-    fn to_string(&self) -> String {
-        unsafe {
-            let my_string = cass_consistency_string(self.0);
-            CStr::from_ptr(my_string).to_string_lossy().into_owned()
-        }
+    ///Whether this is a serial consistency level, for the Paxos round of a
+    ///lightweight transaction
+    pub fn is_serial(&self) -> bool {
+        *self == Consistency::Serial || *self == Consistency::LocalSerial
     }
 }
 
-impl Protected<_CassConsistency> for Consistency {
-    fn inner(&self) -> _CassConsistency {
-        self.0
+///The driver's value for a consistency level
+pub fn raw_consistency(consistency: Consistency) -> _CassConsistency {
+    CONSISTENCIES.iter().find(|entry| entry.0 == consistency).map(|entry| entry.2).expect("every level is listed")
+}
+
+///The consistency level of a driver value, or `None` for
+///`CASS_CONSISTENCY_UNKNOWN`
+pub fn consistency_of(raw: _CassConsistency) -> Option<Consistency> {
+    CONSISTENCIES.iter().find(|entry| entry.2 as i32 == raw as i32).map(|entry| entry.0)
+}
+
+impl Display for Consistency {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
     }
-    fn build(inner: _CassConsistency) -> Self {
-        Consistency(inner)
+}
+
+///A string that is not the name of a consistency level
+#[derive(Clone,PartialEq,Eq)]
+pub struct ParseConsistencyError(String);
+
+impl Display for ParseConsistencyError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "unknown consistency level `{}`", self.0)
+    }
+}
+
+impl Debug for ParseConsistencyError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Error for ParseConsistencyError {
+    fn description(&self) -> &str {
+        "unknown consistency level"
+    }
+}
+
+impl FromStr for Consistency {
+    type Err = ParseConsistencyError;
+
+    fn from_str(name: &str) -> Result<Consistency, ParseConsistencyError> {
+        let upper = name.trim().to_uppercase();
+        CONSISTENCIES.iter()
+                     .find(|entry| entry.1 == upper)
+                     .map(|entry| entry.0)
+                     .ok_or_else(|| ParseConsistencyError(name.to_owned()))
+    }
+}
+
+impl Serialize for Consistency {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Consistency {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Consistency, D::Error> {
+        let name = try!(String::deserialize(deserializer));
+        Consistency::from_str(&name).map_err(de::Error::custom)
+    }
+}
+
+#[test]
+fn test_consistency_names() {
+    for &(consistency, name, _) in CONSISTENCIES.iter() {
+        assert_eq!(consistency.to_string(), name);
+        assert_eq!(name.parse::<Consistency>(), Ok(consistency));
+        assert_eq!(name.to_lowercase().parse::<Consistency>(), Ok(consistency));
     }
+    assert_eq!("most".parse::<Consistency>().unwrap_err().to_string(),
+               "unknown consistency level `most`");
+    assert!(Consistency::LocalSerial.is_serial());
+    assert!(!Consistency::LocalQuorum.is_serial());
 }
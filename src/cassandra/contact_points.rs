@@ -0,0 +1,301 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, ToSocketAddrs};
+use std::str::FromStr;
+
+const DEFAULT_PORT: u16 = 9042;
+
+///The host part of a contact point
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub enum Host {
+    ///An IPv4 or IPv6 address
+    Ip(IpAddr),
+    ///A DNS name, resolved by the driver when it connects unless the
+    ///contact points are resolved with `ContactPoints::resolve`
+    Name(String),
+}
+
+impl Display for Host {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Host::Ip(ref ip) => write!(f, "{}", ip),
+            Host::Name(ref name) => write!(f, "{}", name),
+        }
+    }
+}
+
+///A node to connect to first: a host and, optionally, a port
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct ContactPoint {
+    ///The node's address or name
+    pub host: Host,
+    ///The node's native protocol port, if it differs from the cluster's
+    pub port: Option<u16>,
+}
+
+impl ContactPoint {
+    ///A contact point on the cluster's port
+    pub fn new(host: Host) -> ContactPoint {
+        ContactPoint {
+            host: host,
+            port: None,
+        }
+    }
+
+    ///A contact point on the given port
+    pub fn with_port(host: Host, port: u16) -> ContactPoint {
+        ContactPoint {
+            host: host,
+            port: Some(port),
+        }
+    }
+}
+
+impl Display for ContactPoint {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match (&self.host, self.port) {
+            (&Host::Ip(IpAddr::V6(ref ip)), Some(port)) => write!(f, "[{}]:{}", ip, port),
+            (host, Some(port)) => write!(f, "{}:{}", host, port),
+            (host, None) => write!(f, "{}", host),
+        }
+    }
+}
+
+impl FromStr for ContactPoint {
+    type Err = ContactPointError;
+
+    ///Parses `host`, `host:port`, an IPv6 address or `[ipv6]:port`.
+    fn from_str(s: &str) -> Result<ContactPoint, ContactPointError> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ContactPointError::Empty);
+        }
+        if s.starts_with('[') {
+            let close = try!(s.find(']').ok_or_else(|| ContactPointError::InvalidHost(s.to_owned())));
+            let ip = try!(Ipv6Addr::from_str(&s[1..close])
+                              .map_err(|_| ContactPointError::InvalidHost(s[1..close].to_owned())));
+            let port = match &s[close + 1..] {
+                "" => None,
+                rest if rest.starts_with(':') => Some(try!(parse_port(&rest[1..]))),
+                _ => return Err(ContactPointError::InvalidHost(s.to_owned())),
+            };
+            return Ok(ContactPoint {
+                host: Host::Ip(IpAddr::V6(ip)),
+                port: port,
+            });
+        }
+        if let Ok(ip) = IpAddr::from_str(s) {
+            return Ok(ContactPoint::new(Host::Ip(ip)));
+        }
+        let (host, port) = match s.rfind(':') {
+            Some(colon) => (&s[..colon], Some(try!(parse_port(&s[colon + 1..])))),
+            None => (s, None),
+        };
+        let host = match Ipv4Addr::from_str(host) {
+            Ok(ip) => Host::Ip(IpAddr::V4(ip)),
+            Err(_) if is_host_name(host) => Host::Name(host.to_owned()),
+            // an unbracketed IPv6 address cannot have a port
+            Err(_) => return Err(ContactPointError::InvalidHost(s.to_owned())),
+        };
+        Ok(ContactPoint {
+            host: host,
+            port: port,
+        })
+    }
+}
+
+fn parse_port(port: &str) -> Result<u16, ContactPointError> {
+    match u16::from_str(port) {
+        Ok(0) | Err(_) => Err(ContactPointError::InvalidPort(port.to_owned())),
+        Ok(port) => Ok(port),
+    }
+}
+
+fn is_host_name(name: &str) -> bool {
+    // a fully qualified name may end with a dot
+    let name = if name.ends_with('.') { &name[..name.len() - 1] } else { name };
+    name.len() <= 253 &&
+    name.split('.').all(|label| {
+        !label.is_empty() && label.len() <= 63 && !label.starts_with('-') && !label.ends_with('-') &&
+        label.bytes().all(|c| (c as char).is_alphanumeric() && c < 128 || c == b'-' || c == b'_')
+    })
+}
+
+///An invalid or unresolvable contact point
+pub enum ContactPointError {
+    ///A contact point in the list is blank
+    Empty,
+    ///Not an IP address, a bracketed IPv6 address or a host name
+    InvalidHost(String),
+    ///Not a port between 1 and 65535
+    InvalidPort(String),
+    ///Contact points name different ports, which the driver does not support
+    MixedPorts(u16, u16),
+    ///A host name could not be resolved
+    Resolve(String, io::Error),
+}
+
+impl Display for ContactPointError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            ContactPointError::Empty => write!(f, "empty contact point"),
+            ContactPointError::InvalidHost(ref host) => write!(f, "invalid contact point `{}`", host),
+            ContactPointError::InvalidPort(ref port) => write!(f, "invalid port `{}`", port),
+            ContactPointError::MixedPorts(first, second) => {
+                write!(f, "contact points use different ports ({} and {})", first, second)
+            }
+            ContactPointError::Resolve(ref host, ref err) => write!(f, "cannot resolve `{}`: {}", host, err),
+        }
+    }
+}
+
+impl Debug for ContactPointError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Error for ContactPointError {
+    fn description(&self) -> &str {
+        match *self {
+            ContactPointError::Empty => "empty contact point",
+            ContactPointError::InvalidHost(_) => "invalid contact point",
+            ContactPointError::InvalidPort(_) => "invalid port",
+            ContactPointError::MixedPorts(..) => "contact points use different ports",
+            ContactPointError::Resolve(..) => "cannot resolve contact point",
+        }
+    }
+}
+
+///A set of cassandra contact points: IPv4 and IPv6 addresses and host
+///names, each with an optional port.
+///
+///Parsed from a comma separated list such as
+///`10.0.0.1,node2.example.com:9042,[::1]:9042`; an empty string is an
+///empty set.
+#[derive(Clone,Debug,Default,PartialEq,Eq)]
+pub struct ContactPoints(Vec<ContactPoint>);
+
+impl ContactPoints {
+    ///The contact points
+    pub fn points(&self) -> &[ContactPoint] {
+        &self.0
+    }
+
+    ///Resolves host names now, replacing each with all of its A and AAAA
+    ///records, instead of leaving them to the driver.
+    pub fn resolve(&self) -> Result<ContactPoints, ContactPointError> {
+        let mut resolved: Vec<ContactPoint> = vec![];
+        for point in &self.0 {
+            let name = match point.host {
+                Host::Name(ref name) => name,
+                Host::Ip(_) => {
+                    if !resolved.contains(point) {
+                        resolved.push(point.clone());
+                    }
+                    continue;
+                }
+            };
+            let addrs = try!((&name[..], point.port.unwrap_or(DEFAULT_PORT))
+                                 .to_socket_addrs()
+                                 .map_err(|err| ContactPointError::Resolve(name.clone(), err)));
+            for addr in addrs {
+                let point = ContactPoint {
+                    host: Host::Ip(addr.ip()),
+                    port: point.port,
+                };
+                if !resolved.contains(&point) {
+                    resolved.push(point);
+                }
+            }
+        }
+        Ok(ContactPoints(resolved))
+    }
+
+    ///The one port named by the contact points, if any name a port
+    pub fn port(&self) -> Result<Option<u16>, ContactPointError> {
+        let mut ports = self.0.iter().filter_map(|point| point.port);
+        let first = ports.next();
+        match (first, ports.find(|port| Some(*port) != first)) {
+            (Some(first), Some(other)) => Err(ContactPointError::MixedPorts(first, other)),
+            (first, _) => Ok(first),
+        }
+    }
+
+    ///The hosts in the form the driver accepts, without ports or brackets
+    pub fn hosts(&self) -> String {
+        let hosts: Vec<String> = self.0.iter().map(|point| point.host.to_string()).collect();
+        hosts.join(",")
+    }
+}
+
+impl Display for ContactPoints {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let contact_points: Vec<String> = self.0.iter().map(|point| point.to_string()).collect();
+        write!(f, "{}", contact_points.join(","))
+    }
+}
+
+impl FromStr for ContactPoints {
+    type Err = ContactPointError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Ok(ContactPoints(vec![]));
+        }
+        let contact_points = s.split(',').map(ContactPoint::from_str).collect();
+        Ok(ContactPoints(try!(contact_points)))
+    }
+}
+
+impl From<Vec<ContactPoint>> for ContactPoints {
+    fn from(points: Vec<ContactPoint>) -> ContactPoints {
+        ContactPoints(points)
+    }
+}
+
+impl From<ContactPoint> for ContactPoints {
+    fn from(point: ContactPoint) -> ContactPoints {
+        ContactPoints(vec![point])
+    }
+}
+
+impl From<IpAddr> for ContactPoints {
+    fn from(ip: IpAddr) -> ContactPoints {
+        ContactPoints(vec![ContactPoint::new(Host::Ip(ip))])
+    }
+}
+
+impl From<Vec<IpAddr>> for ContactPoints {
+    fn from(ips: Vec<IpAddr>) -> ContactPoints {
+        ContactPoints(ips.into_iter().map(|ip| ContactPoint::new(Host::Ip(ip))).collect())
+    }
+}
+
+#[test]
+fn test_contact_points_from_str() {
+    let points = ContactPoints::from_str("10.0.0.1, node-2.example.com:9043,::1,[fe80::1]:9043,[::2]").unwrap();
+    assert_eq!(points.points(),
+               &[ContactPoint::new(Host::Ip(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)))),
+                 ContactPoint::with_port(Host::Name("node-2.example.com".to_owned()), 9043),
+                 ContactPoint::new(Host::Ip(IpAddr::from_str("::1").unwrap())),
+                 ContactPoint::with_port(Host::Ip(IpAddr::from_str("fe80::1").unwrap()), 9043),
+                 ContactPoint::new(Host::Ip(IpAddr::from_str("::2").unwrap()))]);
+    assert_eq!(points.to_string(), "10.0.0.1,node-2.example.com:9043,::1,[fe80::1]:9043,::2");
+    assert_eq!(points.hosts(), "10.0.0.1,node-2.example.com,::1,fe80::1,::2");
+    assert_eq!(points.port().unwrap(), Some(9043));
+
+    assert_eq!(ContactPoints::from_str("").unwrap(), ContactPoints::default());
+    assert!(ContactPoints::from_str("10.0.0.1:9042,10.0.0.2:9043").unwrap().port().is_err());
+    assert_eq!(ContactPoints::from_str("10.0.0.1,,10.0.0.2").unwrap_err().to_string(),
+               "empty contact point");
+    assert_eq!(ContactPoints::from_str("[::1]:port").unwrap_err().to_string(), "invalid port `port`");
+    assert_eq!(ContactPoints::from_str("[::1").unwrap_err().to_string(), "invalid contact point `[::1`");
+    assert_eq!(ContactPoints::from_str("bad host").unwrap_err().to_string(),
+               "invalid contact point `bad host`");
+
+    let resolved = ContactPoints::from_str("127.0.0.1:9042,127.0.0.1:9042").unwrap().resolve().unwrap();
+    assert_eq!(resolved.points().len(), 1);
+}
@@ -0,0 +1,408 @@
+use std::fmt::{Display, Formatter};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::slice;
+use std::str;
+use std::ffi::CString;
+
+use cassandra_sys::CASS_ERROR_LIB_BAD_PARAMS;
+use cassandra_sys::CASS_ERROR_LIB_INVALID_DATA;
+use cassandra_sys::CASS_ERROR_LIB_INVALID_VALUE_TYPE;
+use cassandra_sys::CASS_ERROR_LIB_NULL_VALUE;
+use cassandra_sys::CASS_OK;
+use cassandra_sys::CASS_VALUE_TYPE_ASCII;
+use cassandra_sys::CASS_VALUE_TYPE_BIGINT;
+use cassandra_sys::CASS_VALUE_TYPE_BLOB;
+use cassandra_sys::CASS_VALUE_TYPE_BOOLEAN;
+use cassandra_sys::CASS_VALUE_TYPE_COUNTER;
+use cassandra_sys::CASS_VALUE_TYPE_CUSTOM;
+use cassandra_sys::CASS_VALUE_TYPE_DATE;
+use cassandra_sys::CASS_VALUE_TYPE_DECIMAL;
+use cassandra_sys::CASS_VALUE_TYPE_DOUBLE;
+use cassandra_sys::CASS_VALUE_TYPE_FLOAT;
+use cassandra_sys::CASS_VALUE_TYPE_INET;
+use cassandra_sys::CASS_VALUE_TYPE_INT;
+use cassandra_sys::CASS_VALUE_TYPE_LIST;
+use cassandra_sys::CASS_VALUE_TYPE_MAP;
+use cassandra_sys::CASS_VALUE_TYPE_SET;
+use cassandra_sys::CASS_VALUE_TYPE_SMALL_INT;
+use cassandra_sys::CASS_VALUE_TYPE_TEXT;
+use cassandra_sys::CASS_VALUE_TYPE_TIME;
+use cassandra_sys::CASS_VALUE_TYPE_TIMESTAMP;
+use cassandra_sys::CASS_VALUE_TYPE_TIMEUUID;
+use cassandra_sys::CASS_VALUE_TYPE_TINY_INT;
+use cassandra_sys::CASS_VALUE_TYPE_TUPLE;
+use cassandra_sys::CASS_VALUE_TYPE_UDT;
+use cassandra_sys::CASS_VALUE_TYPE_UUID;
+use cassandra_sys::CASS_VALUE_TYPE_VARCHAR;
+use cassandra_sys::CASS_VALUE_TYPE_VARINT;
+use cassandra_sys::CassDataType as _CassDataType;
+use cassandra_sys::CassValueType as _CassValueType;
+use cassandra_sys::cass_data_sub_type_count;
+use cassandra_sys::cass_data_type_add_sub_type_by_name;
+use cassandra_sys::cass_data_type_class_name;
+use cassandra_sys::cass_data_type_keyspace;
+use cassandra_sys::cass_data_type_set_keyspace;
+use cassandra_sys::cass_data_type_set_type_name;
+use cassandra_sys::cass_data_type_sub_data_type;
+use cassandra_sys::cass_data_type_sub_type_name;
+use cassandra_sys::cass_data_type_type;
+use cassandra_sys::cass_data_type_type_name;
+
+use cassandra::data_type::{ConstDataType, DataType};
+use cassandra::error::CassError;
+use cassandra::value::ValueType;
+use cassandra::util::Protected;
+
+///An owned, structural description of a Cassandra data type.
+///
+///Unlike `DataType` and `ConstDataType`, which are opaque handles into the
+///driver, a `CqlType` can be compared, hashed and pattern matched directly.
+///`Text` and `Varchar` are the same CQL type and compare equal.
+#[derive(Clone,Debug)]
+#[allow(missing_docs)]
+pub enum CqlType {
+    Ascii,
+    Bigint,
+    Blob,
+    Boolean,
+    Counter,
+    Decimal,
+    Double,
+    Float,
+    Int,
+    Text,
+    Timestamp,
+    Uuid,
+    Varchar,
+    Varint,
+    Timeuuid,
+    Inet,
+    Date,
+    Time,
+    SmallInt,
+    TinyInt,
+    ///A list of the given element type
+    List(Box<CqlType>),
+    ///A set of the given element type
+    Set(Box<CqlType>),
+    ///A map from the first type to the second
+    Map(Box<CqlType>, Box<CqlType>),
+    ///A tuple of the given component types
+    Tuple(Vec<CqlType>),
+    ///A user defined type with its fields in declaration order
+    Udt {
+        keyspace: String,
+        name: String,
+        fields: Vec<(String, CqlType)>,
+    },
+    ///A custom type, identified by its marshal class name
+    Custom(String),
+}
+
+impl CqlType {
+    ///The driver value type corresponding to this type
+    pub fn value_type(&self) -> ValueType {
+        ValueType::build(self.raw_value_type())
+    }
+
+    // varchar is an alias of text
+    fn canonical_value_type(&self) -> _CassValueType {
+        match *self {
+            CqlType::Varchar => CASS_VALUE_TYPE_TEXT,
+            _ => self.raw_value_type(),
+        }
+    }
+
+    fn raw_value_type(&self) -> _CassValueType {
+        match *self {
+            CqlType::Ascii => CASS_VALUE_TYPE_ASCII,
+            CqlType::Bigint => CASS_VALUE_TYPE_BIGINT,
+            CqlType::Blob => CASS_VALUE_TYPE_BLOB,
+            CqlType::Boolean => CASS_VALUE_TYPE_BOOLEAN,
+            CqlType::Counter => CASS_VALUE_TYPE_COUNTER,
+            CqlType::Decimal => CASS_VALUE_TYPE_DECIMAL,
+            CqlType::Double => CASS_VALUE_TYPE_DOUBLE,
+            CqlType::Float => CASS_VALUE_TYPE_FLOAT,
+            CqlType::Int => CASS_VALUE_TYPE_INT,
+            CqlType::Text => CASS_VALUE_TYPE_TEXT,
+            CqlType::Timestamp => CASS_VALUE_TYPE_TIMESTAMP,
+            CqlType::Uuid => CASS_VALUE_TYPE_UUID,
+            CqlType::Varchar => CASS_VALUE_TYPE_VARCHAR,
+            CqlType::Varint => CASS_VALUE_TYPE_VARINT,
+            CqlType::Timeuuid => CASS_VALUE_TYPE_TIMEUUID,
+            CqlType::Inet => CASS_VALUE_TYPE_INET,
+            CqlType::Date => CASS_VALUE_TYPE_DATE,
+            CqlType::Time => CASS_VALUE_TYPE_TIME,
+            CqlType::SmallInt => CASS_VALUE_TYPE_SMALL_INT,
+            CqlType::TinyInt => CASS_VALUE_TYPE_TINY_INT,
+            CqlType::List(_) => CASS_VALUE_TYPE_LIST,
+            CqlType::Set(_) => CASS_VALUE_TYPE_SET,
+            CqlType::Map(_, _) => CASS_VALUE_TYPE_MAP,
+            CqlType::Tuple(_) => CASS_VALUE_TYPE_TUPLE,
+            CqlType::Udt { .. } => CASS_VALUE_TYPE_UDT,
+            CqlType::Custom(_) => CASS_VALUE_TYPE_CUSTOM,
+        }
+    }
+
+    ///Returns true for list, set and map types
+    pub fn is_collection(&self) -> bool {
+        match *self {
+            CqlType::List(_) | CqlType::Set(_) | CqlType::Map(_, _) => true,
+            _ => false,
+        }
+    }
+
+    ///Builds a new driver data type describing this type.
+    pub fn to_data_type(&self) -> Result<DataType, CassError> {
+        let data_type = match *self {
+            CqlType::List(ref item) | CqlType::Set(ref item) => {
+                let data_type = DataType::new(self.value_type());
+                try!(data_type.add_sub_type(try!(item.to_data_type())));
+                data_type
+            }
+            CqlType::Map(ref key, ref value) => {
+                let data_type = DataType::new(self.value_type());
+                try!(data_type.add_sub_type(try!(key.to_data_type())));
+                try!(data_type.add_sub_type(try!(value.to_data_type())));
+                data_type
+            }
+            CqlType::Tuple(ref items) => {
+                let data_type = DataType::new_tuple(items.len() as u64);
+                for item in items {
+                    try!(data_type.add_sub_type(try!(item.to_data_type())));
+                }
+                data_type
+            }
+            CqlType::Udt { ref keyspace, ref name, ref fields } => {
+                let data_type = DataType::new_udt(fields.len() as u64);
+                let c_string = |name: &str| CString::new(name).map_err(|_| CassError::build(CASS_ERROR_LIB_BAD_PARAMS));
+                unsafe {
+                    let keyspace = try!(c_string(keyspace));
+                    try!(CassError::build(cass_data_type_set_keyspace(data_type.inner(), keyspace.as_ptr())).wrap(()));
+                    let name = try!(c_string(name));
+                    try!(CassError::build(cass_data_type_set_type_name(data_type.inner(), name.as_ptr())).wrap(()));
+                    for &(ref field_name, ref field_type) in fields {
+                        let field_name = try!(c_string(field_name));
+                        let field_type = try!(field_type.to_data_type());
+                        try!(CassError::build(cass_data_type_add_sub_type_by_name(data_type.inner(),
+                                                                                  field_name.as_ptr(),
+                                                                                  field_type.inner()))
+                            .wrap(()));
+                    }
+                }
+                data_type
+            }
+            CqlType::Custom(ref class_name) => {
+                let data_type = DataType::new(self.value_type());
+                try!(data_type.set_class_name(class_name.as_str()));
+                data_type
+            }
+            _ => DataType::new(self.value_type()),
+        };
+        Ok(data_type)
+    }
+}
+
+impl PartialEq for CqlType {
+    fn eq(&self, other: &CqlType) -> bool {
+        match (self, other) {
+            (&CqlType::List(ref a), &CqlType::List(ref b)) |
+            (&CqlType::Set(ref a), &CqlType::Set(ref b)) => a == b,
+            (&CqlType::Map(ref a_key, ref a_value), &CqlType::Map(ref b_key, ref b_value)) => {
+                a_key == b_key && a_value == b_value
+            }
+            (&CqlType::Tuple(ref a), &CqlType::Tuple(ref b)) => a == b,
+            (&CqlType::Udt { keyspace: ref a_keyspace, name: ref a_name, fields: ref a_fields },
+             &CqlType::Udt { keyspace: ref b_keyspace, name: ref b_name, fields: ref b_fields }) => {
+                a_keyspace == b_keyspace && a_name == b_name && a_fields == b_fields
+            }
+            (&CqlType::Custom(ref a), &CqlType::Custom(ref b)) => a == b,
+            _ => self.canonical_value_type() == other.canonical_value_type(),
+        }
+    }
+}
+
+impl Eq for CqlType {}
+
+impl Hash for CqlType {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.canonical_value_type() as u32).hash(state);
+        match *self {
+            CqlType::List(ref item) | CqlType::Set(ref item) => item.hash(state),
+            CqlType::Map(ref key, ref value) => {
+                key.hash(state);
+                value.hash(state);
+            }
+            CqlType::Tuple(ref items) => items.hash(state),
+            CqlType::Udt { ref keyspace, ref name, ref fields } => {
+                keyspace.hash(state);
+                name.hash(state);
+                fields.hash(state);
+            }
+            CqlType::Custom(ref class_name) => class_name.hash(state),
+            _ => {}
+        }
+    }
+}
+
+impl Display for CqlType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            CqlType::Ascii => write!(f, "ascii"),
+            CqlType::Bigint => write!(f, "bigint"),
+            CqlType::Blob => write!(f, "blob"),
+            CqlType::Boolean => write!(f, "boolean"),
+            CqlType::Counter => write!(f, "counter"),
+            CqlType::Decimal => write!(f, "decimal"),
+            CqlType::Double => write!(f, "double"),
+            CqlType::Float => write!(f, "float"),
+            CqlType::Int => write!(f, "int"),
+            CqlType::Text => write!(f, "text"),
+            CqlType::Timestamp => write!(f, "timestamp"),
+            CqlType::Uuid => write!(f, "uuid"),
+            CqlType::Varchar => write!(f, "varchar"),
+            CqlType::Varint => write!(f, "varint"),
+            CqlType::Timeuuid => write!(f, "timeuuid"),
+            CqlType::Inet => write!(f, "inet"),
+            CqlType::Date => write!(f, "date"),
+            CqlType::Time => write!(f, "time"),
+            CqlType::SmallInt => write!(f, "smallint"),
+            CqlType::TinyInt => write!(f, "tinyint"),
+            CqlType::List(ref item) => write!(f, "list<{}>", item),
+            CqlType::Set(ref item) => write!(f, "set<{}>", item),
+            CqlType::Map(ref key, ref value) => write!(f, "map<{}, {}>", key, value),
+            CqlType::Tuple(ref items) => {
+                try!(write!(f, "tuple<"));
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        try!(write!(f, ", "));
+                    }
+                    try!(write!(f, "{}", item));
+                }
+                write!(f, ">")
+            }
+            CqlType::Udt { ref keyspace, ref name, .. } => {
+                if keyspace.is_empty() {
+                    write!(f, "{}", name)
+                } else {
+                    write!(f, "{}.{}", keyspace, name)
+                }
+            }
+            CqlType::Custom(ref class_name) => write!(f, "'{}'", class_name),
+        }
+    }
+}
+
+unsafe fn raw_string(data: *const i8, length: u64) -> Result<String, CassError> {
+    str::from_utf8(slice::from_raw_parts(data as *const u8, length as usize))
+        .map(|name| name.to_owned())
+        .map_err(|_| CassError::build(CASS_ERROR_LIB_INVALID_DATA))
+}
+
+unsafe fn read_string<F>(reader: F) -> Result<String, CassError>
+    where F: FnOnce(*mut *const i8, *mut u64) -> ::cassandra_sys::CassError {
+    let mut data = mem::zeroed();
+    let mut length = mem::zeroed();
+    match reader(&mut data, &mut length) {
+        CASS_OK => raw_string(data, length),
+        err => Err(CassError::build(err)),
+    }
+}
+
+unsafe fn cql_type_of(data_type: *const _CassDataType) -> Result<CqlType, CassError> {
+    if data_type.is_null() {
+        return Err(CassError::build(CASS_ERROR_LIB_NULL_VALUE));
+    }
+    let sub_type = |index: u64| cql_type_of(cass_data_type_sub_data_type(data_type, index));
+    Ok(match cass_data_type_type(data_type) {
+        CASS_VALUE_TYPE_ASCII => CqlType::Ascii,
+        CASS_VALUE_TYPE_BIGINT => CqlType::Bigint,
+        CASS_VALUE_TYPE_BLOB => CqlType::Blob,
+        CASS_VALUE_TYPE_BOOLEAN => CqlType::Boolean,
+        CASS_VALUE_TYPE_COUNTER => CqlType::Counter,
+        CASS_VALUE_TYPE_DECIMAL => CqlType::Decimal,
+        CASS_VALUE_TYPE_DOUBLE => CqlType::Double,
+        CASS_VALUE_TYPE_FLOAT => CqlType::Float,
+        CASS_VALUE_TYPE_INT => CqlType::Int,
+        CASS_VALUE_TYPE_TEXT => CqlType::Text,
+        CASS_VALUE_TYPE_TIMESTAMP => CqlType::Timestamp,
+        CASS_VALUE_TYPE_UUID => CqlType::Uuid,
+        CASS_VALUE_TYPE_VARCHAR => CqlType::Varchar,
+        CASS_VALUE_TYPE_VARINT => CqlType::Varint,
+        CASS_VALUE_TYPE_TIMEUUID => CqlType::Timeuuid,
+        CASS_VALUE_TYPE_INET => CqlType::Inet,
+        CASS_VALUE_TYPE_DATE => CqlType::Date,
+        CASS_VALUE_TYPE_TIME => CqlType::Time,
+        CASS_VALUE_TYPE_SMALL_INT => CqlType::SmallInt,
+        CASS_VALUE_TYPE_TINY_INT => CqlType::TinyInt,
+        CASS_VALUE_TYPE_LIST => CqlType::List(Box::new(try!(sub_type(0)))),
+        CASS_VALUE_TYPE_SET => CqlType::Set(Box::new(try!(sub_type(0)))),
+        CASS_VALUE_TYPE_MAP => CqlType::Map(Box::new(try!(sub_type(0))), Box::new(try!(sub_type(1)))),
+        CASS_VALUE_TYPE_TUPLE => {
+            let mut items = vec![];
+            for index in 0..cass_data_sub_type_count(data_type) {
+                items.push(try!(sub_type(index)));
+            }
+            CqlType::Tuple(items)
+        }
+        CASS_VALUE_TYPE_UDT => {
+            let mut fields = vec![];
+            for index in 0..cass_data_sub_type_count(data_type) {
+                let name = try!(read_string(|name, length| {
+                    cass_data_type_sub_type_name(data_type, index, name, length)
+                }));
+                fields.push((name, try!(sub_type(index))));
+            }
+            CqlType::Udt {
+                keyspace: try!(read_string(|name, length| cass_data_type_keyspace(data_type, name, length))),
+                name: try!(read_string(|name, length| cass_data_type_type_name(data_type, name, length))),
+                fields: fields,
+            }
+        }
+        CASS_VALUE_TYPE_CUSTOM => {
+            CqlType::Custom(try!(read_string(|name, length| cass_data_type_class_name(data_type, name, length))))
+        }
+        _ => return Err(CassError::build(CASS_ERROR_LIB_INVALID_VALUE_TYPE)),
+    })
+}
+
+impl ConstDataType {
+    ///Walks this data type and returns an owned description of it.
+    pub fn cql_type(&self) -> Result<CqlType, CassError> {
+        unsafe { cql_type_of(self.0) }
+    }
+}
+
+impl DataType {
+    ///Walks this data type and returns an owned description of it.
+    pub fn cql_type(&self) -> Result<CqlType, CassError> {
+        unsafe { cql_type_of(self.inner()) }
+    }
+}
+
+#[test]
+fn test_cql_type_display() {
+    let udt = CqlType::Udt {
+        keyspace: "examples".to_owned(),
+        name: "address".to_owned(),
+        fields: vec![("street".to_owned(), CqlType::Text)],
+    };
+    let map = CqlType::Map(Box::new(CqlType::Text), Box::new(CqlType::List(Box::new(udt))));
+    assert_eq!(map.to_string(), "map<text, list<examples.address>>");
+    assert_eq!(CqlType::Tuple(vec![CqlType::Int, CqlType::Custom("a.B".to_owned())]).to_string(),
+               "tuple<int, 'a.B'>");
+}
+
+#[test]
+fn test_cql_type_text_is_varchar() {
+    use std::collections::HashSet;
+    let varchars = CqlType::Map(Box::new(CqlType::Varchar), Box::new(CqlType::List(Box::new(CqlType::Varchar))));
+    let texts = CqlType::Map(Box::new(CqlType::Text), Box::new(CqlType::List(Box::new(CqlType::Text))));
+    assert_eq!(varchars, texts);
+    assert!(CqlType::Ascii != CqlType::Text);
+    assert!(CqlType::List(Box::new(CqlType::Text)) != CqlType::Set(Box::new(CqlType::Text)));
+    let types: HashSet<CqlType> = vec![varchars, texts].into_iter().collect();
+    assert_eq!(types.len(), 1);
+}
@@ -0,0 +1,585 @@
+use std::mem;
+use std::slice;
+use std::str;
+use std::ffi::CString;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use cassandra_sys::CASS_ERROR_LIB_BAD_PARAMS;
+use cassandra_sys::CASS_ERROR_LIB_INVALID_DATA;
+use cassandra_sys::CASS_ERROR_LIB_INVALID_VALUE_TYPE;
+use cassandra_sys::CASS_ERROR_LIB_NULL_VALUE;
+use cassandra_sys::CASS_OK;
+use cassandra_sys::CASS_VALUE_TYPE_ASCII;
+use cassandra_sys::CASS_VALUE_TYPE_BIGINT;
+use cassandra_sys::CASS_VALUE_TYPE_BLOB;
+use cassandra_sys::CASS_VALUE_TYPE_BOOLEAN;
+use cassandra_sys::CASS_VALUE_TYPE_COUNTER;
+use cassandra_sys::CASS_VALUE_TYPE_CUSTOM;
+use cassandra_sys::CASS_VALUE_TYPE_DATE;
+use cassandra_sys::CASS_VALUE_TYPE_DECIMAL;
+use cassandra_sys::CASS_VALUE_TYPE_DOUBLE;
+use cassandra_sys::CASS_VALUE_TYPE_FLOAT;
+use cassandra_sys::CASS_VALUE_TYPE_INET;
+use cassandra_sys::CASS_VALUE_TYPE_INT;
+use cassandra_sys::CASS_VALUE_TYPE_LIST;
+use cassandra_sys::CASS_VALUE_TYPE_MAP;
+use cassandra_sys::CASS_VALUE_TYPE_SET;
+use cassandra_sys::CASS_VALUE_TYPE_SMALL_INT;
+use cassandra_sys::CASS_VALUE_TYPE_TEXT;
+use cassandra_sys::CASS_VALUE_TYPE_TIME;
+use cassandra_sys::CASS_VALUE_TYPE_TIMESTAMP;
+use cassandra_sys::CASS_VALUE_TYPE_TIMEUUID;
+use cassandra_sys::CASS_VALUE_TYPE_TINY_INT;
+use cassandra_sys::CASS_VALUE_TYPE_TUPLE;
+use cassandra_sys::CASS_VALUE_TYPE_UDT;
+use cassandra_sys::CASS_VALUE_TYPE_UUID;
+use cassandra_sys::CASS_VALUE_TYPE_VARCHAR;
+use cassandra_sys::CASS_VALUE_TYPE_VARINT;
+use cassandra_sys::CassValue as _CassValue;
+use cassandra_sys::cass_false;
+use cassandra_sys::cass_inet_init_v6;
+use cassandra_sys::cass_iterator_fields_from_user_type;
+use cassandra_sys::cass_iterator_free;
+use cassandra_sys::cass_iterator_from_collection;
+use cassandra_sys::cass_iterator_from_map;
+use cassandra_sys::cass_iterator_from_tuple;
+use cassandra_sys::cass_iterator_get_user_type_field_name;
+use cassandra_sys::cass_iterator_get_user_type_field_value;
+use cassandra_sys::cass_iterator_next;
+use cassandra_sys::cass_tuple_set_collection;
+use cassandra_sys::cass_tuple_set_inet;
+use cassandra_sys::cass_user_type_set_collection_by_name;
+use cassandra_sys::cass_value_get_inet;
+use cassandra_sys::cass_value_get_uint32;
+use cassandra_sys::cass_value_type;
+
+use cassandra::codec::CodecRegistry;
+use cassandra::collection::{CassCollection, List, Map, Set};
+use cassandra::column::Column;
+use cassandra::cql_type::CqlType;
+use cassandra::error::CassError;
+use cassandra::inet::Inet;
+use cassandra::iterator::{MapIterator, SetIterator, TupleIterator};
+use cassandra::row::Row;
+use cassandra::statement::{Bound, Statement, bind_owned_user_type, named_indices, record_value};
+use cassandra::tuple::Tuple;
+use cassandra::user_type::UserType;
+use cassandra::uuid::Uuid;
+use cassandra::value::Value;
+use cassandra::util::Protected;
+
+///An owned Cassandra value, detached from any result or statement.
+///
+///This is the common currency between type codecs and the driver: codecs
+///encode Rust values into a `CqlValue`, which can then be bound to a
+///statement or placed in a collection, tuple or UDT.
+#[derive(Clone,Debug,PartialEq)]
+pub enum CqlValue {
+    ///A null value
+    Null,
+    ///A "boolean"
+    Boolean(bool),
+    ///A "tinyint"
+    TinyInt(i8),
+    ///A "smallint"
+    SmallInt(i16),
+    ///An "int"
+    Int(i32),
+    ///A "bigint", "counter", "timestamp" or "time"
+    Bigint(i64),
+    ///A "date"
+    Date(u32),
+    ///A "float"
+    Float(f32),
+    ///A "double"
+    Double(f64),
+    ///An "ascii", "text" or "varchar"
+    Text(String),
+    ///A "blob", "varint", "decimal" or "custom" in its serialized form
+    Blob(Vec<u8>),
+    ///A "uuid" or "timeuuid"
+    Uuid(Uuid),
+    ///An "inet"
+    Inet(IpAddr),
+    ///A "list"
+    List(Vec<CqlValue>),
+    ///A "set"
+    Set(Vec<CqlValue>),
+    ///A "map", as key/value pairs
+    Map(Vec<(CqlValue, CqlValue)>),
+    ///A "tuple"
+    Tuple(Vec<CqlValue>),
+    ///A user defined type and its field values
+    Udt(CqlType, Vec<(String, CqlValue)>),
+}
+
+macro_rules! cql_value_from {
+    ($rust_type:ty, $variant:ident) => {
+        impl From<$rust_type> for CqlValue {
+            fn from(value: $rust_type) -> CqlValue {
+                CqlValue::$variant(value)
+            }
+        }
+    };
+}
+
+cql_value_from!(bool, Boolean);
+cql_value_from!(i8, TinyInt);
+cql_value_from!(i16, SmallInt);
+cql_value_from!(i32, Int);
+cql_value_from!(i64, Bigint);
+cql_value_from!(f32, Float);
+cql_value_from!(f64, Double);
+cql_value_from!(String, Text);
+cql_value_from!(Vec<u8>, Blob);
+cql_value_from!(Uuid, Uuid);
+cql_value_from!(IpAddr, Inet);
+
+impl<'a> From<&'a str> for CqlValue {
+    fn from(value: &'a str) -> CqlValue {
+        CqlValue::Text(value.to_owned())
+    }
+}
+
+fn push_int(output: &mut Vec<u8>, value: i32) {
+    output.extend_from_slice(&[(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]);
+}
+
+fn push_bytes(output: &mut Vec<u8>, value: Option<Vec<u8>>) {
+    match value {
+        Some(bytes) => {
+            push_int(output, bytes.len() as i32);
+            output.extend(bytes);
+        }
+        None => push_int(output, -1),
+    }
+}
+
+fn be_bytes(value: u64, width: usize) -> Vec<u8> {
+    (0..width).map(|i| (value >> (8 * (width - 1 - i))) as u8).collect()
+}
+
+impl CqlValue {
+    ///The value in the serialized form of the native protocol (version 3
+    ///and later), or `None` for null.
+    pub fn serialize(&self) -> Option<Vec<u8>> {
+        Some(match *self {
+            CqlValue::Null => return None,
+            CqlValue::Boolean(v) => vec![v as u8],
+            CqlValue::TinyInt(v) => vec![v as u8],
+            CqlValue::SmallInt(v) => be_bytes(v as u64, 2),
+            CqlValue::Int(v) => be_bytes(v as u64, 4),
+            CqlValue::Bigint(v) => be_bytes(v as u64, 8),
+            CqlValue::Date(v) => be_bytes(v as u64, 4),
+            CqlValue::Float(v) => be_bytes(v.to_bits() as u64, 4),
+            CqlValue::Double(v) => be_bytes(v.to_bits(), 8),
+            CqlValue::Text(ref v) => v.as_bytes().to_vec(),
+            CqlValue::Blob(ref v) => v.clone(),
+            CqlValue::Uuid(ref v) => v.to_bytes().to_vec(),
+            CqlValue::Inet(IpAddr::V4(v)) => v.octets().to_vec(),
+            CqlValue::Inet(IpAddr::V6(v)) => v.octets().to_vec(),
+            CqlValue::List(ref items) |
+            CqlValue::Set(ref items) => {
+                let mut output = vec![];
+                push_int(&mut output, items.len() as i32);
+                for item in items {
+                    push_bytes(&mut output, item.serialize());
+                }
+                output
+            }
+            CqlValue::Map(ref entries) => {
+                let mut output = vec![];
+                push_int(&mut output, entries.len() as i32);
+                for &(ref key, ref value) in entries {
+                    push_bytes(&mut output, key.serialize());
+                    push_bytes(&mut output, value.serialize());
+                }
+                output
+            }
+            CqlValue::Tuple(ref items) => {
+                let mut output = vec![];
+                for item in items {
+                    push_bytes(&mut output, item.serialize());
+                }
+                output
+            }
+            CqlValue::Udt(_, ref fields) => {
+                let mut output = vec![];
+                for &(_, ref value) in fields {
+                    push_bytes(&mut output, value.serialize());
+                }
+                output
+            }
+        })
+    }
+}
+
+fn invalid_type() -> CassError {
+    CassError::build(CASS_ERROR_LIB_INVALID_VALUE_TYPE)
+}
+
+fn to_inet(address: &IpAddr) -> Inet {
+    match *address {
+        IpAddr::V4(v4) => Inet::cass_inet_init_v4(v4),
+        IpAddr::V6(v6) => unsafe { Inet::build(cass_inet_init_v6(v6.octets().as_ptr())) },
+    }
+}
+
+fn to_list(items: &[CqlValue]) -> Result<List, CassError> {
+    let mut list = List::new(items.len() as u64);
+    for item in items {
+        try!(list.append_value(item));
+    }
+    Ok(list)
+}
+
+fn to_set(items: &[CqlValue]) -> Result<Set, CassError> {
+    let mut set = Set::new(items.len() as u64);
+    for item in items {
+        try!(set.append_value(item));
+    }
+    Ok(set)
+}
+
+fn to_map(entries: &[(CqlValue, CqlValue)]) -> Result<Map, CassError> {
+    let mut map = Map::new(entries.len() as u64);
+    for &(ref key, ref value) in entries {
+        try!(map.append_value(key));
+        try!(map.append_value(value));
+    }
+    Ok(map)
+}
+
+fn to_tuple(items: &[CqlValue]) -> Result<Tuple, CassError> {
+    let mut tuple = Tuple::new(items.len() as u64);
+    for (index, item) in items.iter().enumerate() {
+        try!(tuple.set_value(index as u64, item));
+    }
+    Ok(tuple)
+}
+
+fn to_user_type(cql_type: &CqlType, fields: &[(String, CqlValue)]) -> Result<UserType, CassError> {
+    let mut user_type = try!(cql_type.to_data_type()).new_user_type();
+    for &(ref name, ref value) in fields {
+        try!(user_type.set_value_by_name(name, value));
+    }
+    Ok(user_type)
+}
+
+///Appends an owned value to any of the collection types
+pub fn append_value<C: CassCollection>(collection: &mut C, value: &CqlValue) -> Result<(), CassError> {
+    match *value {
+        CqlValue::Null => Err(CassError::build(CASS_ERROR_LIB_NULL_VALUE)),
+        CqlValue::Boolean(v) => collection.append_bool(v).map(|_| ()),
+        CqlValue::TinyInt(v) => collection.append_int8(v).map(|_| ()),
+        CqlValue::SmallInt(v) => collection.append_int16(v).map(|_| ()),
+        CqlValue::Int(v) => collection.append_int32(v).map(|_| ()),
+        CqlValue::Bigint(v) => collection.append_int64(v).map(|_| ()),
+        CqlValue::Date(v) => collection.append_uint32(v).map(|_| ()),
+        CqlValue::Float(v) => collection.append_float(v).map(|_| ()),
+        CqlValue::Double(v) => collection.append_double(v).map(|_| ()),
+        CqlValue::Text(ref v) => collection.append_string(v).map(|_| ()),
+        CqlValue::Blob(ref v) => collection.append_bytes(v.clone()).map(|_| ()),
+        CqlValue::Uuid(v) => collection.append_uuid(v).map(|_| ()),
+        CqlValue::Inet(ref v) => collection.append_inet(to_inet(v)).map(|_| ()),
+        CqlValue::List(ref items) => collection.append_list(try!(to_list(items))).map(|_| ()),
+        CqlValue::Set(ref items) => collection.append_set(try!(to_set(items))).map(|_| ()),
+        CqlValue::Map(ref entries) => collection.append_map(try!(to_map(entries))).map(|_| ()),
+        CqlValue::Tuple(ref items) => collection.append_tuple(try!(to_tuple(items))).map(|_| ()),
+        CqlValue::Udt(ref cql_type, ref fields) => {
+            collection.append_user_type(&try!(to_user_type(cql_type, fields))).map(|_| ())
+        }
+    }
+}
+
+impl Statement {
+    ///Binds an owned value to a query or bound statement at the specified index.
+    pub fn bind_value(&mut self, index: u64, value: &CqlValue) -> Result<&mut Self, CassError> {
+        try!(match *value {
+            CqlValue::Null => self.bind_null(index),
+            CqlValue::Boolean(v) => self.bind_bool(index, v),
+            CqlValue::TinyInt(v) => self.bind_int8(index, v),
+            CqlValue::SmallInt(v) => self.bind_int16(index, v),
+            CqlValue::Int(v) => self.bind_int32(index, v),
+            CqlValue::Bigint(v) => self.bind_int64(index, v),
+            CqlValue::Date(v) => self.bind_uint32(index, v),
+            CqlValue::Float(v) => self.bind_float(index, v),
+            CqlValue::Double(v) => self.bind_double(index, v),
+            CqlValue::Text(ref v) => self.bind_string(index, v),
+            CqlValue::Blob(ref v) => self.bind_bytes(index, v.clone()),
+            CqlValue::Uuid(v) => self.bind_uuid(index, v),
+            CqlValue::Inet(ref v) => self.bind_inet(index, to_inet(v)),
+            CqlValue::List(ref items) => self.bind_list(index, try!(to_list(items))),
+            CqlValue::Set(ref items) => self.bind_set(index, try!(to_set(items))),
+            CqlValue::Map(ref entries) => self.bind_map(index, try!(to_map(entries))),
+            CqlValue::Tuple(ref items) => self.bind_tuple(index, try!(to_tuple(items))),
+            CqlValue::Udt(ref cql_type, ref fields) => {
+                bind_owned_user_type(self, index, try!(to_user_type(cql_type, fields)))
+            }
+        });
+        // values the driver holds in opaque objects
+        match *value {
+            CqlValue::Inet(_) | CqlValue::List(_) | CqlValue::Set(_) | CqlValue::Map(_) | CqlValue::Tuple(_) |
+            CqlValue::Udt(..) => {
+                let bytes = value.serialize();
+                record_value(self, index, bytes.as_ref().map_or(Bound::Null, |bytes| Bound::Bytes(bytes)));
+            }
+            _ => {}
+        }
+        Ok(self)
+    }
+
+    ///Binds an owned value to all the values with the specified name.
+    pub fn bind_value_by_name(&mut self, name: &str, value: &CqlValue) -> Result<&mut Self, CassError> {
+        if let Some(indices) = named_indices(self, name) {
+            for index in indices {
+                try!(self.bind_value(index, value));
+            }
+            return Ok(self);
+        }
+        match *value {
+            CqlValue::Null => self.bind_null_by_name(name),
+            CqlValue::Boolean(v) => self.bind_bool_by_name(name, v),
+            CqlValue::TinyInt(v) => self.bind_int8_by_name(name, v),
+            CqlValue::SmallInt(v) => self.bind_int16_by_name(name, v),
+            CqlValue::Int(v) => self.bind_int32_by_name(name, v),
+            CqlValue::Bigint(v) => self.bind_int64_by_name(name, v),
+            CqlValue::Date(v) => self.bind_uint32_by_name(name, v),
+            CqlValue::Float(v) => self.bind_float_by_name(name, v),
+            CqlValue::Double(v) => self.bind_double_by_name(name, v),
+            CqlValue::Text(ref v) => self.bind_string_by_name(name, v),
+            CqlValue::Blob(ref v) => self.bind_bytes_by_name(name, v.clone()),
+            CqlValue::Uuid(v) => self.bind_uuid_by_name(name, v),
+            CqlValue::Inet(ref v) => self.bind_inet_by_name(name, to_inet(v)),
+            CqlValue::List(ref items) => self.bind_list_by_name(name, try!(to_list(items))),
+            CqlValue::Set(ref items) => self.bind_set_by_name(name, try!(to_set(items))),
+            CqlValue::Map(ref entries) => self.bind_map_by_name(name, try!(to_map(entries))),
+            CqlValue::Tuple(ref items) => self.bind_tuple_by_name(name, try!(to_tuple(items))),
+            CqlValue::Udt(ref cql_type, ref fields) => {
+                self.bind_user_type_by_name(name, &try!(to_user_type(cql_type, fields)))
+            }
+        }
+    }
+}
+
+impl Tuple {
+    ///Sets an owned value in a tuple at the specified index.
+    pub fn set_value(&mut self, index: u64, value: &CqlValue) -> Result<(), CassError> {
+        match *value {
+            CqlValue::Null => self.set_null(index),
+            CqlValue::Boolean(v) => self.set_bool(index, v),
+            CqlValue::TinyInt(v) => self.set_int8(index, v),
+            CqlValue::SmallInt(v) => self.set_int16(index, v),
+            CqlValue::Int(v) => self.set_int32(index, v),
+            CqlValue::Bigint(v) => self.set_int64(index, v),
+            CqlValue::Date(v) => self.set_uint32(index, v),
+            CqlValue::Float(v) => self.set_float(index, v),
+            CqlValue::Double(v) => self.set_double(index, v),
+            CqlValue::Text(ref v) => self.set_string(index, v.as_str()),
+            CqlValue::Blob(ref v) => self.set_bytes(index, v.clone()),
+            CqlValue::Uuid(v) => self.set_uuid(index, v),
+            CqlValue::Inet(ref v) => unsafe {
+                CassError::build(cass_tuple_set_inet(self.inner(), index, to_inet(v).inner())).wrap(())
+            },
+            CqlValue::List(ref items) => {
+                let list = try!(to_list(items));
+                unsafe { CassError::build(cass_tuple_set_collection(self.inner(), index, list.inner())).wrap(()) }
+            }
+            CqlValue::Set(ref items) => self.set_collection(index, try!(to_set(items))),
+            CqlValue::Map(ref entries) => {
+                let map = try!(to_map(entries));
+                unsafe { CassError::build(cass_tuple_set_collection(self.inner(), index, map.inner())).wrap(()) }
+            }
+            CqlValue::Tuple(ref items) => self.set_tuple(index, try!(to_tuple(items))),
+            CqlValue::Udt(ref cql_type, ref fields) => self.set_user_type(index, &try!(to_user_type(cql_type, fields))),
+        }
+    }
+}
+
+impl UserType {
+    ///Sets an owned value in a user defined type at the specified name.
+    pub fn set_value_by_name(&mut self, name: &str, value: &CqlValue) -> Result<(), CassError> {
+        match *value {
+            CqlValue::Null => self.set_null_by_name(name),
+            CqlValue::Boolean(v) => self.set_bool_by_name(name, v),
+            CqlValue::TinyInt(v) => self.set_int8_by_name(name, v),
+            CqlValue::SmallInt(v) => self.set_int16_by_name(name, v),
+            CqlValue::Int(v) => self.set_int32_by_name(name, v),
+            CqlValue::Bigint(v) => self.set_int64_by_name(name, v),
+            CqlValue::Date(v) => self.set_uint32_by_name(name, v),
+            CqlValue::Float(v) => self.set_float_by_name(name, v),
+            CqlValue::Double(v) => self.set_double_by_name(name, v),
+            CqlValue::Text(ref v) => self.set_string_by_name(name, v.as_str()),
+            CqlValue::Blob(ref v) => self.set_bytes_by_name(name, v.clone()),
+            CqlValue::Uuid(v) => self.set_uuid_by_name(name, v),
+            CqlValue::Inet(ref v) => self.set_inet_by_name(name, to_inet(v)),
+            CqlValue::List(ref items) => {
+                let list = try!(to_list(items));
+                self.set_raw_collection_by_name(name, list.inner())
+            }
+            CqlValue::Set(ref items) => self.set_collection_by_name(name, try!(to_set(items))),
+            CqlValue::Map(ref entries) => {
+                let map = try!(to_map(entries));
+                self.set_raw_collection_by_name(name, map.inner())
+            }
+            CqlValue::Tuple(ref items) => self.set_tuple_by_name(name, try!(to_tuple(items))),
+            CqlValue::Udt(ref cql_type, ref fields) => {
+                self.set_user_type_by_name(name, try!(to_user_type(cql_type, fields)))
+            }
+        }
+    }
+
+    fn set_raw_collection_by_name(&mut self, name: &str, collection: *mut ::cassandra_sys::CassCollection)
+                                  -> Result<(), CassError> {
+        let name = try!(CString::new(name).map_err(|_| CassError::build(CASS_ERROR_LIB_BAD_PARAMS)));
+        unsafe {
+            CassError::build(cass_user_type_set_collection_by_name(self.inner(), name.as_ptr(), collection)).wrap(())
+        }
+    }
+}
+
+unsafe fn from_inet(value: *const _CassValue) -> Result<IpAddr, CassError> {
+    let mut inet = mem::zeroed();
+    try!(CassError::build(cass_value_get_inet(value, &mut inet)).wrap(()));
+    let inet: ::cassandra_sys::CassInet = inet;
+    let raw = inet.address;
+    match inet.address_length {
+        4 => Ok(IpAddr::V4(Ipv4Addr::new(raw[0], raw[1], raw[2], raw[3]))),
+        16 => Ok(IpAddr::V6(Ipv6Addr::from(raw))),
+        _ => Err(invalid_type()),
+    }
+}
+
+unsafe fn decode(value: *const _CassValue, codecs: &CodecRegistry) -> Result<CqlValue, CassError> {
+    let wrapped = Value::build(value);
+    if wrapped.is_null() {
+        return Ok(CqlValue::Null);
+    }
+    Ok(match cass_value_type(value) {
+        CASS_VALUE_TYPE_ASCII | CASS_VALUE_TYPE_TEXT | CASS_VALUE_TYPE_VARCHAR => {
+            CqlValue::Text(try!(wrapped.get_string()))
+        }
+        CASS_VALUE_TYPE_BIGINT | CASS_VALUE_TYPE_COUNTER | CASS_VALUE_TYPE_TIMESTAMP | CASS_VALUE_TYPE_TIME => {
+            CqlValue::Bigint(try!(wrapped.get_i64()))
+        }
+        CASS_VALUE_TYPE_BLOB | CASS_VALUE_TYPE_VARINT | CASS_VALUE_TYPE_DECIMAL => {
+            CqlValue::Blob(try!(wrapped.get_bytes()).to_vec())
+        }
+        CASS_VALUE_TYPE_BOOLEAN => CqlValue::Boolean(try!(wrapped.get_bool())),
+        CASS_VALUE_TYPE_DOUBLE => CqlValue::Double(try!(wrapped.get_dbl())),
+        CASS_VALUE_TYPE_FLOAT => CqlValue::Float(try!(wrapped.get_flt())),
+        CASS_VALUE_TYPE_INT => CqlValue::Int(try!(wrapped.get_i32())),
+        CASS_VALUE_TYPE_SMALL_INT => CqlValue::SmallInt(try!(wrapped.get_i16())),
+        CASS_VALUE_TYPE_TINY_INT => CqlValue::TinyInt(try!(wrapped.get_i8())),
+        CASS_VALUE_TYPE_DATE => {
+            let mut output = mem::zeroed();
+            try!(CassError::build(cass_value_get_uint32(value, &mut output)).wrap(()));
+            CqlValue::Date(output)
+        }
+        CASS_VALUE_TYPE_UUID | CASS_VALUE_TYPE_TIMEUUID => CqlValue::Uuid(try!(wrapped.get_uuid())),
+        CASS_VALUE_TYPE_INET => CqlValue::Inet(try!(from_inet(value))),
+        CASS_VALUE_TYPE_LIST | CASS_VALUE_TYPE_SET => {
+            let mut items = vec![];
+            for item in SetIterator::build(cass_iterator_from_collection(value)) {
+                items.push(try!(decode(item.inner(), codecs)));
+            }
+            if cass_value_type(value) == CASS_VALUE_TYPE_LIST {
+                CqlValue::List(items)
+            } else {
+                CqlValue::Set(items)
+            }
+        }
+        CASS_VALUE_TYPE_MAP => {
+            let mut entries = vec![];
+            for (key, item) in MapIterator::build(cass_iterator_from_map(value)) {
+                entries.push((try!(decode(key.inner(), codecs)), try!(decode(item.inner(), codecs))));
+            }
+            CqlValue::Map(entries)
+        }
+        CASS_VALUE_TYPE_TUPLE => {
+            let mut items = vec![];
+            for item in TupleIterator(cass_iterator_from_tuple(value)) {
+                items.push(try!(decode(item.inner(), codecs)));
+            }
+            CqlValue::Tuple(items)
+        }
+        CASS_VALUE_TYPE_UDT => {
+            let cql_type = try!(wrapped.data_type().cql_type());
+            let iterator = cass_iterator_fields_from_user_type(value);
+            let mut fields = vec![];
+            let mut result = Ok(());
+            while cass_iterator_next(iterator) != cass_false {
+                let mut name = mem::zeroed();
+                let mut name_length = mem::zeroed();
+                match cass_iterator_get_user_type_field_name(iterator, &mut name, &mut name_length) {
+                    CASS_OK => {}
+                    err => {
+                        result = Err(CassError::build(err));
+                        break;
+                    }
+                }
+                let name = match str::from_utf8(slice::from_raw_parts(name as *const u8, name_length as usize)) {
+                    Ok(name) => name.to_owned(),
+                    Err(_) => {
+                        result = Err(CassError::build(CASS_ERROR_LIB_INVALID_DATA));
+                        break;
+                    }
+                };
+                match decode(cass_iterator_get_user_type_field_value(iterator), codecs) {
+                    Ok(field) => fields.push((name, field)),
+                    Err(err) => {
+                        result = Err(err);
+                        break;
+                    }
+                }
+            }
+            cass_iterator_free(iterator);
+            try!(result);
+            CqlValue::Udt(cql_type, fields)
+        }
+        CASS_VALUE_TYPE_CUSTOM => {
+            let bytes = try!(wrapped.get_bytes());
+            match try!(wrapped.data_type().cql_type()) {
+                CqlType::Custom(ref class_name) => {
+                    match codecs.decode_custom(class_name, bytes) {
+                        Some(decoded) => try!(decoded),
+                        None => CqlValue::Blob(bytes.to_vec()),
+                    }
+                }
+                _ => CqlValue::Blob(bytes.to_vec()),
+            }
+        }
+        _ => return Err(invalid_type()),
+    })
+}
+
+impl Value {
+    ///Copies this value into an owned `CqlValue`, decoding custom types
+    ///with any decoders registered for their marshal class.
+    pub fn cql_value(&self, codecs: &CodecRegistry) -> Result<CqlValue, CassError> {
+        unsafe { decode(self.inner(), codecs) }
+    }
+}
+
+impl Column {
+    ///Copies this column into an owned `CqlValue`, decoding custom types
+    ///with any decoders registered for their marshal class.
+    pub fn cql_value(&self, codecs: &CodecRegistry) -> Result<CqlValue, CassError> {
+        unsafe { decode(self.inner(), codecs) }
+    }
+}
+
+impl Row {
+    ///Copies the column at the specified index into an owned `CqlValue`,
+    ///decoding custom types with the row's registry.
+    pub fn get_value(&self, index: u64) -> Result<CqlValue, CassError> {
+        try!(self.get_column(index)).cql_value(self.codec_registry())
+    }
+
+    ///Copies the column with the specified name into an owned `CqlValue`,
+    ///decoding custom types with the row's registry.
+    pub fn get_value_by_name<S>(&self, name: S) -> Result<CqlValue, CassError>
+        where S: Into<String> {
+        try!(self.get_column_by_name(name)).cql_value(self.codec_registry())
+    }
+}
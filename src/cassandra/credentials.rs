@@ -0,0 +1,158 @@
+use std::env;
+use std::fmt::{Debug, Formatter};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+///A username and password
+#[derive(Clone,PartialEq,Eq)]
+pub struct Credentials {
+    ///The username
+    pub username: String,
+    ///The password
+    pub password: String,
+}
+
+impl Credentials {
+    ///Credentials for `username`
+    pub fn new(username: &str, password: &str) -> Credentials {
+        Credentials {
+            username: username.to_owned(),
+            password: password.to_owned(),
+        }
+    }
+}
+
+impl Debug for Credentials {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Credentials {{ username: {:?}, password: <hidden> }}", self.username)
+    }
+}
+
+///The current credentials, asked for each time a new connection
+///authenticates, so that passwords can be rotated without restarting.
+///
+///Set with `Cluster::set_credentials_provider`. Fixed credentials, and
+///closures returning them, are providers too.
+pub trait CredentialsProvider: Send + Sync {
+    ///The credentials to authenticate the next connection with, or why
+    ///there are none
+    fn credentials(&self) -> Result<Credentials, String>;
+}
+
+impl CredentialsProvider for Credentials {
+    fn credentials(&self) -> Result<Credentials, String> {
+        Ok(self.clone())
+    }
+}
+
+impl<F: Fn() -> Result<Credentials, String> + Send + Sync> CredentialsProvider for F {
+    fn credentials(&self) -> Result<Credentials, String> {
+        self()
+    }
+}
+
+///Credentials from a file holding the username on its first line and the
+///password on its second, read again whenever the file changes.
+pub struct FileCredentials {
+    path: PathBuf,
+    cached: Mutex<Option<(SystemTime, u64, Credentials)>>,
+}
+
+impl FileCredentials {
+    ///Credentials from the file at `path`
+    pub fn new<P: AsRef<Path>>(path: P) -> FileCredentials {
+        FileCredentials {
+            path: path.as_ref().to_owned(),
+            cached: Mutex::new(None),
+        }
+    }
+
+    fn read(&self) -> Result<Credentials, String> {
+        let mut contents = String::new();
+        try!(File::open(&self.path)
+                 .and_then(|mut file| file.read_to_string(&mut contents))
+                 .map_err(|err| format!("cannot read {}: {}", self.path.display(), err)));
+        let mut lines = contents.lines();
+        match (lines.next(), lines.next()) {
+            (Some(username), Some(password)) if !username.is_empty() => Ok(Credentials::new(username, password)),
+            _ => Err(format!("{} must hold a username line and a password line", self.path.display())),
+        }
+    }
+}
+
+impl CredentialsProvider for FileCredentials {
+    fn credentials(&self) -> Result<Credentials, String> {
+        let metadata = try!(fs::metadata(&self.path)
+                                .map_err(|err| format!("cannot read {}: {}", self.path.display(), err)));
+        // the length catches rewrites within the file system's timestamp granularity
+        let version = (try!(metadata.modified().map_err(|err| err.to_string())), metadata.len());
+        let mut cached = self.cached.lock().expect("poisoned credentials cache");
+        if let Some((modified, len, ref credentials)) = *cached {
+            if (modified, len) == version {
+                return Ok(credentials.clone());
+            }
+        }
+        let credentials = try!(self.read());
+        debug!("read credentials for {} from {}", credentials.username, self.path.display());
+        *cached = Some((version.0, version.1, credentials.clone()));
+        Ok(credentials)
+    }
+}
+
+///Credentials from two environment variables, read each time they are
+///needed.
+#[derive(Clone,Debug)]
+pub struct EnvCredentials {
+    username_var: String,
+    password_var: String,
+}
+
+impl EnvCredentials {
+    ///Credentials from the variables named `username_var` and
+    ///`password_var`
+    pub fn new(username_var: &str, password_var: &str) -> EnvCredentials {
+        EnvCredentials {
+            username_var: username_var.to_owned(),
+            password_var: password_var.to_owned(),
+        }
+    }
+}
+
+impl CredentialsProvider for EnvCredentials {
+    fn credentials(&self) -> Result<Credentials, String> {
+        let var = |name: &str| env::var(name).map_err(|err| format!("{}: {}", name, err));
+        Ok(Credentials {
+            username: try!(var(&self.username_var)),
+            password: try!(var(&self.password_var)),
+        })
+    }
+}
+
+#[test]
+fn test_file_credentials_reload() {
+    use std::io::Write;
+
+    let path = env::temp_dir().join(format!("cassandra-rs-credentials-{}", ::time::precise_time_ns()));
+    let write = |contents: &str| File::create(&path).unwrap().write_all(contents.as_bytes()).unwrap();
+    let provider = FileCredentials::new(&path);
+    assert!(provider.credentials().is_err());
+
+    write("app\nfirst\n");
+    assert_eq!(provider.credentials().unwrap(), Credentials::new("app", "first"));
+    write("app\nrotated\n");
+    assert_eq!(provider.credentials().unwrap(), Credentials::new("app", "rotated"));
+    write("app\n");
+    assert!(provider.credentials().is_err());
+    fs::remove_file(&path).unwrap();
+
+    env::set_var("CASSANDRA_RS_TEST_USER", "env");
+    env::set_var("CASSANDRA_RS_TEST_PASSWORD", "secret");
+    let provider = EnvCredentials::new("CASSANDRA_RS_TEST_USER", "CASSANDRA_RS_TEST_PASSWORD");
+    assert_eq!(provider.credentials().unwrap(), Credentials::new("env", "secret"));
+    assert_eq!(format!("{:?}", Credentials::new("env", "secret")),
+               "Credentials { username: \"env\", password: <hidden> }");
+}
@@ -20,7 +20,7 @@ use cassandra_sys::cass_error_result_responses_required;
 use cassandra_sys::cass_error_result_table;
 use cassandra_sys::cass_error_result_write_type;
 
-use cassandra::consistency::Consistency;
+use cassandra::consistency::{Consistency, consistency_of};
 use cassandra::write_type::WriteType;
 use cassandra_sys::CassError as _CassError;
 use cassandra::util::Protected;
@@ -178,8 +178,10 @@ impl CassErrorResult {
     ///  <li>CASS_ERROR_SERVER_WRITE_FAILURE</li>
     ///  <li>CASS_ERROR_SERVER_UNAVAILABLE</li>
     /// </ul>
-    pub fn result_consistency(&self) -> Consistency {
-        unsafe { Consistency::build(cass_error_result_consistency(self.0)) }
+    ///
+    ///`None` for other errors.
+    pub fn result_consistency(&self) -> Option<Consistency> {
+        unsafe { consistency_of(cass_error_result_consistency(self.0)) }
     }
 
     /// Gets the actual number of received responses, received acknowledgments
@@ -0,0 +1,428 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::mem;
+use std::os::raw;
+use std::ptr;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::thread;
+use std::time::Instant;
+
+use futures::task::Task;
+
+use cassandra::error::{CassError, CassErrorResult};
+use cassandra::metrics::record_request;
+use cassandra::policy::retry_strategy::{NextAttempt, Retrier};
+use cassandra::prepared_cache::PreparedCache;
+use cassandra::shutdown::RequestGuard;
+use cassandra::util::Protected;
+
+use cassandra_sys::CassError as _CassError;
+use cassandra_sys::CassFuture as _Future;
+use cassandra_sys::CASS_OK;
+use cassandra_sys::CASS_ERROR_LIB_REQUEST_TIMED_OUT;
+use cassandra_sys::cass_future_error_code;
+use cassandra_sys::cass_future_free;
+use cassandra_sys::cass_future_get_error_result;
+use cassandra_sys::cass_future_set_callback;
+
+///How an execution ended
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Outcome {
+    ///The future of the last attempt holds the result or the error
+    Attempt,
+    ///The `RetryStrategy` ignored the error of the last attempt
+    Ignored,
+    ///The deadline of the `RetryStrategy` passed first
+    TimedOut,
+    ///The statement could not be sent again
+    Failed(_CassError),
+}
+
+struct State {
+    future: *mut _Future,
+    outcome: Option<Outcome>,
+    retrier: Option<Retrier>,
+    task: Option<Task>,
+    keyspace: Option<(Arc<PreparedCache>, String)>,
+    guard: Option<RequestGuard>,
+    // futures of earlier attempts and prepares, freed with the execution
+    // rather than from their own callbacks
+    retired: Vec<*mut _Future>,
+}
+
+///Follows the execution of a statement through every attempt its
+///`Retrier` sends, and completes once the last one has.
+///
+///Attempts are watched with driver callbacks, so retries are decided and
+///sent as soon as an attempt fails, whether or not anything waits on the
+///execution. Delays before a retry and the strategy's deadline are kept by
+///a timer thread shared by all sessions.
+///
+///A tracked execution counts as in flight for `Session::shutdown` until it
+///completes, when its outcome is counted in the crate's counters.
+pub struct Execution {
+    state: Mutex<State>,
+    completed: Condvar,
+}
+
+unsafe impl Send for Execution {}
+unsafe impl Sync for Execution {}
+
+impl Drop for Execution {
+    fn drop(&mut self) {
+        let state = match self.state.get_mut() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        for future in state.retired.drain(..).chain(Some(state.future)) {
+            if !future.is_null() {
+                unsafe { cass_future_free(future) }
+            }
+        }
+    }
+}
+
+impl Execution {
+    ///Follows the execution whose first attempt is `future`. Once it
+    ///succeeds, the keyspace, if given, becomes the cache's.
+    pub fn start(future: *mut _Future,
+                 retrier: Option<Retrier>,
+                 keyspace: Option<(Arc<PreparedCache>, String)>)
+                 -> Arc<Execution> {
+        let deadline = retrier.as_ref().and_then(|retrier| retrier.deadline());
+        let execution = Arc::new(Execution {
+            state: Mutex::new(State {
+                future: future,
+                outcome: None,
+                retrier: retrier,
+                task: None,
+                keyspace: keyspace,
+                guard: None,
+                retired: vec![],
+            }),
+            completed: Condvar::new(),
+        });
+        if let Some(deadline) = deadline {
+            TIMER.schedule(Instant::now() + deadline, &execution, Timeout::Deadline);
+        }
+        watch(&execution, future, attempt_done);
+        execution
+    }
+
+    ///An execution that failed before anything was sent. It has no driver
+    ///future.
+    pub fn failed(code: _CassError) -> Arc<Execution> {
+        Arc::new(Execution {
+            state: Mutex::new(State {
+                future: ptr::null_mut(),
+                outcome: Some(Outcome::Failed(code)),
+                retrier: None,
+                task: None,
+                keyspace: None,
+                guard: None,
+                retired: vec![],
+            }),
+            completed: Condvar::new(),
+        })
+    }
+
+    ///Counts the execution as in flight until it completes, and its
+    ///outcome once it has
+    pub fn track(&self, guard: RequestGuard) {
+        let mut state = self.lock();
+        match state.outcome {
+            None => state.guard = Some(guard),
+            Some(outcome) => {
+                let future = state.future;
+                drop(state);
+                record(future, outcome);
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<State> {
+        self.state.lock().expect("poisoned execution")
+    }
+
+    ///The driver future of the latest attempt
+    pub fn current(&self) -> *mut _Future {
+        self.lock().future
+    }
+
+    ///The future of the last attempt and how the execution ended, once it
+    ///has
+    pub fn outcome(&self) -> Option<(*mut _Future, Outcome)> {
+        let state = self.lock();
+        state.outcome.map(|outcome| (state.future, outcome))
+    }
+
+    ///Blocks until the execution has ended
+    pub fn wait(&self) -> (*mut _Future, Outcome) {
+        let mut state = self.lock();
+        loop {
+            if let Some(outcome) = state.outcome {
+                return (state.future, outcome);
+            }
+            state = self.completed.wait(state).expect("poisoned execution");
+        }
+    }
+
+    ///How the execution ended, or `None` after arranging for the task to
+    ///be notified once it has
+    pub fn poll(&self, task: Task) -> Option<(*mut _Future, Outcome)> {
+        let mut state = self.lock();
+        match state.outcome {
+            Some(outcome) => Some((state.future, outcome)),
+            None => {
+                state.task = Some(task);
+                None
+            }
+        }
+    }
+
+    fn finish(&self, mut state: MutexGuard<State>, outcome: Outcome) {
+        state.outcome = Some(outcome);
+        let succeeded = outcome == Outcome::Attempt && unsafe { cass_future_error_code(state.future) } == CASS_OK;
+        let keyspace = if succeeded { state.keyspace.take() } else { None };
+        let task = state.task.take();
+        let guard = state.guard.take();
+        let future = state.future;
+        drop(state);
+        if let Some(guard) = guard {
+            record(future, outcome);
+            drop(guard);
+        }
+        self.completed.notify_all();
+        if let Some((cache, keyspace)) = keyspace {
+            cache.set_keyspace(Some(keyspace));
+        }
+        if let Some(task) = task {
+            task.notify();
+        }
+    }
+}
+
+///The error an execution ended with, if any
+pub fn outcome_error(future: *mut _Future, outcome: Outcome) -> Option<CassError> {
+    let code = match outcome {
+        Outcome::Attempt | Outcome::Ignored => unsafe { cass_future_error_code(future) },
+        Outcome::TimedOut => CASS_ERROR_LIB_REQUEST_TIMED_OUT,
+        Outcome::Failed(code) => code,
+    };
+    if code == CASS_OK { None } else { Some(CassError::build(code)) }
+}
+
+fn record(future: *mut _Future, outcome: Outcome) {
+    record_request(outcome_error(future, outcome).as_ref(), outcome == Outcome::Ignored);
+}
+
+type Callback = unsafe extern "C" fn(*mut _Future, *mut raw::c_void);
+
+///Calls back once the future is set. The callback owns a reference to the
+///execution.
+fn watch(execution: &Arc<Execution>, future: *mut _Future, callback: Callback) {
+    let data = Arc::into_raw(execution.clone()) as *mut raw::c_void;
+    let code = unsafe { cass_future_set_callback(future, Some(callback), data) };
+    if code != CASS_OK {
+        drop(unsafe { Arc::from_raw(data as *const Execution) });
+        let state = execution.lock();
+        if state.outcome.is_none() {
+            execution.finish(state, Outcome::Failed(code));
+        }
+    }
+}
+
+unsafe extern "C" fn attempt_done(future: *mut _Future, data: *mut raw::c_void) {
+    let execution = Arc::from_raw(data as *const Execution);
+    after_attempt(&execution, future);
+}
+
+unsafe extern "C" fn prepare_done(future: *mut _Future, data: *mut raw::c_void) {
+    let execution = Arc::from_raw(data as *const Execution);
+    if cass_future_error_code(future) == CASS_OK {
+        send(&execution);
+    } else {
+        // the error of the attempt that found the statement unprepared stands
+        let state = execution.lock();
+        if state.outcome.is_none() {
+            execution.finish(state, Outcome::Attempt);
+        }
+    }
+}
+
+fn after_attempt(execution: &Arc<Execution>, future: *mut _Future) {
+    let mut state = execution.lock();
+    if future != state.future {
+        return;
+    }
+    if let Some(ref mut retrier) = state.retrier {
+        retrier.attempt_finished();
+    }
+    if state.outcome.is_some() {
+        return;
+    }
+    let code = unsafe { cass_future_error_code(future) };
+    if code == CASS_OK {
+        return execution.finish(state, Outcome::Attempt);
+    }
+    let next = match state.retrier {
+        Some(ref mut retrier) => {
+            let error_result = unsafe { cass_future_get_error_result(future) };
+            let error_result = if error_result.is_null() { None } else { Some(CassErrorResult::build(error_result)) };
+            retrier.next(&CassError::build(code), error_result.as_ref())
+        }
+        None => NextAttempt::Fail,
+    };
+    match next {
+        NextAttempt::Fail => execution.finish(state, Outcome::Attempt),
+        NextAttempt::Ignore => execution.finish(state, Outcome::Ignored),
+        NextAttempt::Retry(None) => {
+            drop(state);
+            send(execution);
+        }
+        NextAttempt::Retry(Some(delay)) => {
+            drop(state);
+            TIMER.schedule(Instant::now() + delay, execution, Timeout::Retry);
+        }
+        NextAttempt::Reprepare(query) => {
+            let prepare = state.retrier.as_ref().expect("only a retrier re-prepares").prepare(&query);
+            state.retired.push(prepare);
+            drop(state);
+            watch(execution, prepare, prepare_done);
+        }
+    }
+}
+
+///Sends the next attempt, unless the execution has ended meanwhile
+fn send(execution: &Arc<Execution>) {
+    let mut state = execution.lock();
+    if state.outcome.is_some() {
+        return;
+    }
+    let future = match state.retrier {
+        Some(ref mut retrier) => retrier.send(),
+        None => return,
+    };
+    let previous = mem::replace(&mut state.future, future);
+    state.retired.push(previous);
+    drop(state);
+    watch(execution, future, attempt_done);
+}
+
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+enum Timeout {
+    Retry,
+    Deadline,
+}
+
+struct Scheduled {
+    at: Instant,
+    execution: Weak<Execution>,
+    timeout: Timeout,
+}
+
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Scheduled) -> bool {
+        self.at == other.at
+    }
+}
+
+impl Eq for Scheduled {}
+
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Scheduled) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scheduled {
+    // the earliest first out of the heap
+    fn cmp(&self, other: &Scheduled) -> Ordering {
+        other.at.cmp(&self.at)
+    }
+}
+
+type Queue = (Mutex<BinaryHeap<Scheduled>>, Condvar);
+
+///Runs the delayed retries and deadlines of executions on one thread
+struct Timer(Arc<Queue>);
+
+lazy_static! {
+    static ref TIMER: Timer = Timer::start();
+}
+
+impl Timer {
+    fn start() -> Timer {
+        let queue: Arc<Queue> = Arc::new((Mutex::new(BinaryHeap::new()), Condvar::new()));
+        let shared = queue.clone();
+        thread::Builder::new()
+            .name("cassandra-retry-timer".to_owned())
+            .spawn(move || run(&shared))
+            .expect("cannot start the retry timer");
+        Timer(queue)
+    }
+
+    fn schedule(&self, at: Instant, execution: &Arc<Execution>, timeout: Timeout) {
+        let mut scheduled = (self.0).0.lock().expect("poisoned retry timer");
+        scheduled.push(Scheduled {
+            at: at,
+            execution: Arc::downgrade(execution),
+            timeout: timeout,
+        });
+        (self.0).1.notify_one();
+    }
+}
+
+fn run(queue: &Queue) {
+    let mut scheduled = queue.0.lock().expect("poisoned retry timer");
+    loop {
+        let now = Instant::now();
+        let next = scheduled.peek().map(|next| next.at);
+        match next {
+            None => scheduled = queue.1.wait(scheduled).expect("poisoned retry timer"),
+            Some(at) if at > now => {
+                let wait = at.duration_since(now);
+                scheduled = queue.1.wait_timeout(scheduled, wait).expect("poisoned retry timer").0;
+            }
+            Some(_) => {
+                let due = scheduled.pop().expect("peeked");
+                drop(scheduled);
+                if let Some(execution) = due.execution.upgrade() {
+                    expire(&execution, due.timeout);
+                }
+                scheduled = queue.0.lock().expect("poisoned retry timer");
+            }
+        }
+    }
+}
+
+fn expire(execution: &Arc<Execution>, timeout: Timeout) {
+    match timeout {
+        Timeout::Retry => send(execution),
+        Timeout::Deadline => {
+            let state = execution.lock();
+            if state.outcome.is_none() {
+                debug!("execution timed out");
+                execution.finish(state, Outcome::TimedOut);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_timer_order() {
+    use std::time::Duration;
+
+    let now = Instant::now();
+    let mut heap = BinaryHeap::new();
+    for &offset in &[30, 10, 20] {
+        heap.push(Scheduled {
+            at: now + Duration::from_millis(offset),
+            execution: Weak::new(),
+            timeout: Timeout::Retry,
+        });
+    }
+    let order: Vec<Instant> = (0..3).map(|_| heap.pop().unwrap().at).collect();
+    assert_eq!(order,
+               vec![now + Duration::from_millis(10), now + Duration::from_millis(20), now + Duration::from_millis(30)]);
+}
@@ -0,0 +1,328 @@
+use std::fmt::{self, Write};
+
+use cassandra::metrics::{DriverCounters, SessionMetrics, driver_counters};
+use cassandra::session::Session;
+
+#[cfg(feature = "prometheus")]
+use std::collections::HashMap;
+#[cfg(feature = "prometheus")]
+use std::sync::Arc;
+#[cfg(feature = "prometheus")]
+use prometheus::core::{Collector, Desc};
+#[cfg(feature = "prometheus")]
+use prometheus::proto;
+
+///The content type of the Prometheus text exposition format, for the
+///response serving `render`'s output
+pub const CONTENT_TYPE: &'static str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+enum Kind {
+    Counter,
+    Gauge,
+    // samples are labelled by their quantile
+    Summary,
+}
+
+///A metric with its samples, one per combination of label values
+struct Family {
+    name: &'static str,
+    help: &'static str,
+    kind: Kind,
+    labels: &'static [&'static str],
+    samples: Vec<(Vec<String>, f64)>,
+}
+
+impl Family {
+    fn new(name: &'static str, help: &'static str, kind: Kind, labels: &'static [&'static str]) -> Family {
+        Family {
+            name: name,
+            help: help,
+            kind: kind,
+            labels: labels,
+            samples: vec![],
+        }
+    }
+
+    fn single(name: &'static str, help: &'static str, kind: Kind, value: u64) -> Family {
+        Family::new(name, help, kind, &[]).sample(&[], value as f64)
+    }
+
+    fn sample(mut self, labels: &[&str], value: f64) -> Family {
+        self.samples.push((labels.iter().map(|label| label.to_string()).collect(), value));
+        self
+    }
+}
+
+fn families(session: &SessionMetrics, counters: &DriverCounters) -> Vec<Family> {
+    let latency = &session.latency;
+    let mut errors = Family::new("cassandra_request_errors_total",
+                                 "Failed executions by error code",
+                                 Kind::Counter,
+                                 &["code"]);
+    for (code, count) in &counters.errors {
+        errors = errors.sample(&[code], *count as f64);
+    }
+    vec![Family::new("cassandra_request_latency_microseconds",
+                     "Request latency percentiles",
+                     Kind::Summary,
+                     &["quantile"])
+             .sample(&["0.5"], latency.median as f64)
+             .sample(&["0.75"], latency.percentile_75th as f64)
+             .sample(&["0.95"], latency.percentile_95th as f64)
+             .sample(&["0.98"], latency.percentile_98th as f64)
+             .sample(&["0.99"], latency.percentile_99th as f64)
+             .sample(&["0.999"], latency.percentile_999th as f64),
+         Family::single("cassandra_request_latency_min_microseconds",
+                        "Fastest request",
+                        Kind::Gauge,
+                        latency.min),
+         Family::single("cassandra_request_latency_max_microseconds",
+                        "Slowest request",
+                        Kind::Gauge,
+                        latency.max),
+         Family::single("cassandra_request_latency_mean_microseconds",
+                        "Mean request latency",
+                        Kind::Gauge,
+                        latency.mean),
+         Family::single("cassandra_request_latency_stddev_microseconds",
+                        "Standard deviation of request latency",
+                        Kind::Gauge,
+                        latency.stddev),
+         Family::new("cassandra_requests_per_second",
+                     "Request throughput, since the session connected and exponentially weighted",
+                     Kind::Gauge,
+                     &["window"])
+             .sample(&["mean"], session.rates.mean)
+             .sample(&["1m"], session.rates.one_minute)
+             .sample(&["5m"], session.rates.five_minutes)
+             .sample(&["15m"], session.rates.fifteen_minutes),
+         Family::single("cassandra_connections",
+                        "Connections open",
+                        Kind::Gauge,
+                        session.connections.total_connections),
+         Family::single("cassandra_available_connections",
+                        "Connections ready to take requests",
+                        Kind::Gauge,
+                        session.connections.available_connections),
+         Family::single("cassandra_pending_requests_water_mark_exceeded_total",
+                        "Times a connection had more pending requests than its high water mark",
+                        Kind::Counter,
+                        session.connections.exceeded_pending_requests_water_mark),
+         Family::single("cassandra_write_bytes_water_mark_exceeded_total",
+                        "Times a connection had more bytes to write than its high water mark",
+                        Kind::Counter,
+                        session.connections.exceeded_write_bytes_water_mark),
+         Family::new("cassandra_timeouts_total", "Timeouts by kind", Kind::Counter, &["kind"])
+             .sample(&["connection"], session.errors.connection_timeouts as f64)
+             .sample(&["pending_request"], session.errors.pending_request_timeouts as f64)
+             .sample(&["request"], session.errors.request_timeouts as f64),
+         Family::new("cassandra_requests_total",
+                     "Executions completed, by outcome",
+                     Kind::Counter,
+                     &["outcome"])
+             .sample(&["success"], counters.requests_succeeded as f64)
+             .sample(&["error"], counters.requests_failed as f64)
+             .sample(&["ignored"], counters.requests_ignored as f64),
+         errors,
+         Family::single("cassandra_retries_total",
+                        "Statements sent again by a retry strategy",
+                        Kind::Counter,
+                        counters.retries),
+         Family::single("cassandra_prepared_cache_hits_total",
+                        "Prepared statements found in a session's cache",
+                        Kind::Counter,
+                        counters.prepared_cache_hits),
+         Family::single("cassandra_prepared_cache_misses_total",
+                        "Prepared statements missing from a session's cache",
+                        Kind::Counter,
+                        counters.prepared_cache_misses),
+         Family::single("cassandra_page_fetches_total",
+                        "Pages requested by paged executions",
+                        Kind::Counter,
+                        counters.page_fetches)]
+}
+
+fn write_value<W: Write>(out: &mut W, value: f64) -> fmt::Result {
+    if value.is_nan() {
+        write!(out, "NaN")
+    } else if value.is_infinite() {
+        write!(out, "{}Inf", if value > 0.0 { "+" } else { "-" })
+    } else {
+        write!(out, "{}", value)
+    }
+}
+
+fn write_label_value<W: Write>(out: &mut W, value: &str) -> fmt::Result {
+    for c in value.chars() {
+        try!(match c {
+            '\\' => out.write_str("\\\\"),
+            '"' => out.write_str("\\\""),
+            '\n' => out.write_str("\\n"),
+            c => out.write_char(c),
+        });
+    }
+    Ok(())
+}
+
+///Writes session metrics and the crate's counters in the Prometheus text
+///exposition format.
+pub fn write_metrics<W: Write>(out: &mut W, session: &SessionMetrics, counters: &DriverCounters) -> fmt::Result {
+    for family in families(session, counters) {
+        let kind = match family.kind {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+            Kind::Summary => "summary",
+        };
+        try!(writeln!(out, "# HELP {} {}", family.name, family.help));
+        try!(writeln!(out, "# TYPE {} {}", family.name, kind));
+        for &(ref values, value) in &family.samples {
+            try!(out.write_str(family.name));
+            if !values.is_empty() {
+                try!(out.write_char('{'));
+                for (index, (name, value)) in family.labels.iter().zip(values).enumerate() {
+                    if index > 0 {
+                        try!(out.write_char(','));
+                    }
+                    try!(write!(out, "{}=\"", name));
+                    try!(write_label_value(out, value));
+                    try!(out.write_char('"'));
+                }
+                try!(out.write_char('}'));
+            }
+            try!(out.write_char(' '));
+            try!(write_value(out, value));
+            try!(out.write_char('\n'));
+        }
+    }
+    Ok(())
+}
+
+///Renders session metrics and the crate's counters in the Prometheus text
+///exposition format, to be served with `CONTENT_TYPE` by any HTTP server.
+pub fn render(session: &SessionMetrics, counters: &DriverCounters) -> String {
+    let mut out = String::new();
+    write_metrics(&mut out, session, counters).expect("writing to a string cannot fail");
+    out
+}
+
+impl Session {
+    ///This session's metrics and the crate's counters in the Prometheus
+    ///text exposition format.
+    pub fn prometheus_metrics(&self) -> String {
+        render(&self.get_metrics(), &driver_counters())
+    }
+}
+
+///Collects a session's metrics and the crate's counters for a
+///`prometheus::Registry`.
+///
+///Only one collector should be registered per registry, since each one
+///reports the crate's counters.
+#[cfg(feature = "prometheus")]
+pub struct SessionCollector {
+    session: Arc<Session>,
+    descs: Vec<Desc>,
+}
+
+#[cfg(feature = "prometheus")]
+impl SessionCollector {
+    ///Collects the metrics of `session`
+    pub fn new(session: Arc<Session>) -> SessionCollector {
+        let descs = families(&SessionMetrics::default(), &DriverCounters::default())
+            .into_iter()
+            .map(|family| {
+                Desc::new(family.name.to_owned(),
+                          family.help.to_owned(),
+                          family.labels.iter().map(|label| label.to_string()).collect(),
+                          HashMap::new())
+                    .expect("metric names and labels are valid")
+            })
+            .collect();
+        SessionCollector {
+            session: session,
+            descs: descs,
+        }
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl Collector for SessionCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        self.descs.iter().collect()
+    }
+
+    fn collect(&self) -> Vec<proto::MetricFamily> {
+        families(&self.session.get_metrics(), &driver_counters())
+            .into_iter()
+            .filter(|family| !family.samples.is_empty())
+            .map(|family| {
+                let mut metric_family = proto::MetricFamily::default();
+                metric_family.set_name(family.name.to_owned());
+                metric_family.set_help(family.help.to_owned());
+                // the crate's text encoder cannot encode summaries, so
+                // quantiles are gauges labelled by their quantile
+                metric_family.set_field_type(match family.kind {
+                    Kind::Counter => proto::MetricType::COUNTER,
+                    Kind::Gauge | Kind::Summary => proto::MetricType::GAUGE,
+                });
+                let mut metrics = vec![];
+                for (values, value) in family.samples {
+                    let mut metric = proto::Metric::default();
+                    let labels: Vec<proto::LabelPair> = family.labels
+                        .iter()
+                        .zip(values)
+                        .map(|(name, value)| {
+                            let mut label = proto::LabelPair::default();
+                            label.set_name(name.to_string());
+                            label.set_value(value);
+                            label
+                        })
+                        .collect();
+                    metric.set_label(labels.into());
+                    match family.kind {
+                        Kind::Counter => {
+                            let mut counter = proto::Counter::default();
+                            counter.set_value(value);
+                            metric.set_counter(counter);
+                        }
+                        Kind::Gauge | Kind::Summary => {
+                            let mut gauge = proto::Gauge::default();
+                            gauge.set_value(value);
+                            metric.set_gauge(gauge);
+                        }
+                    }
+                    metrics.push(metric);
+                }
+                metric_family.set_metric(metrics.into());
+                metric_family
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_render_prometheus_text() {
+    let mut session = SessionMetrics::default();
+    session.latency.percentile_99th = 1500;
+    session.rates.one_minute = 12.5;
+    session.connections.total_connections = 2;
+    let mut counters = DriverCounters::default();
+    counters.requests_succeeded = 10;
+    counters.errors.insert("CASS_ERROR_SERVER_READ_TIMEOUT".to_owned(), 3);
+    counters.errors.insert("odd \"code\"\n".to_owned(), 1);
+
+    let text = render(&session, &counters);
+    assert!(text.contains("# HELP cassandra_request_latency_microseconds Request latency percentiles\n\
+                           # TYPE cassandra_request_latency_microseconds summary\n"));
+    assert!(text.contains("\ncassandra_request_latency_microseconds{quantile=\"0.99\"} 1500\n"));
+    assert!(text.contains("\ncassandra_requests_per_second{window=\"1m\"} 12.5\n"));
+    assert!(text.contains("\ncassandra_connections 2\n"));
+    assert!(text.contains("# TYPE cassandra_requests_total counter\n"));
+    assert!(text.contains("\ncassandra_requests_total{outcome=\"success\"} 10\n"));
+    assert!(text.contains("\ncassandra_request_errors_total{code=\"CASS_ERROR_SERVER_READ_TIMEOUT\"} 3\n"));
+    assert!(text.contains("\ncassandra_request_errors_total{code=\"odd \\\"code\\\"\\n\"} 1\n"));
+    for line in text.lines() {
+        assert!(line.starts_with("# ") || line.starts_with("cassandra_"), "{}", line);
+    }
+}
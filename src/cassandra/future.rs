@@ -2,13 +2,22 @@ use std::mem;
 use std::str;
 use std::slice;
 use std::os::raw;
+use std::sync::Arc;
+
+use futures::{self, Async, Poll};
+use futures::task;
 
 use cassandra::error::CassError;
-use cassandra::result::CassResult;
-use cassandra::prepared::PreparedStatement;
+use cassandra::codec::CodecRegistry;
+use cassandra::execution::{Execution, Outcome, outcome_error};
+use cassandra::result::{CassResult, empty_result, query_result};
+use cassandra::shutdown::RequestGuard;
+use cassandra::prepared::{PreparedStatement, set_source};
+use cassandra::uuid::Uuid;
 
 use cassandra_sys::cass_true;
 
+use cassandra_sys::CassError as _CassError;
 use cassandra_sys::CassFuture as _Future;
 // use cassandra_sys::CassResult as _CassResult;
 use cassandra_sys::cass_future_free;
@@ -22,6 +31,7 @@ use cassandra_sys::cass_future_get_prepared;
 use cassandra_sys::cass_future_custom_payload_item;
 use cassandra_sys::cass_future_custom_payload_item_count;
 use cassandra_sys::cass_future_get_error_result;
+use cassandra_sys::cass_future_tracing_id;
 use cassandra_sys::cass_future_set_callback;
 use cassandra_sys::CassFutureCallback as _CassFutureCallback;
 use cassandra::error::CassErrorResult;
@@ -64,6 +74,11 @@ impl Future {
         }
     }
 
+    ///Gets the set status of the future.
+    pub fn ready(&self) -> bool {
+        unsafe { cass_future_ready(self.0) == cass_true }
+    }
+
     ///Wait for the future to be set or timeout.
     pub fn wait_timed(&mut self, timeout_us: u64) -> bool {
         unsafe { cass_future_wait_timed(self.0, timeout_us) == cass_true }
@@ -134,60 +149,124 @@ impl Future {
 #[must_use]
 ///The future result of an operation.
 ///It can represent a result if the operation completed successfully or an
-///error if the operation failed. It can be waited on, polled or a callback
-///can be attached.
-pub struct ResultFuture(*mut _Future);
-
-impl Drop for ResultFuture {
-    fn drop(&mut self) {
-        unsafe { cass_future_free(self.0) }
-    }
+///error if the operation failed. It can be waited on or polled.
+///
+///If the session has a `RetryStrategy`, the statement is executed again for
+///as long as the strategy asks it to, as soon as an attempt fails; the
+///future completes with the last attempt. A statement bound from a prepared
+///statement the node no longer knows is prepared again and retried once.
+///The driver future of each attempt already has a callback set, so none can
+///be set on the future returned by `inner`.
+///
+///The request counts as in flight for `Session::shutdown` until its last
+///attempt completes, whether or not the future is waited on.
+pub struct ResultFuture {
+    execution: Arc<Execution>,
+    fingerprint: Option<u64>,
+    codecs: Option<Arc<CodecRegistry>>,
 }
 
 impl ResultFuture {
     ///Blocks until the future returns or times out
     pub fn wait(&mut self) -> Result<CassResult, CassError> {
-        unsafe {
-            cass_future_wait(self.0);
-            self.error_code()
-        }
+        let (future, outcome) = self.execution.wait();
+        self.result_of(future, outcome)
+    }
+
+    ///Whether the future has returned
+    pub fn ready(&self) -> bool {
+        self.execution.outcome().is_some()
+    }
+
+    ///Whether the session's `RetryStrategy` chose to ignore the error of
+    ///the last attempt, in which case the result is empty
+    pub fn ignored(&self) -> bool {
+        self.execution.outcome().map_or(false, |(_, outcome)| outcome == Outcome::Ignored)
     }
 
     ///Gets the error code from future. If the future is not ready this method will
     ///wait for the future to be set.
     pub fn error_code(&mut self) -> Result<CassResult, CassError> {
-        unsafe {
-            let x = self.get();
-            let error_code = CassError::build(cass_future_error_code(self.0));
-            match x {
-                Some(x) => error_code.wrap(x),
-                None => Err(error_code)
-            }
+        let (future, outcome) = self.execution.wait();
+        self.result_of(future, outcome)
+    }
+
+    fn result_of(&self, future: *mut _Future, outcome: Outcome) -> Result<CassResult, CassError> {
+        match outcome {
+            Outcome::Attempt => unsafe {
+                let result = cass_future_get_result(future);
+                if result.is_null() {
+                    Err(CassError::build(cass_future_error_code(future)))
+                } else {
+                    Ok(query_result(result, self.fingerprint, self.codecs.clone()))
+                }
+            },
+            Outcome::Ignored => Ok(empty_result(self.fingerprint, self.codecs.clone())),
+            outcome => Err(outcome_error(future, outcome).expect("the execution failed")),
         }
     }
 
     ///Gets the error message from future. If the future is not ready this method will
     ///wait for the future to be set.
     pub fn error_message(&mut self) -> String {
-        unsafe {
-            let message = mem::zeroed();
-            let message_length = mem::zeroed();
-            cass_future_error_message(self.0, message, message_length);
-
-            let slice = slice::from_raw_parts(message as *const u8, message_length as usize);
-            str::from_utf8(slice).expect("must be utf8").to_owned()
+        match self.execution.wait() {
+            (future, Outcome::Attempt) |
+            (future, Outcome::Ignored) => unsafe {
+                let message = mem::zeroed();
+                let message_length = mem::zeroed();
+                cass_future_error_message(future, message, message_length);
+
+                let slice = slice::from_raw_parts(message as *const u8, message_length as usize);
+                str::from_utf8(slice).expect("must be utf8").to_owned()
+            },
+            (future, outcome) => outcome_error(future, outcome).map(|err| err.to_string()).unwrap_or_default(),
         }
     }
 
+    ///Gets the tracing id of a request executed with tracing enabled. If the
+    ///future is not ready this method will wait for the future to be set.
+    pub fn tracing_id(&self) -> Result<Uuid, CassError> {
+        match self.execution.wait() {
+            (future, Outcome::Attempt) |
+            (future, Outcome::Ignored) => unsafe {
+                let mut id = mem::zeroed();
+                CassError::build(cass_future_tracing_id(future, &mut id)).wrap(Uuid::build(id))
+            },
+            (future, outcome) => Err(outcome_error(future, outcome).expect("the execution failed")),
+        }
+    }
 
+    ///Gets the error result from a future that failed as a result of a server error. If the
+    ///future is not ready this method will wait for the future to be set.
+    ///a None response indicates that the request did not fail with a server error
+    pub fn get_error_result(&self) -> Option<CassErrorResult> {
+        match self.execution.wait() {
+            (future, Outcome::Attempt) |
+            (future, Outcome::Ignored) => unsafe {
+                let result = cass_future_get_error_result(future);
+                if result.is_null() { None } else { Some(CassErrorResult::build(result)) }
+            },
+            _ => None,
+        }
+    }
 
     ///Gets the result of a successful future. If the future is not ready this method will
     ///wait for the future to be set.
     ///a None response indicates that there was an error
     pub fn get(&mut self) -> Option<CassResult> {
-        unsafe {
-            let result = cass_future_get_result(self.0);
-            if result.is_null() { None } else { Some((CassResult::build(result))) }
+        let (future, outcome) = self.execution.wait();
+        self.result_of(future, outcome).ok()
+    }
+}
+
+impl futures::Future for ResultFuture {
+    type Item = CassResult;
+    type Error = CassError;
+
+    fn poll(&mut self) -> Poll<CassResult, CassError> {
+        match self.execution.poll(task::current()) {
+            Some((future, outcome)) => self.result_of(future, outcome).map(Async::Ready),
+            None => Ok(Async::NotReady),
         }
     }
 }
@@ -197,7 +276,7 @@ impl ResultFuture {
 ///It can represent a result if the operation completed successfully or an
 ///error if the operation failed. It can be waited on, polled or a callback
 ///can be attached.
-pub struct PreparedFuture(*mut _Future);
+pub struct PreparedFuture(*mut _Future, Option<(String, Option<String>, Arc<Vec<String>>, Arc<CodecRegistry>)>);
 
 impl Drop for PreparedFuture {
     fn drop(&mut self) {
@@ -226,10 +305,26 @@ impl PreparedFuture {
     ///Gets the error message from future. If the future is not ready this method will
     ///wait for the future to be set.
     pub fn get(&mut self) -> PreparedStatement {
-        unsafe { PreparedStatement::build(cass_future_get_prepared(self.0)) }
+        let mut prepared = unsafe { PreparedStatement::build(cass_future_get_prepared(self.0)) };
+        if let Some((ref query, ref keyspace, ref partition_key, ref codecs)) = self.1 {
+            set_source(&mut prepared, query, keyspace.clone(), partition_key, codecs.clone());
+        }
+        prepared
     }
 }
 
+///Wraps the future of a prepare, remembering the query being prepared, the
+///keyspace of the session, the partition key columns of its table and the
+///codecs of the session.
+pub fn prepared_future(inner: *mut _Future,
+                       query: &str,
+                       keyspace: Option<String>,
+                       partition_key: Arc<Vec<String>>,
+                       codecs: Arc<CodecRegistry>)
+                       -> PreparedFuture {
+    PreparedFuture(inner, Some((query.to_owned(), keyspace, partition_key, codecs)))
+}
+
 pub struct ConnectFuture(*mut _Future);
 
 impl Protected<*mut _Future> for ConnectFuture {
@@ -310,16 +405,47 @@ impl Protected<*mut _Future> for PreparedFuture {
         self.0
     }
     fn build(inner: *mut _Future) -> Self {
-        PreparedFuture(inner)
+        PreparedFuture(inner, None)
+    }
+}
+
+///Follows a statement's execution, remembering the statement's fingerprint
+///for the paging state of its result and the codecs its rows are decoded
+///with.
+pub fn result_future(execution: Arc<Execution>,
+                     fingerprint: Option<u64>,
+                     codecs: Arc<CodecRegistry>)
+                     -> ResultFuture {
+    ResultFuture {
+        execution: execution,
+        fingerprint: fingerprint,
+        codecs: Some(codecs),
     }
 }
 
+///Counts a request as in flight until it completes, and its outcome in the
+///crate's counters once it has.
+pub fn tracked_future(future: ResultFuture, guard: RequestGuard) -> ResultFuture {
+    future.execution.track(guard);
+    future
+}
+
+///The future of a request that could not be sent
+pub fn failed_future(code: _CassError, codecs: Arc<CodecRegistry>) -> ResultFuture {
+    result_future(Execution::failed(code), None, codecs)
+}
+
 impl Protected<*mut _Future> for ResultFuture {
+    ///The driver future of the latest attempt
     fn inner(&self) -> *mut _Future {
-        self.0
+        self.execution.current()
     }
     fn build(inner: *mut _Future) -> Self {
-        ResultFuture(inner)
+        ResultFuture {
+            execution: Execution::start(inner, None, None),
+            fingerprint: None,
+            codecs: None,
+        }
     }
 }
 
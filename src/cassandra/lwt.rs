@@ -0,0 +1,250 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::fmt;
+
+use cassandra::consistency::Consistency;
+use cassandra::cql_value::CqlValue;
+use cassandra::error::CassError;
+use cassandra::result::CassResult;
+use cassandra::row::Row;
+use cassandra::session::Session;
+use cassandra::statement::Statement;
+use cassandra::util::Protected;
+use cassandra::write_type::WriteType;
+
+use cassandra_sys::CASS_ERROR_LIB_BAD_PARAMS;
+use cassandra_sys::CASS_ERROR_LIB_INDEX_OUT_OF_BOUNDS;
+use cassandra_sys::CASS_ERROR_SERVER_WRITE_TIMEOUT;
+use cassandra_sys::CASS_WRITE_TYPE_CAS;
+
+///The column of a lightweight transaction's result telling whether it was
+///applied
+const APPLIED: &'static str = "[applied]";
+
+///The outcome of a lightweight transaction
+#[derive(Clone,Debug,PartialEq)]
+pub enum LwtOutcome {
+    ///The condition held and the write was applied
+    Applied,
+    ///The condition did not hold. `current` holds the existing values of
+    ///the columns in the condition, or of the whole row for
+    ///`IF NOT EXISTS`, by column name. They are copied out of the result's
+    ///row, without its `[applied]` column, so that the outcome can outlive
+    ///the result; `LwtResult::current_row` gives the row itself.
+    NotApplied {
+        ///The existing values
+        current: Vec<(String, CqlValue)>,
+    },
+}
+
+impl LwtOutcome {
+    ///The outcome of a result whose `[applied]` column is `applied`, given
+    ///the columns of its row.
+    fn of(applied: bool, columns: Vec<(String, CqlValue)>) -> LwtOutcome {
+        if applied {
+            return LwtOutcome::Applied;
+        }
+        LwtOutcome::NotApplied { current: columns.into_iter().filter(|column| column.0 != APPLIED).collect() }
+    }
+
+    ///The existing value of a column, if the write was not applied and the
+    ///server returned it.
+    pub fn current(&self, column: &str) -> Option<&CqlValue> {
+        match *self {
+            LwtOutcome::Applied => None,
+            LwtOutcome::NotApplied { ref current } => {
+                current.iter().find(|entry| entry.0 == column).map(|entry| &entry.1)
+            }
+        }
+    }
+}
+
+///The result of a conditional INSERT, UPDATE or DELETE (`IF ...`, `IF EXISTS`
+///or `IF NOT EXISTS`).
+pub struct LwtResult {
+    result: CassResult,
+    outcome: LwtOutcome,
+}
+
+impl LwtResult {
+    ///Reads the `[applied]` column of a lightweight transaction's result,
+    ///copying the existing values if the write was not applied.
+    pub fn from_result(result: CassResult) -> Result<LwtResult, CassError> {
+        let outcome = {
+            let row = match result.first_row() {
+                Some(row) => row,
+                None => return Err(CassError::build(CASS_ERROR_LIB_INDEX_OUT_OF_BOUNDS)),
+            };
+            let applied = try!(try!(row.get_column_by_name(APPLIED)).get_bool());
+            let mut columns = vec![];
+            if !applied {
+                for index in 0..result.column_count() {
+                    columns.push((result.column_name(index), try!(row.get_value(index))));
+                }
+            }
+            LwtOutcome::of(applied, columns)
+        };
+        Ok(LwtResult {
+            result: result,
+            outcome: outcome,
+        })
+    }
+
+    ///Whether the write was applied
+    pub fn applied(&self) -> bool {
+        self.outcome == LwtOutcome::Applied
+    }
+
+    ///Whether the write was applied and, if not, the existing values.
+    pub fn outcome(&self) -> &LwtOutcome {
+        &self.outcome
+    }
+
+    ///The row holding the existing values, if the write was not applied.
+    ///Besides the existing values it has the `[applied]` column.
+    pub fn current_row(&self) -> Option<Row> {
+        if self.applied() {
+            None
+        } else {
+            self.result.first_row()
+        }
+    }
+
+    ///The underlying result
+    pub fn into_result(self) -> CassResult {
+        self.result
+    }
+}
+
+///An error executing a lightweight transaction
+pub enum LwtError {
+    ///The Paxos round timed out at the serial consistency level. The write
+    ///may or may not have been applied, so the current values must be read
+    ///at serial consistency before retrying.
+    CasWriteTimeout {
+        ///The serial consistency level of the Paxos round, if the server
+        ///reported a known one
+        consistency: Option<Consistency>,
+        ///Acknowledgements received
+        received: i32,
+        ///Acknowledgements required
+        required: i32,
+    },
+    ///Any other error
+    Cass(CassError),
+}
+
+impl From<CassError> for LwtError {
+    fn from(err: CassError) -> LwtError {
+        LwtError::Cass(err)
+    }
+}
+
+impl Display for LwtError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            LwtError::CasWriteTimeout { consistency, received, required } => {
+                write!(f,
+                       "Paxos write timed out at {} ({} of {} acknowledgements)",
+                       consistency.map_or("an unknown consistency", |consistency| consistency.name()),
+                       received,
+                       required)
+            }
+            LwtError::Cass(ref err) => Display::fmt(err, f),
+        }
+    }
+}
+
+impl Debug for LwtError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Error for LwtError {
+    fn description(&self) -> &str {
+        match *self {
+            LwtError::CasWriteTimeout { .. } => "Paxos write timed out",
+            LwtError::Cass(ref err) => err.description(),
+        }
+    }
+}
+
+///The error for a write timeout during the Paxos round, or `None` for any
+///other error
+fn cas_timeout(err: &CassError,
+               write_type: WriteType,
+               consistency: Option<Consistency>,
+               received: i32,
+               required: i32)
+               -> Option<LwtError> {
+    match (err.inner(), write_type.0) {
+        (CASS_ERROR_SERVER_WRITE_TIMEOUT, CASS_WRITE_TYPE_CAS) => {
+            Some(LwtError::CasWriteTimeout {
+                consistency: consistency,
+                received: received,
+                required: required,
+            })
+        }
+        _ => None,
+    }
+}
+
+impl Session {
+    ///Executes a lightweight transaction and waits for its outcome.
+    ///
+    ///The Paxos round runs at `serial_consistency`, which is set on the
+    ///statement and must be `SERIAL` or `LOCAL_SERIAL`; any other level fails
+    ///with `CASS_ERROR_LIB_BAD_PARAMS`. A timeout during that round is
+    ///reported as `LwtError::CasWriteTimeout`.
+    pub fn execute_lwt(&self,
+                       statement: &mut Statement,
+                       serial_consistency: Consistency)
+                       -> Result<LwtResult, LwtError> {
+        if !serial_consistency.is_serial() {
+            return Err(LwtError::Cass(CassError::build(CASS_ERROR_LIB_BAD_PARAMS)));
+        }
+        try!(statement.set_serial_consistency(serial_consistency));
+        let mut future = self.execute(statement);
+        match future.wait() {
+            Ok(result) => Ok(try!(LwtResult::from_result(result))),
+            Err(err) => {
+                let timeout = future.get_error_result().and_then(|error_result| {
+                    cas_timeout(&err,
+                                error_result.write_type(),
+                                error_result.result_consistency(),
+                                error_result.responses_received(),
+                                error_result.responses_required())
+                });
+                Err(timeout.unwrap_or(LwtError::Cass(err)))
+            }
+        }
+    }
+}
+
+#[test]
+fn test_lwt_outcome() {
+    let columns = vec![(APPLIED.to_owned(), CqlValue::Boolean(false)),
+                       ("id".to_owned(), CqlValue::Int(1)),
+                       ("owner".to_owned(), CqlValue::Text("alice".to_owned()))];
+    let outcome = LwtOutcome::of(false, columns.clone());
+    assert_eq!(outcome,
+               LwtOutcome::NotApplied { current: columns[1..].to_vec() });
+    assert_eq!(outcome.current("owner"), Some(&CqlValue::Text("alice".to_owned())));
+    assert_eq!(outcome.current(APPLIED), None);
+    assert_eq!(LwtOutcome::of(true, vec![]), LwtOutcome::Applied);
+    assert_eq!(LwtOutcome::Applied.current("owner"), None);
+}
+
+#[test]
+fn test_cas_timeout() {
+    use cassandra_sys::CASS_WRITE_TYPE_SIMPLE;
+
+    let write_timeout = CassError::build(CASS_ERROR_SERVER_WRITE_TIMEOUT);
+    let timeout = cas_timeout(&write_timeout, WriteType(CASS_WRITE_TYPE_CAS), Some(Consistency::Serial), 1, 2);
+    assert_eq!(timeout.map(|timeout| timeout.to_string()),
+               Some("Paxos write timed out at SERIAL (1 of 2 acknowledgements)".to_owned()));
+    assert!(cas_timeout(&write_timeout, WriteType(CASS_WRITE_TYPE_SIMPLE), None, 1, 2).is_none());
+    let bad_params = CassError::build(CASS_ERROR_LIB_BAD_PARAMS);
+    assert!(cas_timeout(&bad_params, WriteType(CASS_WRITE_TYPE_CAS), None, 1, 2).is_none());
+}
@@ -1,15 +1,317 @@
-use cassandra_sys::CassMetrics as _CassMetrics;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use cassandra::error::CassError;
 use cassandra::util::Protected;
 
-///A view into server metrics FIXME not meaingfully implemented
-pub struct SessionMetrics(*const _CassMetrics);
+use cassandra_sys::CassMetrics as _CassMetrics;
+
+///Request latencies in microseconds
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq,Serialize,Deserialize)]
+pub struct RequestLatency {
+    ///Fastest request
+    pub min: u64,
+    ///Slowest request
+    pub max: u64,
+    ///Mean
+    pub mean: u64,
+    ///Standard deviation
+    pub stddev: u64,
+    ///50th percentile
+    pub median: u64,
+    ///75th percentile
+    pub percentile_75th: u64,
+    ///95th percentile
+    pub percentile_95th: u64,
+    ///98th percentile
+    pub percentile_98th: u64,
+    ///99th percentile
+    pub percentile_99th: u64,
+    ///99.9th percentile
+    pub percentile_999th: u64,
+}
+
+///Requests per second
+#[derive(Clone,Copy,Debug,Default,PartialEq,Serialize,Deserialize)]
+pub struct RequestRates {
+    ///Since the session connected
+    pub mean: f64,
+    ///Exponentially weighted over one minute
+    pub one_minute: f64,
+    ///Exponentially weighted over five minutes
+    pub five_minutes: f64,
+    ///Exponentially weighted over fifteen minutes
+    pub fifteen_minutes: f64,
+}
+
+///Connections to the cluster's nodes
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq,Serialize,Deserialize)]
+pub struct ConnectionStats {
+    ///Connections open
+    pub total_connections: u64,
+    ///Connections ready to take requests
+    pub available_connections: u64,
+    ///Times a connection had more pending requests than its high water mark
+    pub exceeded_pending_requests_water_mark: u64,
+    ///Times a connection had more bytes to write than its high water mark
+    pub exceeded_write_bytes_water_mark: u64,
+}
+
+///Timeouts counted since the session connected
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq,Serialize,Deserialize)]
+pub struct ErrorStats {
+    ///Connections that could not be established in time
+    pub connection_timeouts: u64,
+    ///Requests that waited too long for a connection
+    pub pending_request_timeouts: u64,
+    ///Requests that waited too long for a response
+    pub request_timeouts: u64,
+}
+
+///A copy of a session's performance and diagnostic metrics, taken by
+///`Session::get_metrics`
+#[derive(Clone,Copy,Debug,Default,PartialEq,Serialize,Deserialize)]
+pub struct SessionMetrics {
+    ///Request latencies
+    pub latency: RequestLatency,
+    ///Request throughput
+    pub rates: RequestRates,
+    ///Connection totals
+    pub connections: ConnectionStats,
+    ///Timeouts
+    pub errors: ErrorStats,
+}
+
+impl SessionMetrics {
+    ///The metrics since `previous` was taken from the same session: the
+    ///counters are the increase since then, while latencies, rates and
+    ///connections open are the current values.
+    pub fn since(&self, previous: &SessionMetrics) -> SessionMetrics {
+        let connections = &self.connections;
+        let errors = &self.errors;
+        SessionMetrics {
+            latency: self.latency,
+            rates: self.rates,
+            connections: ConnectionStats {
+                total_connections: connections.total_connections,
+                available_connections: connections.available_connections,
+                exceeded_pending_requests_water_mark: connections.exceeded_pending_requests_water_mark
+                    .saturating_sub(previous.connections.exceeded_pending_requests_water_mark),
+                exceeded_write_bytes_water_mark: connections.exceeded_write_bytes_water_mark
+                    .saturating_sub(previous.connections.exceeded_write_bytes_water_mark),
+            },
+            errors: ErrorStats {
+                connection_timeouts: errors.connection_timeouts.saturating_sub(previous.errors.connection_timeouts),
+                pending_request_timeouts: errors.pending_request_timeouts
+                    .saturating_sub(previous.errors.pending_request_timeouts),
+                request_timeouts: errors.request_timeouts.saturating_sub(previous.errors.request_timeouts),
+            },
+        }
+    }
+}
+
+impl Display for SessionMetrics {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let latency = &self.latency;
+        try!(writeln!(f,
+                      "latency (us): min {} mean {} (stddev {}) median {} p75 {} p95 {} p98 {} p99 {} p99.9 {} max {}",
+                      latency.min,
+                      latency.mean,
+                      latency.stddev,
+                      latency.median,
+                      latency.percentile_75th,
+                      latency.percentile_95th,
+                      latency.percentile_98th,
+                      latency.percentile_99th,
+                      latency.percentile_999th,
+                      latency.max));
+        try!(writeln!(f,
+                      "requests/s: mean {:.2} 1m {:.2} 5m {:.2} 15m {:.2}",
+                      self.rates.mean,
+                      self.rates.one_minute,
+                      self.rates.five_minutes,
+                      self.rates.fifteen_minutes));
+        try!(writeln!(f,
+                      "connections: {} total, {} available, pending requests water mark exceeded {} times, write \
+                       bytes water mark exceeded {} times",
+                      self.connections.total_connections,
+                      self.connections.available_connections,
+                      self.connections.exceeded_pending_requests_water_mark,
+                      self.connections.exceeded_write_bytes_water_mark));
+        write!(f,
+               "timeouts: {} connection, {} pending request, {} request",
+               self.errors.connection_timeouts,
+               self.errors.pending_request_timeouts,
+               self.errors.request_timeouts)
+    }
+}
+
+///Copies the metrics filled in by the driver.
+pub fn session_metrics(metrics: &_CassMetrics) -> SessionMetrics {
+    let requests = &metrics.requests;
+    SessionMetrics {
+        latency: RequestLatency {
+            min: requests.min,
+            max: requests.max,
+            mean: requests.mean,
+            stddev: requests.stddev,
+            median: requests.median,
+            percentile_75th: requests.percentile_75th,
+            percentile_95th: requests.percentile_95th,
+            percentile_98th: requests.percentile_98th,
+            percentile_99th: requests.percentile_99th,
+            percentile_999th: requests.percentile_999th,
+        },
+        rates: RequestRates {
+            mean: requests.mean_rate,
+            one_minute: requests.one_minute_rate,
+            five_minutes: requests.five_minute_rate,
+            fifteen_minutes: requests.fifteen_minute_rate,
+        },
+        connections: ConnectionStats {
+            total_connections: metrics.stats.total_connections,
+            available_connections: metrics.stats.available_connections,
+            exceeded_pending_requests_water_mark: metrics.stats.exceeded_pending_requests_water_mark,
+            exceeded_write_bytes_water_mark: metrics.stats.exceeded_write_bytes_water_mark,
+        },
+        errors: ErrorStats {
+            connection_timeouts: metrics.errors.connection_timeouts,
+            pending_request_timeouts: metrics.errors.pending_request_timeouts,
+            request_timeouts: metrics.errors.request_timeouts,
+        },
+    }
+}
+
+///Counters kept by the crate across all of its sessions since the process
+///started, read with `driver_counters`
+#[derive(Clone,Debug,Default,PartialEq,Eq,Serialize,Deserialize)]
+pub struct DriverCounters {
+    ///Executions that succeeded, whether or not their futures were waited on
+    pub requests_succeeded: u64,
+    ///Executions that failed, or were refused by a session shutting down
+    pub requests_failed: u64,
+    ///Executions that failed with an error the `RetryStrategy` ignored,
+    ///not counted as failed
+    pub requests_ignored: u64,
+    ///Failed and ignored executions by error code
+    pub errors: BTreeMap<String, u64>,
+    ///Statements sent again by a `RetryStrategy`
+    pub retries: u64,
+    ///Prepared statements found in a session's cache
+    pub prepared_cache_hits: u64,
+    ///Prepared statements missing from a session's cache
+    pub prepared_cache_misses: u64,
+    ///Pages requested by paged executions
+    pub page_fetches: u64,
+}
+
+static REQUESTS_SUCCEEDED: AtomicUsize = AtomicUsize::new(0);
+static REQUESTS_FAILED: AtomicUsize = AtomicUsize::new(0);
+static REQUESTS_IGNORED: AtomicUsize = AtomicUsize::new(0);
+static RETRIES: AtomicUsize = AtomicUsize::new(0);
+static PREPARED_CACHE_HITS: AtomicUsize = AtomicUsize::new(0);
+static PREPARED_CACHE_MISSES: AtomicUsize = AtomicUsize::new(0);
+static PAGE_FETCHES: AtomicUsize = AtomicUsize::new(0);
 
+lazy_static! {
+    static ref ERRORS: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
+}
 
-impl Protected<*const _CassMetrics> for SessionMetrics {
-    fn inner(&self) -> *const _CassMetrics {
-        self.0
+///The crate's counters as of now
+pub fn driver_counters() -> DriverCounters {
+    let count = |counter: &AtomicUsize| counter.load(Ordering::Relaxed) as u64;
+    DriverCounters {
+        requests_succeeded: count(&REQUESTS_SUCCEEDED),
+        requests_failed: count(&REQUESTS_FAILED),
+        requests_ignored: count(&REQUESTS_IGNORED),
+        errors: ERRORS.lock().expect("poisoned error counters").clone(),
+        retries: count(&RETRIES),
+        prepared_cache_hits: count(&PREPARED_CACHE_HITS),
+        prepared_cache_misses: count(&PREPARED_CACHE_MISSES),
+        page_fetches: count(&PAGE_FETCHES),
     }
-    fn build(inner: *const _CassMetrics) -> Self {
-        SessionMetrics(inner)
+}
+
+///Counts the outcome of an execution once it has completed.
+pub fn record_request(error: Option<&CassError>, ignored: bool) {
+    match error {
+        None => {
+            REQUESTS_SUCCEEDED.fetch_add(1, Ordering::Relaxed);
+        }
+        Some(err) => {
+            if ignored {
+                REQUESTS_IGNORED.fetch_add(1, Ordering::Relaxed);
+            } else {
+                REQUESTS_FAILED.fetch_add(1, Ordering::Relaxed);
+            }
+            let code = format!("{:?}", err.inner());
+            *ERRORS.lock().expect("poisoned error counters").entry(code).or_insert(0) += 1;
+        }
     }
 }
+
+///Counts a statement sent again.
+pub fn record_retry() {
+    RETRIES.fetch_add(1, Ordering::Relaxed);
+}
+
+///Counts a prepared statement cache lookup.
+pub fn record_prepared_cache(hit: bool) {
+    if hit {
+        PREPARED_CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+    } else {
+        PREPARED_CACHE_MISSES.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+///Counts a page requested by a paged execution.
+pub fn record_page_fetch() {
+    PAGE_FETCHES.fetch_add(1, Ordering::Relaxed);
+}
+
+#[test]
+fn test_session_metrics_since() {
+    let mut previous = SessionMetrics::default();
+    previous.connections.total_connections = 4;
+    previous.connections.exceeded_write_bytes_water_mark = 2;
+    previous.errors.request_timeouts = 5;
+
+    let mut current = previous;
+    current.latency.percentile_99th = 1200;
+    current.rates.one_minute = 42.5;
+    current.connections.total_connections = 3;
+    current.connections.exceeded_write_bytes_water_mark = 3;
+    current.errors.request_timeouts = 12;
+
+    let delta = current.since(&previous);
+    assert_eq!(delta.latency.percentile_99th, 1200);
+    assert_eq!(delta.rates.one_minute, 42.5);
+    assert_eq!(delta.connections.total_connections, 3);
+    assert_eq!(delta.connections.exceeded_write_bytes_water_mark, 1);
+    assert_eq!(delta.errors.request_timeouts, 7);
+    // counters reset by a new session do not underflow
+    assert_eq!(previous.since(&current).errors.request_timeouts, 0);
+    assert!(current.to_string().contains("p99 1200"));
+}
+
+#[test]
+fn test_record_request() {
+    use cassandra_sys::CASS_ERROR_SERVER_OVERLOADED;
+
+    let before = driver_counters();
+    record_request(None, false);
+    record_request(Some(&CassError::build(CASS_ERROR_SERVER_OVERLOADED)), false);
+    record_request(Some(&CassError::build(CASS_ERROR_SERVER_OVERLOADED)), true);
+    let after = driver_counters();
+    // other tests may count requests meanwhile
+    assert!(after.requests_succeeded >= before.requests_succeeded + 1);
+    assert!(after.requests_failed >= before.requests_failed + 1);
+    assert!(after.requests_ignored >= before.requests_ignored + 1);
+    let overloaded = |counters: &DriverCounters| {
+        counters.errors.get(&format!("{:?}", CASS_ERROR_SERVER_OVERLOADED)).cloned().unwrap_or(0)
+    };
+    assert!(overloaded(&after) >= overloaded(&before) + 2);
+}
@@ -0,0 +1,364 @@
+use std::collections::VecDeque;
+use std::fmt::{Debug, Formatter};
+use std::fmt;
+use std::sync::Arc;
+
+use futures::{self, Async, Poll, Stream};
+
+use cassandra::cql_value::CqlValue;
+use cassandra::error::CassError;
+use cassandra::future::ResultFuture;
+use cassandra::metrics::record_page_fetch;
+use cassandra::result::CassResult;
+use cassandra::session::Session;
+use cassandra::statement::Statement;
+
+
+///A row read by `PagedRows` or `PagedStream`.
+///
+///The row owns copies of its values, so it stays valid after the next row
+///or page has been read.
+#[derive(Clone,PartialEq)]
+pub struct PagedRow {
+    columns: Arc<Vec<String>>,
+    values: Vec<CqlValue>,
+}
+
+impl PagedRow {
+    ///The value of the column at the specified index
+    pub fn get(&self, index: usize) -> Option<&CqlValue> {
+        self.values.get(index)
+    }
+
+    ///The value of the column with the specified name
+    pub fn get_by_name(&self, name: &str) -> Option<&CqlValue> {
+        self.columns.iter().position(|column| column == name).and_then(|index| self.values.get(index))
+    }
+
+    ///The names of the columns, in order
+    pub fn column_names(&self) -> &[String] {
+        &self.columns
+    }
+
+    ///The values of the columns, in order
+    pub fn values(&self) -> &[CqlValue] {
+        &self.values
+    }
+
+    ///Takes the values of the columns, in order
+    pub fn into_values(self) -> Vec<CqlValue> {
+        self.values
+    }
+}
+
+impl Debug for PagedRow {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for value in &self.values {
+            try!(write!(f, "{:?}\t", value));
+        }
+        Ok(())
+    }
+}
+
+///Copies the rows of a page, decoding custom types with the codecs of the
+///statement's session.
+pub fn page_rows(page: &CassResult) -> Result<VecDeque<PagedRow>, CassError> {
+    let columns = Arc::new((0..page.column_count()).map(|index| page.column_name(index)).collect::<Vec<_>>());
+    let mut rows = VecDeque::with_capacity(page.row_count() as usize);
+    for row in page.iter() {
+        let values: Result<Vec<_>, CassError> = (0..columns.len() as u64).map(|index| row.get_value(index)).collect();
+        rows.push_back(PagedRow {
+            columns: columns.clone(),
+            values: try!(values),
+        });
+    }
+    Ok(rows)
+}
+
+///Walks the pages of a statement's result, one request at a time
+struct Pager<'a> {
+    session: &'a Session,
+    statement: Statement,
+    prefetch: bool,
+    rows: VecDeque<PagedRow>,
+    pending: Option<ResultFuture>,
+    more: bool,
+}
+
+impl<'a> Pager<'a> {
+    fn new(session: &'a Session, statement: Statement, prefetch: bool) -> Self {
+        Pager {
+            session: session,
+            statement: statement,
+            prefetch: prefetch,
+            rows: VecDeque::new(),
+            pending: None,
+            more: true,
+        }
+    }
+
+    ///The next row of the current page
+    fn buffered(&mut self) -> Option<PagedRow> {
+        self.rows.pop_front()
+    }
+
+    ///Requests the next page unless it was already requested or there is none
+    fn request(&mut self) {
+        if self.more && self.pending.is_none() {
+            self.more = false;
+            self.pending = Some(self.session.execute(&self.statement));
+            record_page_fetch();
+        }
+    }
+
+    ///Waits for the requested page and makes it the current one
+    fn receive(&mut self) -> Result<(), CassError> {
+        let mut future = match self.pending.take() {
+            Some(future) => future,
+            None => return Ok(()),
+        };
+        let result = try!(future.wait());
+        self.accept(result)
+    }
+
+    ///Whether the requested page has arrived, making it the current one if
+    ///so. If not, the current task is notified once it does.
+    fn poll_pending(&mut self) -> Poll<(), CassError> {
+        let result = match self.pending {
+            Some(ref mut future) => {
+                match try!(futures::Future::poll(future)) {
+                    Async::Ready(result) => result,
+                    Async::NotReady => return Ok(Async::NotReady),
+                }
+            }
+            None => return Ok(Async::Ready(())),
+        };
+        self.pending = None;
+        try!(self.accept(result));
+        Ok(Async::Ready(()))
+    }
+
+    ///Makes an arrived page the current one
+    fn accept(&mut self, result: CassResult) -> Result<(), CassError> {
+        let rows = try!(page_rows(&result));
+        if result.has_more_pages() {
+            try!(self.statement.set_paging_state(result));
+            self.more = true;
+        }
+        self.rows = rows;
+        if self.prefetch {
+            self.request();
+        }
+        Ok(())
+    }
+}
+
+///An iterator over every row of a statement's result, fetching the next
+///page when the current one has been read.
+///
+///Created by `Session::execute_paged`.
+pub struct PagedRows<'a> {
+    pager: Pager<'a>,
+}
+
+impl<'a> Iterator for PagedRows<'a> {
+    type Item = Result<PagedRow, CassError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.pager.buffered() {
+                return Some(Ok(row));
+            }
+            self.pager.request();
+            if self.pager.pending.is_none() {
+                return None;
+            }
+            if let Err(err) = self.pager.receive() {
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+///A stream over every row of a statement's result.
+///
+///The next page is requested as soon as the current one arrives, so it is
+///usually ready by the time the current page has been consumed.
+///
+///Created by `Session::execute_paged_stream`.
+pub struct PagedStream<'a> {
+    pager: Pager<'a>,
+}
+
+impl<'a> Stream for PagedStream<'a> {
+    type Item = PagedRow;
+    type Error = CassError;
+
+    fn poll(&mut self) -> Poll<Option<PagedRow>, CassError> {
+        loop {
+            if let Some(row) = self.pager.buffered() {
+                return Ok(Async::Ready(Some(row)));
+            }
+            self.pager.request();
+            if self.pager.pending.is_none() {
+                return Ok(Async::Ready(None));
+            }
+            if let Async::NotReady = try!(self.pager.poll_pending()) {
+                return Ok(Async::NotReady);
+            }
+        }
+    }
+}
+
+impl Session {
+    ///Executes a statement and iterates over the rows of every page of its
+    ///result. Pages are requested one at a time, when the rows of the
+    ///previous page have all been read; the page size is the statement's
+    ///(see `Statement::set_paging_size`).
+    pub fn execute_paged(&self, statement: Statement) -> PagedRows {
+        PagedRows { pager: Pager::new(self, statement, false) }
+    }
+
+    ///Executes a statement and streams the rows of every page of its result,
+    ///requesting each page while the previous one is being consumed.
+    pub fn execute_paged_stream(&self, statement: Statement) -> PagedStream {
+        let mut pager = Pager::new(self, statement, true);
+        pager.request();
+        PagedStream { pager: pager }
+    }
+}
+
+///Answers as a single node whose table `t` has four rows, `(id, name)`
+///from `(0, "row 0")`, in pages of two. Other queries get the node's
+///`system.local` row or no rows.
+#[cfg(test)]
+fn stand_in_node() -> u16 {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn int(output: &mut Vec<u8>, value: usize) {
+        output.extend_from_slice(&[(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]);
+    }
+    fn string(output: &mut Vec<u8>, value: &str) {
+        output.extend_from_slice(&[(value.len() >> 8) as u8, value.len() as u8]);
+        output.extend_from_slice(value.as_bytes());
+    }
+    fn bytes(output: &mut Vec<u8>, value: &[u8]) {
+        int(output, value.len());
+        output.extend_from_slice(value);
+    }
+    // a RESULT of kind Rows, with the columns' names and type ids
+    fn rows(paging_state: Option<&[u8]>, table: &str, columns: &[(&str, u8)], rows: &[Vec<Vec<u8>>]) -> Vec<u8> {
+        let mut output = vec![];
+        int(&mut output, 2);
+        int(&mut output, if paging_state.is_some() { 3 } else { 1 });
+        int(&mut output, columns.len());
+        if let Some(paging_state) = paging_state {
+            bytes(&mut output, paging_state);
+        }
+        string(&mut output, if table == "t" { "ks" } else { "system" });
+        string(&mut output, table);
+        for &(name, type_id) in columns {
+            string(&mut output, name);
+            output.extend_from_slice(&[0, type_id]);
+        }
+        int(&mut output, rows.len());
+        for row in rows {
+            for value in row {
+                bytes(&mut output, value);
+            }
+        }
+        output
+    }
+    fn query_result(body: &[u8]) -> Vec<u8> {
+        let length = body[..4].iter().fold(0, |length, byte| length << 8 | *byte as usize);
+        let query = String::from_utf8_lossy(&body[4..4 + length]).into_owned();
+        if query.contains("system.local") {
+            let varchar = 0x0d;
+            return rows(None,
+                        "local",
+                        &[("data_center", varchar), ("rack", varchar), ("release_version", varchar)],
+                        &[vec![b"dc1".to_vec(), b"rack1".to_vec(), b"3.11.0".to_vec()]]);
+        }
+        if !query.ends_with(" FROM t") {
+            return rows(None, "other", &[], &[]);
+        }
+        // after the query come its consistency and flags, 0x08 for a paging state
+        let second_page = body[4 + length + 2] & 0x08 != 0;
+        let first = if second_page { 2 } else { 0 };
+        let page: Vec<_> = (first..first + 2)
+                               .map(|id: u8| vec![vec![0, 0, 0, id], format!("row {}", id).into_bytes()])
+                               .collect();
+        let paging_state = if second_page { None } else { Some(&b"next"[..]) };
+        rows(paging_state, "t", &[("id", 0x09), ("name", 0x0d)], &page)
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for connection in listener.incoming() {
+            let mut connection = match connection {
+                Ok(connection) => connection,
+                Err(_) => return,
+            };
+            thread::spawn(move || {
+                let mut header = [0u8; 9];
+                while connection.read_exact(&mut header).is_ok() {
+                    let length = header[5..].iter().fold(0, |length, byte| length << 8 | *byte as usize);
+                    let mut body = vec![0u8; length];
+                    if connection.read_exact(&mut body).is_err() {
+                        return;
+                    }
+                    let (opcode, body) = match header[4] {
+                        // OPTIONS gets SUPPORTED with no options
+                        0x05 => (0x06, vec![0, 0]),
+                        0x07 => (0x08, query_result(&body)),
+                        // STARTUP and REGISTER get READY
+                        _ => (0x02, vec![]),
+                    };
+                    let mut frame = vec![0x80 | header[0], 0, header[2], header[3], opcode];
+                    int(&mut frame, body.len());
+                    frame.extend(body);
+                    if connection.write_all(&frame).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    port
+}
+
+#[test]
+fn test_paged_rows_outlive_their_page() {
+    use std::str::FromStr;
+    use cassandra::cluster::Cluster;
+    use cassandra::contact_points::ContactPoints;
+
+    let mut cluster = Cluster::new();
+    cluster.set_contact_points(ContactPoints::from_str("127.0.0.1").unwrap()).unwrap();
+    cluster.set_port(stand_in_node()).unwrap();
+    cluster.set_use_schema(false).set_token_aware_routing(false);
+    let session = cluster.connect().unwrap();
+    let mut statement = Statement::new("SELECT id, name FROM t", 0);
+    statement.set_paging_size(2).unwrap();
+
+    // rows copied out of a page stay readable once the page is freed
+    let page = session.execute(&statement).wait().unwrap();
+    let rows = page_rows(&page).unwrap();
+    drop(page);
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[1].get(0), Some(&CqlValue::Int(1)));
+    assert_eq!(rows[1].get_by_name("name"), Some(&CqlValue::Text("row 1".to_owned())));
+
+    // and a row kept while the next page replaces its own
+    let mut paged = session.execute_paged(statement);
+    let kept = paged.next().unwrap().unwrap();
+    let rest: Vec<PagedRow> = paged.map(|row| row.unwrap()).collect();
+    assert_eq!(rest.iter().map(|row| row.get(0).cloned()).collect::<Vec<_>>(),
+               vec![Some(CqlValue::Int(1)), Some(CqlValue::Int(2)), Some(CqlValue::Int(3))]);
+    assert_eq!(kept.get(0), Some(&CqlValue::Int(0)));
+    assert_eq!(kept.get_by_name("name"), Some(&CqlValue::Text("row 0".to_owned())));
+    assert_eq!(kept.column_names(), &["id".to_owned(), "name".to_owned()][..]);
+}
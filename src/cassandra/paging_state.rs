@@ -0,0 +1,244 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::fmt;
+use std::mem;
+use std::slice;
+use std::str::FromStr;
+
+use cassandra::error::CassError;
+use cassandra::result::{CassResult, result_fingerprint};
+use cassandra::statement::{Statement, fingerprint, set_paging_token};
+use cassandra::util::Protected;
+
+use cassandra_sys::cass_result_paging_state_token;
+
+const VERSION: u8 = 1;
+const ALPHABET: &'static [u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+///A cursor to the next page of a query's result, safe to hand to clients.
+///
+///The cursor records which statement produced it, and a statement only
+///resumes from cursors produced by the same query. Its string form is URL
+///safe base64 without padding.
+///
+///<b>Warning:</b> The cursor is not signed. A client can still craft one
+///for the same query starting at an arbitrary partition.
+#[derive(Clone,PartialEq,Eq)]
+pub struct PagingState {
+    fingerprint: u64,
+    token: Vec<u8>,
+}
+
+///An error resuming from a paging state
+pub enum PagingStateError {
+    ///The cursor is not valid base64 or was not produced by this crate
+    Malformed,
+    ///The cursor was produced by a different query
+    WrongStatement,
+    ///The driver rejected the paging state
+    Cass(CassError),
+}
+
+impl From<CassError> for PagingStateError {
+    fn from(err: CassError) -> PagingStateError {
+        PagingStateError::Cass(err)
+    }
+}
+
+impl Display for PagingStateError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            PagingStateError::Malformed => write!(f, "malformed paging state"),
+            PagingStateError::WrongStatement => write!(f, "paging state belongs to a different query"),
+            PagingStateError::Cass(ref err) => Display::fmt(err, f),
+        }
+    }
+}
+
+impl Debug for PagingStateError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Error for PagingStateError {
+    fn description(&self) -> &str {
+        match *self {
+            PagingStateError::Malformed => "malformed paging state",
+            PagingStateError::WrongStatement => "paging state belongs to a different query",
+            PagingStateError::Cass(ref err) => err.description(),
+        }
+    }
+}
+
+impl PagingState {
+    ///The driver's opaque paging state
+    pub fn token(&self) -> &[u8] {
+        &self.token
+    }
+
+    ///Whether the cursor was produced by the given statement's query, with
+    ///the same keyspace and bound values (see `fingerprint`). Always false
+    ///for statements bound from a prepared statement whose query is not
+    ///known.
+    pub fn matches(&self, statement: &Statement) -> bool {
+        Some(self.fingerprint) == fingerprint(statement)
+    }
+
+    ///Encodes the cursor as URL safe base64.
+    pub fn encode(&self) -> String {
+        let mut bytes = Vec::with_capacity(9 + self.token.len());
+        bytes.push(VERSION);
+        for shift in (0..8).rev() {
+            bytes.push((self.fingerprint >> (shift * 8)) as u8);
+        }
+        bytes.extend_from_slice(&self.token);
+        encode_base64(&bytes)
+    }
+
+    ///Decodes a cursor produced by `encode`.
+    pub fn decode(encoded: &str) -> Result<PagingState, PagingStateError> {
+        let bytes = try!(decode_base64(encoded).ok_or(PagingStateError::Malformed));
+        if bytes.len() < 9 || bytes[0] != VERSION {
+            return Err(PagingStateError::Malformed);
+        }
+        Ok(PagingState {
+            fingerprint: bytes[1..9].iter().fold(0, |fingerprint, byte| fingerprint << 8 | *byte as u64),
+            token: bytes[9..].to_vec(),
+        })
+    }
+}
+
+impl Display for PagingState {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.encode())
+    }
+}
+
+impl Debug for PagingState {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "PagingState({})", self.encode())
+    }
+}
+
+impl FromStr for PagingState {
+    type Err = PagingStateError;
+
+    fn from_str(encoded: &str) -> Result<PagingState, PagingStateError> {
+        PagingState::decode(encoded)
+    }
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 4 + 2) / 3);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| group | (*byte as u32) << (16 - i * 8));
+        for i in 0..chunk.len() + 1 {
+            encoded.push(ALPHABET[(group >> (18 - i * 6)) as usize & 0x3f] as char);
+        }
+    }
+    encoded
+}
+
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.as_bytes();
+    let encoded = &encoded[..encoded.iter().rposition(|c| *c != b'=').map_or(0, |last| last + 1)];
+    if encoded.len() % 4 == 1 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+    for chunk in encoded.chunks(4) {
+        let mut group = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let value = match ALPHABET.iter().position(|a| a == c) {
+                Some(value) => value as u32,
+                None => return None,
+            };
+            group |= value << (18 - i * 6);
+        }
+        for i in 0..chunk.len() - 1 {
+            bytes.push((group >> (16 - i * 8)) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+impl CassResult {
+    ///The cursor to the next page of this result, if there are more pages
+    ///and the result was produced by `Session::execute`.
+    pub fn paging_state(&self) -> Option<PagingState> {
+        if !self.has_more_pages() {
+            return None;
+        }
+        let fingerprint = match result_fingerprint(self) {
+            Some(fingerprint) => fingerprint,
+            None => return None,
+        };
+        unsafe {
+            let mut token = mem::zeroed();
+            let mut token_length = mem::zeroed();
+            match CassError::build(cass_result_paging_state_token(self.inner(), &mut token, &mut token_length))
+                      .wrap(()) {
+                Ok(()) => {
+                    Some(PagingState {
+                        fingerprint: fingerprint,
+                        token: slice::from_raw_parts(token as *const u8, token_length as usize).to_vec(),
+                    })
+                }
+                Err(_) => None,
+            }
+        }
+    }
+}
+
+impl Statement {
+    ///Continues the statement's query from a cursor produced by an earlier
+    ///execution of the same query. Bind the statement's values first, the
+    ///cursor only matches a statement bound with the same values.
+    pub fn resume_from(&mut self, state: &PagingState) -> Result<&mut Self, PagingStateError> {
+        if !state.matches(self) {
+            return Err(PagingStateError::WrongStatement);
+        }
+        try!(set_paging_token(self, &state.token));
+        Ok(self)
+    }
+}
+
+#[test]
+fn test_paging_state_round_trip() {
+    assert_eq!(encode_base64(b"foob"), "Zm9vYg");
+    assert_eq!(encode_base64(&[0xfb, 0xff]), "-_8");
+    assert_eq!(decode_base64("Zm9vYmFy"), Some(b"foobar".to_vec()));
+    assert_eq!(decode_base64("Zm9vYg=="), Some(b"foob".to_vec()));
+    assert_eq!(decode_base64("Zm9v+g"), None);
+
+    let select = Statement::new("SELECT * FROM t", 0);
+    let state = PagingState {
+        fingerprint: fingerprint(&select).unwrap(),
+        token: vec![0, 1, 2, 254, 255],
+    };
+    let encoded = state.to_string();
+    let decoded: PagingState = encoded.parse().unwrap();
+    assert!(decoded == state);
+    assert!(decoded.matches(&select));
+    assert!(!decoded.matches(&Statement::new("SELECT * FROM u", 0)));
+    assert!(PagingState::decode("AAAA").is_err());
+}
+
+#[test]
+fn test_paging_state_fingerprint() {
+    let query = "SELECT * FROM t WHERE token(k) > ? AND token(k) <= ?";
+    let mut first = Statement::new(query, 2);
+    first.bind_int64(0, -100).unwrap().bind_int64(1, 0).unwrap();
+    let mut second = Statement::new(query, 2);
+    second.bind_int64(0, 0).unwrap().bind_int64(1, 100).unwrap();
+    let mut same = Statement::new(query, 2);
+    same.bind_int64(0, -100).unwrap().bind_int64(1, 0).unwrap();
+    assert!(fingerprint(&first) != fingerprint(&second));
+    assert_eq!(fingerprint(&first), fingerprint(&same));
+
+    let mut other_keyspace = Statement::new(query, 2);
+    other_keyspace.set_keyspace("other".to_owned()).unwrap();
+    other_keyspace.bind_int64(0, -100).unwrap().bind_int64(1, 0).unwrap();
+    assert!(fingerprint(&first) != fingerprint(&other_keyspace));
+}
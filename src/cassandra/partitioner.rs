@@ -0,0 +1,218 @@
+use std::fmt::Debug;
+
+use num::BigInt;
+use num::bigint::Sign;
+
+///Computes the token of a serialized partition key the way a Cassandra
+///partitioner does.
+///
+///Keys of tables with a composite partition key are serialized with
+///`composite_key`.
+pub trait Partitioner {
+    ///The partitioner's token type
+    type Token: Ord + Clone + Debug;
+
+    ///The token of a serialized partition key
+    fn token(&self, partition_key: &[u8]) -> Self::Token;
+}
+
+///Cassandra's default `Murmur3Partitioner`.
+///
+///Tokens are the first 64 bits of the x64 128-bit MurmurHash3 of the key.
+///Cassandra's implementation sign-extends the trailing bytes of the key,
+///so keys whose length is not a multiple of 16 and whose tail has bytes
+///of 0x80 or above hash differently from the reference MurmurHash3; this
+///implementation reproduces that.
+#[derive(Clone,Copy,Debug,Default)]
+pub struct Murmur3Partitioner;
+
+///Cassandra's `RandomPartitioner`: the absolute value of the key's MD5 hash
+///read as a signed 128-bit integer.
+#[derive(Clone,Copy,Debug,Default)]
+pub struct RandomPartitioner;
+
+///Serializes the components of a composite partition key: each component
+///as a 2-byte length, its bytes and an end-of-component byte of 0. A key
+///with a single component is its serialized value, unchanged.
+pub fn composite_key(components: &[&[u8]]) -> Vec<u8> {
+    if components.len() == 1 {
+        return components[0].to_vec();
+    }
+    let mut key = Vec::with_capacity(components.iter().map(|component| component.len() + 3).sum());
+    for component in components {
+        key.push((component.len() >> 8) as u8);
+        key.push(component.len() as u8);
+        key.extend_from_slice(component);
+        key.push(0);
+    }
+    key
+}
+
+impl Partitioner for Murmur3Partitioner {
+    type Token = i64;
+
+    fn token(&self, partition_key: &[u8]) -> i64 {
+        if partition_key.is_empty() {
+            return i64::min_value();
+        }
+        match murmur3_x64_128(partition_key)[0] as i64 {
+            // the minimum token is reserved
+            token if token == i64::min_value() => i64::max_value(),
+            token => token,
+        }
+    }
+}
+
+impl Partitioner for RandomPartitioner {
+    type Token = BigInt;
+
+    fn token(&self, partition_key: &[u8]) -> BigInt {
+        if partition_key.is_empty() {
+            return BigInt::from(-1);
+        }
+        let mut hash = md5(partition_key);
+        if hash[0] & 0x80 != 0 {
+            // negate the two's complement value
+            let mut carry = true;
+            for byte in hash.iter_mut().rev() {
+                let (negated, overflow) = (!*byte).overflowing_add(carry as u8);
+                *byte = negated;
+                carry = overflow;
+            }
+        }
+        BigInt::from_bytes_be(Sign::Plus, &hash)
+    }
+}
+
+const C1: u64 = 0x87c37b91114253d5;
+const C2: u64 = 0x4cf5ad432745937f;
+
+fn fmix(mut k: u64) -> u64 {
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xff51afd7ed558ccd);
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xc4ceb9fe1a85ec53);
+    k ^= k >> 33;
+    k
+}
+
+fn mix_k1(k1: u64) -> u64 {
+    k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2)
+}
+
+fn mix_k2(k2: u64) -> u64 {
+    k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1)
+}
+
+fn murmur3_x64_128(key: &[u8]) -> [u64; 2] {
+    let (mut h1, mut h2) = (0u64, 0u64);
+    let blocks = key.len() / 16;
+    let block = |offset: usize| (0..8).fold(0u64, |k, i| k | (key[offset + i] as u64) << (i * 8));
+    for index in 0..blocks {
+        h1 ^= mix_k1(block(index * 16));
+        h1 = h1.rotate_left(27).wrapping_add(h2).wrapping_mul(5).wrapping_add(0x52dce729);
+        h2 ^= mix_k2(block(index * 16 + 8));
+        h2 = h2.rotate_left(31).wrapping_add(h1).wrapping_mul(5).wrapping_add(0x38495ab5);
+    }
+
+    // Cassandra reads the tail as signed bytes, so they are sign-extended
+    let tail = &key[blocks * 16..];
+    let byte = |i: usize| tail[i] as i8 as i64 as u64;
+    let (mut k1, mut k2) = (0u64, 0u64);
+    for i in (8..tail.len()).rev() {
+        k2 ^= byte(i) << ((i - 8) * 8);
+    }
+    if tail.len() > 8 {
+        h2 ^= mix_k2(k2);
+    }
+    for i in (0..tail.len().min(8)).rev() {
+        k1 ^= byte(i) << (i * 8);
+    }
+    if !tail.is_empty() {
+        h1 ^= mix_k1(k1);
+    }
+
+    h1 ^= key.len() as u64;
+    h2 ^= key.len() as u64;
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    h1 = fmix(h1);
+    h2 = fmix(h2);
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    [h1, h2]
+}
+
+const MD5_SHIFTS: [u32; 64] = [7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20,
+                               5, 9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23,
+                               6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21];
+
+fn md5(message: &[u8]) -> [u8; 16] {
+    let constants: Vec<u32> = (0..64).map(|i: i32| ((i as f64 + 1.0).sin().abs() * 4294967296.0) as u32).collect();
+    let mut padded = message.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    let bits = (message.len() as u64).wrapping_mul(8);
+    for i in 0..8 {
+        padded.push((bits >> (i * 8)) as u8);
+    }
+
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    for chunk in padded.chunks(64) {
+        let word = |g: usize| (0..4).fold(0u32, |w, i| w | (chunk[g * 4 + i] as u32) << (i * 8));
+        let (mut a, mut b, mut c, mut d) = (state[0], state[1], state[2], state[3]);
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a.wrapping_add(f).wrapping_add(constants[i]).wrapping_add(word(g)).rotate_left(MD5_SHIFTS[i]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(rotated);
+        }
+        state[0] = state[0].wrapping_add(a);
+        state[1] = state[1].wrapping_add(b);
+        state[2] = state[2].wrapping_add(c);
+        state[3] = state[3].wrapping_add(d);
+    }
+
+    let mut digest = [0u8; 16];
+    for (i, word) in state.iter().enumerate() {
+        for j in 0..4 {
+            digest[i * 4 + j] = (word >> (j * 8)) as u8;
+        }
+    }
+    digest
+}
+
+#[test]
+fn test_partitioner_tokens() {
+    use std::str::FromStr;
+
+    let murmur3 = Murmur3Partitioner;
+    assert_eq!(murmur3.token(b"123"), -7468325962851647638);
+    assert_eq!(murmur3.token(b"9223372036854775807"), 7162290910810015547);
+    assert_eq!(murmur3.token(&[0x10; 8]), 1446172840243228796);
+    // tails with bytes of 0x80 and above hit the sign extension
+    assert_eq!(murmur3.token(&[0xfe; 8]), -8927430733708461935);
+    let repeated: Vec<u8> = (0..50).map(|i| [0x00, 0xff, 0x10, 0xfa, 0x99][i % 5]).collect();
+    assert_eq!(murmur3.token(&repeated), 5837342703291459765);
+    assert_eq!(murmur3.token(b""), i64::min_value());
+
+    let random = RandomPartitioner;
+    assert_eq!(random.token(b"123"),
+               BigInt::from_str("42767516990368493138776584305024125808").unwrap());
+    assert_eq!(random.token(&[0xff; 3]),
+               BigInt::from_str("162706688412082095103064631352303179814").unwrap());
+    assert_eq!(random.token(b"hello world"),
+               BigInt::from_str("125893641179230474042701625388361764291").unwrap());
+
+    assert_eq!(composite_key(&[b"ab", &[0, 1]]), vec![0, 2, b'a', b'b', 0, 0, 2, 0, 1, 0]);
+    assert_eq!(composite_key(&[b"ab"]), b"ab".to_vec());
+}
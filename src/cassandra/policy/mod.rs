@@ -1 +1,2 @@
 pub mod retry;
+pub mod retry_strategy;
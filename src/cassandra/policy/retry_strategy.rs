@@ -0,0 +1,543 @@
+use std::ffi::CString;
+use std::fmt::{Debug, Formatter};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+
+use time::{self, Duration};
+
+use cassandra::consistency::{Consistency, raw_consistency};
+use cassandra::error::{CassError, CassErrorResult};
+use cassandra::metrics::record_retry;
+use cassandra::session::SessionHandle;
+use cassandra::statement::StatementHandle;
+use cassandra::util::Protected;
+use cassandra::write_type::WriteType;
+
+use cassandra_sys::CassFuture as _Future;
+use cassandra_sys::CASS_ERROR_LIB_NO_HOSTS_AVAILABLE;
+use cassandra_sys::CASS_ERROR_LIB_REQUEST_TIMED_OUT;
+use cassandra_sys::CASS_ERROR_LIB_UNABLE_TO_CONNECT;
+use cassandra_sys::CASS_ERROR_SERVER_IS_BOOTSTRAPPING;
+use cassandra_sys::CASS_ERROR_SERVER_OVERLOADED;
+use cassandra_sys::CASS_ERROR_SERVER_READ_TIMEOUT;
+use cassandra_sys::CASS_ERROR_SERVER_UNAVAILABLE;
+use cassandra_sys::CASS_ERROR_SERVER_UNPREPARED;
+use cassandra_sys::CASS_ERROR_SERVER_WRITE_TIMEOUT;
+use cassandra_sys::CASS_CONSISTENCY_UNKNOWN;
+use cassandra_sys::cass_session_execute;
+use cassandra_sys::cass_session_prepare;
+use cassandra_sys::cass_statement_set_consistency;
+
+///Why a request failed
+pub enum RetryCause {
+    ///The replicas did not answer a read in time
+    ReadTimeout {
+        ///Replicas that answered
+        received: i32,
+        ///Replicas the consistency level needs
+        required: i32,
+        ///Whether the replica asked for the data answered
+        data_present: bool,
+    },
+    ///The replicas did not acknowledge a write in time; it may or may not
+    ///have been applied
+    WriteTimeout {
+        ///Replicas that acknowledged
+        received: i32,
+        ///Replicas the consistency level needs
+        required: i32,
+        ///What kind of write timed out
+        write_type: WriteType,
+    },
+    ///The coordinator knew too few replicas were alive and did not try
+    Unavailable {
+        ///Replicas the consistency level needs
+        required: i32,
+        ///Replicas that were alive
+        alive: i32,
+    },
+    ///The coordinator refused the request because it is overloaded or
+    ///still bootstrapping
+    Overloaded,
+    ///The request timed out or could not be sent on the client side; it may
+    ///or may not have been applied
+    RequestFailed,
+    ///Any other error, such as an invalid query
+    Other,
+}
+
+impl RetryCause {
+    fn of(error: &CassError, error_result: Option<&CassErrorResult>) -> RetryCause {
+        match (error.inner(), error_result) {
+            (CASS_ERROR_SERVER_READ_TIMEOUT, Some(result)) => {
+                RetryCause::ReadTimeout {
+                    received: result.responses_received(),
+                    required: result.responses_required(),
+                    data_present: result.data_present(),
+                }
+            }
+            (CASS_ERROR_SERVER_WRITE_TIMEOUT, Some(result)) => {
+                RetryCause::WriteTimeout {
+                    received: result.responses_received(),
+                    required: result.responses_required(),
+                    write_type: result.write_type(),
+                }
+            }
+            (CASS_ERROR_SERVER_UNAVAILABLE, Some(result)) => {
+                RetryCause::Unavailable {
+                    required: result.responses_required(),
+                    alive: result.responses_received(),
+                }
+            }
+            (CASS_ERROR_SERVER_OVERLOADED, _) |
+            (CASS_ERROR_SERVER_IS_BOOTSTRAPPING, _) => RetryCause::Overloaded,
+            (CASS_ERROR_LIB_REQUEST_TIMED_OUT, _) |
+            (CASS_ERROR_LIB_NO_HOSTS_AVAILABLE, _) |
+            (CASS_ERROR_LIB_UNABLE_TO_CONNECT, _) => RetryCause::RequestFailed,
+            _ => RetryCause::Other,
+        }
+    }
+}
+
+///A failed attempt, as seen by a `RetryStrategy`
+pub struct RetryContext<'a> {
+    ///The error
+    pub error: &'a CassError,
+    ///The server's details of the error, if it came from the server
+    pub error_result: Option<&'a CassErrorResult>,
+    ///Why the request failed
+    pub cause: RetryCause,
+    ///The consistency level of the failed attempt, if the server reported it
+    pub consistency: Option<Consistency>,
+    ///How many attempts have failed, including this one
+    pub attempt: u32,
+    ///Whether the statement may safely be executed again (see
+    ///`Statement::set_idempotent`)
+    pub idempotent: bool,
+    ///Time since the first attempt was sent
+    pub elapsed: Duration,
+}
+
+///What to do about a failed attempt
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum RetryDecision {
+    ///Sends the request again, after the delay if there is one
+    Retry(Option<Duration>),
+    ///Sends the request again at another consistency level, after the delay
+    ///if there is one. The level applies to that attempt only: the
+    ///statement gets its own level back once the attempt has completed.
+    RetryAt(Consistency, Option<Duration>),
+    ///Reports the error
+    Rethrow,
+    ///Gives up without treating the failure as an error: the request
+    ///completes with an empty result; see `ResultFuture::ignored`
+    Ignore,
+}
+
+///Decides whether failed requests are sent again.
+///
+///Set on a cluster with `Cluster::set_retry_strategy`. It is consulted as
+///soon as an attempt fails, on one of the driver's threads, whether or not
+///the request's future is being waited on, so it must not block; delays
+///are waited out without holding up any thread. It runs on top of the
+///driver's `RetryPolicy`, so it sees only the errors that policy rethrows.
+pub trait RetryStrategy: Send + Sync {
+    ///Decides what to do about a failed attempt.
+    fn on_error(&self, context: &RetryContext) -> RetryDecision;
+
+    ///The time after the first attempt at which a request fails with
+    ///`CASS_ERROR_LIB_REQUEST_TIMED_OUT`, whether it is waiting to be
+    ///retried or an attempt is still in flight.
+    ///
+    ///<b>Default:</b> no deadline
+    fn deadline(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Debug for RetryStrategy {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "RetryStrategy")
+    }
+}
+
+///Retries with exponentially growing, randomized delays.
+///
+///Read timeouts, unavailable replicas and overloaded coordinators are
+///retried for any statement; write timeouts and client-side failures only
+///for idempotent statements, since the failed attempt may have been
+///applied. Other errors are rethrown.
+///
+///The delay before retry `n` is chosen at random between zero and
+///`base_delay * 2^(n-1)`, capped at `max_delay` ("full jitter"), so that
+///clients failing together do not retry together.
+#[derive(Clone,Debug)]
+pub struct ExponentialBackoff {
+    base_delay: Duration,
+    max_delay: Duration,
+    max_retries: u32,
+    deadline: Option<Duration>,
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> ExponentialBackoff {
+        ExponentialBackoff {
+            base_delay: Duration::milliseconds(100),
+            max_delay: Duration::seconds(10),
+            max_retries: 3,
+            deadline: None,
+        }
+    }
+}
+
+impl ExponentialBackoff {
+    ///A backoff strategy with the default settings
+    pub fn new() -> ExponentialBackoff {
+        ExponentialBackoff::default()
+    }
+
+    ///Sets the longest delay before the first retry.
+    ///
+    ///<b>Default:</b> 100ms
+    pub fn set_base_delay(&mut self, base_delay: Duration) -> &mut Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    ///Sets the longest delay before any retry.
+    ///
+    ///<b>Default:</b> 10s
+    pub fn set_max_delay(&mut self, max_delay: Duration) -> &mut Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    ///Sets how many times a request is retried.
+    ///
+    ///<b>Default:</b> 3
+    pub fn set_max_retries(&mut self, max_retries: u32) -> &mut Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    ///Sets the time since the first attempt after which no retry is sent.
+    ///The request also fails with `CASS_ERROR_LIB_REQUEST_TIMED_OUT` once
+    ///the deadline has passed, even while an attempt is in flight.
+    ///
+    ///<b>Default:</b> no deadline
+    pub fn set_deadline(&mut self, deadline: Duration) -> &mut Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    ///The delay before a retry, given how many attempts failed and a random
+    ///number
+    fn delay(&self, attempt: u32, random: u64) -> Duration {
+        let max_delay = self.max_delay.num_milliseconds().max(0) as u64;
+        let shift = if attempt > 0 { (attempt - 1).min(32) } else { 0 };
+        let ceiling = (self.base_delay.num_milliseconds().max(0) as u64).saturating_mul(1 << shift).min(max_delay);
+        Duration::milliseconds((random % (ceiling + 1)) as i64)
+    }
+}
+
+impl RetryStrategy for ExponentialBackoff {
+    fn on_error(&self, context: &RetryContext) -> RetryDecision {
+        if context.attempt > self.max_retries {
+            return RetryDecision::Rethrow;
+        }
+        let retry = match context.cause {
+            RetryCause::ReadTimeout { .. } |
+            RetryCause::Unavailable { .. } |
+            RetryCause::Overloaded => true,
+            RetryCause::WriteTimeout { .. } |
+            RetryCause::RequestFailed => context.idempotent,
+            RetryCause::Other => false,
+        };
+        if !retry {
+            return RetryDecision::Rethrow;
+        }
+        let delay = self.delay(context.attempt, random());
+        match self.deadline {
+            Some(deadline) if context.elapsed + delay >= deadline => RetryDecision::Rethrow,
+            _ => RetryDecision::Retry(Some(delay)),
+        }
+    }
+
+    fn deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+}
+
+///A pseudo-random number, good enough to spread retries out
+fn random() -> u64 {
+    // splitmix64 of the clock
+    let mut z = time::precise_time_ns().wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+///What a request does after a failed attempt
+#[derive(Debug,PartialEq)]
+pub enum NextAttempt {
+    ///Sends the statement again, after the delay if there is one
+    Retry(Option<::std::time::Duration>),
+    ///Prepares the query again, then sends the statement again
+    Reprepare(CString),
+    ///Completes the request with an empty result
+    Ignore,
+    ///Completes the request with the attempt's error
+    Fail,
+}
+
+///Decides how a statement's request goes on after a failed attempt, with
+///a `RetryStrategy` or because the node no longer knows the prepared
+///statement it was bound from, and sends the further attempts.
+///
+///Nothing here blocks: the request calls it from the driver's callbacks.
+///
+///Every attempt sends the retrier's own copy of the statement, so that the
+///caller may change or drop the statement once it has been executed.
+pub struct Retrier {
+    session: Arc<SessionHandle>,
+    statement: StatementHandle,
+    strategy: Option<Arc<RetryStrategy>>,
+    prepared_query: Option<String>,
+    consistency: Option<Consistency>,
+    retry_consistency: Option<Consistency>,
+    restore_consistency: bool,
+    idempotent: bool,
+    started: Instant,
+    attempt: u32,
+}
+
+impl Retrier {
+    ///Retries the statement's requests with the strategy, if there is one.
+    ///A statement bound from a prepared `prepared_query` is prepared again
+    ///and retried once if it fails with `CASS_ERROR_SERVER_UNPREPARED`.
+    ///`statement` is a copy of the statement taken when it was executed.
+    ///`consistency` is the level the statement was executed at, which the
+    ///copy is set to, and restored after an attempt sent at another level.
+    pub fn new(session: Arc<SessionHandle>,
+               statement: StatementHandle,
+               strategy: Option<Arc<RetryStrategy>>,
+               prepared_query: Option<String>,
+               consistency: Option<Consistency>,
+               idempotent: bool)
+               -> Retrier {
+        if let Some(consistency) = consistency {
+            unsafe {
+                cass_statement_set_consistency(statement.inner(), raw_consistency(consistency));
+            }
+        }
+        Retrier {
+            session: session,
+            statement: statement,
+            strategy: strategy,
+            prepared_query: prepared_query,
+            consistency: consistency,
+            retry_consistency: None,
+            restore_consistency: false,
+            idempotent: idempotent,
+            started: Instant::now(),
+            attempt: 0,
+        }
+    }
+
+    ///The strategy's deadline, measured from the first attempt
+    pub fn deadline(&self) -> Option<::std::time::Duration> {
+        self.strategy.as_ref().and_then(|strategy| strategy.deadline()).and_then(|deadline| deadline.to_std().ok())
+    }
+
+    ///Consults the strategy about a failed attempt.
+    pub fn next(&mut self, error: &CassError, error_result: Option<&CassErrorResult>) -> NextAttempt {
+        if error.inner() == CASS_ERROR_SERVER_UNPREPARED {
+            return match self.prepared_query.take().and_then(|query| CString::new(query).ok()) {
+                Some(query) => NextAttempt::Reprepare(query),
+                None => NextAttempt::Fail,
+            };
+        }
+        let strategy = match self.strategy {
+            Some(ref strategy) => strategy.clone(),
+            None => return NextAttempt::Fail,
+        };
+        self.attempt += 1;
+        let decision = strategy.on_error(&RetryContext {
+            error: error,
+            error_result: error_result,
+            cause: RetryCause::of(error, error_result),
+            consistency: error_result.and_then(|result| result.result_consistency()),
+            attempt: self.attempt,
+            idempotent: self.idempotent,
+            elapsed: Duration::from_std(self.started.elapsed()).unwrap_or(Duration::max_value()),
+        });
+        let delay = match decision {
+            RetryDecision::Retry(delay) => delay,
+            RetryDecision::RetryAt(consistency, delay) => {
+                self.retry_consistency = Some(consistency);
+                delay
+            }
+            RetryDecision::Rethrow => return NextAttempt::Fail,
+            RetryDecision::Ignore => return NextAttempt::Ignore,
+        };
+        debug!("retrying after {} (attempt {}, delay {:?})", error, self.attempt, delay);
+        record_retry();
+        NextAttempt::Retry(delay.and_then(|delay| delay.to_std().ok()))
+    }
+
+    ///Sends the statement, at the consistency level the strategy asked for
+    ///if it did.
+    pub fn send(&mut self) -> *mut _Future {
+        if let Some(consistency) = self.retry_consistency.take() {
+            unsafe {
+                cass_statement_set_consistency(self.statement.inner(), raw_consistency(consistency));
+            }
+            self.restore_consistency = true;
+        }
+        unsafe { cass_session_execute(self.session.inner(), self.statement.inner()) }
+    }
+
+    ///Prepares the query again. The statement stays valid, since the node
+    ///gives the query the same id as before.
+    pub fn prepare(&self, query: &CString) -> *mut _Future {
+        debug!("re-preparing {:?}", query);
+        unsafe { cass_session_prepare(self.session.inner(), query.as_ptr()) }
+    }
+
+    ///Called once an attempt has completed: gives the statement back its
+    ///own consistency level if the attempt was sent at another one.
+    pub fn attempt_finished(&mut self) {
+        if self.restore_consistency {
+            self.restore_consistency = false;
+            let consistency = self.consistency.map_or(CASS_CONSISTENCY_UNKNOWN, raw_consistency);
+            unsafe {
+                cass_statement_set_consistency(self.statement.inner(), consistency);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_exponential_backoff_delay() {
+    let mut backoff = ExponentialBackoff::new();
+    backoff.set_base_delay(Duration::milliseconds(100)).set_max_delay(Duration::milliseconds(250));
+    assert_eq!(backoff.delay(1, 100), Duration::milliseconds(100));
+    assert_eq!(backoff.delay(1, 101), Duration::milliseconds(0));
+    assert_eq!(backoff.delay(2, 200), Duration::milliseconds(200));
+    // capped at the maximum delay
+    assert_eq!(backoff.delay(3, 250), Duration::milliseconds(250));
+    assert_eq!(backoff.delay(40, 250), Duration::milliseconds(250));
+    for random in 0..1000 {
+        assert!(backoff.delay(2, random * 7919) <= Duration::milliseconds(200));
+    }
+}
+
+#[test]
+fn test_retry_cause_of_error() {
+    let cause = |code| RetryCause::of(&CassError::build(code), None);
+    assert!(match cause(CASS_ERROR_SERVER_OVERLOADED) {
+        RetryCause::Overloaded => true,
+        _ => false,
+    });
+    assert!(match cause(CASS_ERROR_SERVER_IS_BOOTSTRAPPING) {
+        RetryCause::Overloaded => true,
+        _ => false,
+    });
+    for &code in &[CASS_ERROR_LIB_REQUEST_TIMED_OUT,
+                   CASS_ERROR_LIB_NO_HOSTS_AVAILABLE,
+                   CASS_ERROR_LIB_UNABLE_TO_CONNECT] {
+        assert!(match cause(code) {
+            RetryCause::RequestFailed => true,
+            _ => false,
+        });
+    }
+    // a server timeout without the server's details cannot be judged
+    assert!(match cause(CASS_ERROR_SERVER_READ_TIMEOUT) {
+        RetryCause::Other => true,
+        _ => false,
+    });
+    assert!(match cause(CASS_ERROR_SERVER_UNPREPARED) {
+        RetryCause::Other => true,
+        _ => false,
+    });
+}
+
+#[test]
+fn test_exponential_backoff_decisions() {
+    use cassandra_sys::CASS_WRITE_TYPE_SIMPLE;
+
+    let mut backoff = ExponentialBackoff::new();
+    backoff.set_base_delay(Duration::milliseconds(10)).set_max_delay(Duration::milliseconds(10)).set_max_retries(2);
+    let error = CassError::build(CASS_ERROR_SERVER_READ_TIMEOUT);
+    let decide = |backoff: &ExponentialBackoff, cause: RetryCause, attempt: u32, idempotent: bool| {
+        backoff.on_error(&RetryContext {
+            error: &error,
+            error_result: None,
+            cause: cause,
+            consistency: None,
+            attempt: attempt,
+            idempotent: idempotent,
+            elapsed: Duration::milliseconds(0),
+        })
+    };
+    let retried = |decision: RetryDecision| {
+        match decision {
+            RetryDecision::Retry(Some(delay)) => delay <= Duration::milliseconds(10),
+            _ => false,
+        }
+    };
+    let read_timeout = || {
+        RetryCause::ReadTimeout {
+            received: 1,
+            required: 2,
+            data_present: false,
+        }
+    };
+    let write_timeout = || {
+        RetryCause::WriteTimeout {
+            received: 1,
+            required: 2,
+            write_type: WriteType(CASS_WRITE_TYPE_SIMPLE),
+        }
+    };
+    let unavailable = || {
+        RetryCause::Unavailable {
+            required: 2,
+            alive: 1,
+        }
+    };
+
+    // retried whether or not the statement is idempotent
+    for &idempotent in &[false, true] {
+        assert!(retried(decide(&backoff, read_timeout(), 1, idempotent)));
+        assert!(retried(decide(&backoff, unavailable(), 1, idempotent)));
+        assert!(retried(decide(&backoff, RetryCause::Overloaded, 1, idempotent)));
+        assert_eq!(decide(&backoff, RetryCause::Other, 1, idempotent), RetryDecision::Rethrow);
+    }
+    // the failed attempt may have been applied
+    assert!(retried(decide(&backoff, write_timeout(), 1, true)));
+    assert_eq!(decide(&backoff, write_timeout(), 1, false), RetryDecision::Rethrow);
+    assert!(retried(decide(&backoff, RetryCause::RequestFailed, 1, true)));
+    assert_eq!(decide(&backoff, RetryCause::RequestFailed, 1, false), RetryDecision::Rethrow);
+
+    // out of retries
+    assert!(retried(decide(&backoff, read_timeout(), 2, false)));
+    assert_eq!(decide(&backoff, read_timeout(), 3, false), RetryDecision::Rethrow);
+
+    // past the deadline
+    assert_eq!(backoff.deadline(), None);
+    backoff.set_deadline(Duration::milliseconds(5)).set_base_delay(Duration::milliseconds(0));
+    assert_eq!(backoff.deadline(), Some(Duration::milliseconds(5)));
+    assert!(retried(decide(&backoff, read_timeout(), 1, false)));
+    let late = backoff.on_error(&RetryContext {
+        error: &error,
+        error_result: None,
+        cause: read_timeout(),
+        consistency: None,
+        attempt: 1,
+        idempotent: false,
+        elapsed: Duration::milliseconds(5),
+    });
+    assert_eq!(late, RetryDecision::Rethrow);
+}
@@ -1,5 +1,6 @@
-use cassandra::statement::Statement;
+use cassandra::statement::{Statement, bound_statement};
 
+use cassandra_sys::CASS_OK;
 use cassandra_sys::CassPrepared as _PreparedStatement;
 use cassandra_sys::cass_prepared_free;
 use cassandra_sys::cass_prepared_bind;
@@ -8,17 +9,40 @@ use cassandra_sys::cass_prepared_parameter_data_type;
 use cassandra_sys::cass_prepared_parameter_data_type_by_name;
 use cassandra::data_type::ConstDataType;
 use std::{mem, slice, str};
+use std::collections::HashMap;
 use std::ffi::CString;
+use std::sync::Arc;
+use cassandra::codec::CodecRegistry;
 use cassandra::util::Protected;
 
 /// A statement that has been prepared against at least one Cassandra node.
 /// Instances of this class should not be created directly, but through Session.prepare().
-pub struct PreparedStatement(*const _PreparedStatement);
+pub struct PreparedStatement {
+    inner: Arc<PreparedHandle>,
+    parameters: HashMap<String, Vec<u64>>,
+    query: Option<String>,
+    keyspace: Option<String>,
+    partition_key_indices: Option<Vec<u64>>,
+    codecs: Option<Arc<CodecRegistry>>,
+}
 
 unsafe impl Sync for PreparedStatement {}
 unsafe impl Send for PreparedStatement {}
 
-impl Drop for PreparedStatement {
+///Owns a driver prepared statement, which is freed once the
+///`PreparedStatement` and the statements bound from it are gone.
+pub struct PreparedHandle(*const _PreparedStatement);
+unsafe impl Sync for PreparedHandle {}
+unsafe impl Send for PreparedHandle {}
+
+impl PreparedHandle {
+    ///The driver prepared statement
+    pub fn inner(&self) -> *const _PreparedStatement {
+        self.0
+    }
+}
+
+impl Drop for PreparedHandle {
     ///Frees a prepared statement
     fn drop(&mut self) {
         if !self.0.is_null() {
@@ -30,17 +54,167 @@ impl Drop for PreparedStatement {
 
 impl Protected<*const _PreparedStatement> for PreparedStatement {
     fn inner(&self) -> *const _PreparedStatement {
-        self.0
+        self.inner.inner()
     }
     fn build(inner: *const _PreparedStatement) -> Self {
-        PreparedStatement(inner)
+        let mut parameters: HashMap<String, Vec<u64>> = HashMap::new();
+        if !inner.is_null() {
+            let mut index = 0;
+            while let Some(name) = unsafe { parameter_name(inner, index) } {
+                parameters.entry(name.to_owned()).or_insert_with(Vec::new).push(index);
+                index += 1;
+            }
+        }
+        PreparedStatement {
+            inner: Arc::new(PreparedHandle(inner)),
+            parameters: parameters,
+            query: None,
+            keyspace: None,
+            partition_key_indices: None,
+            codecs: None,
+        }
+    }
+}
+
+///Records the query a statement was prepared from, the keyspace of the
+///session that prepared it, the partition key columns of the table it reads
+///or writes, in key order, and the codecs of the session.
+pub fn set_source(prepared: &mut PreparedStatement,
+                  query: &str,
+                  keyspace: Option<String>,
+                  partition_key: &[String],
+                  codecs: Arc<CodecRegistry>) {
+    prepared.query = Some(query.to_owned());
+    prepared.keyspace = keyspace;
+    prepared.codecs = Some(codecs);
+    // a column bound by several parameters, such as `k = ? ... IF k = ?`,
+    // cannot be told apart by name, so the key is then left unknown
+    let indices: Option<Vec<u64>> = partition_key.iter()
+                                                 .map(|column| {
+                                                     match prepared.parameters.get(column) {
+                                                         Some(indices) if indices.len() == 1 => Some(indices[0]),
+                                                         _ => None,
+                                                     }
+                                                 })
+                                                 .collect();
+    prepared.partition_key_indices = match indices {
+        Some(ref indices) if indices.is_empty() => None,
+        indices => indices,
+    };
+}
+
+///The keyspace, if given, and the table a CQL statement reads or writes.
+///Unquoted names are lower-cased, as Cassandra does.
+pub fn query_table(query: &str) -> Option<(Option<String>, String)> {
+    let mut words = query.split_whitespace();
+    let keyword = match words.next() {
+        Some(word) => word.to_lowercase(),
+        None => return None,
+    };
+    let table = match &keyword[..] {
+        "update" => words.next(),
+        "insert" => words.skip_while(|word| word.to_lowercase() != "into").nth(1),
+        "select" | "delete" => words.skip_while(|word| word.to_lowercase() != "from").nth(1),
+        _ => None,
+    };
+    let table = match table {
+        Some(table) => table.split('(').next().unwrap_or(table),
+        None => return None,
+    };
+    let mut names = names(table);
+    match names.len() {
+        1 => Some((None, names.remove(0))),
+        2 => {
+            let table = names.remove(1);
+            Some((Some(names.remove(0)), table))
+        }
+        _ => None,
+    }
+}
+
+///The keyspace a `USE` statement switches to.
+pub fn used_keyspace(query: &str) -> Option<String> {
+    let mut words = query.split_whitespace();
+    match words.next() {
+        Some(word) if word.to_lowercase() == "use" => {}
+        _ => return None,
+    }
+    let mut names = names(&words.collect::<Vec<_>>().join(" "));
+    match names.len() {
+        1 if !names[0].is_empty() => Some(names.remove(0)),
+        _ => None,
+    }
+}
+
+///Splits a dotted name, unquoting quoted parts and lower-casing the others
+fn names(text: &str) -> Vec<String> {
+    let mut names = vec![];
+    let mut name = String::new();
+    let mut quoted = false;
+    let mut chars = text.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                name.push('"');
+            }
+            '"' => quoted = !quoted,
+            '.' if !quoted => names.push(name.split_off(0)),
+            ';' if !quoted => break,
+            c if quoted => name.push(c),
+            c if c.is_whitespace() => {}
+            c => name.extend(c.to_lowercase()),
+        }
+    }
+    names.push(name);
+    names
+}
+
+unsafe fn parameter_name<'a>(prepared: *const _PreparedStatement, index: u64) -> Option<&'a str> {
+    let mut name = mem::zeroed();
+    let mut name_length = mem::zeroed();
+    match cass_prepared_parameter_name(prepared, index, &mut name, &mut name_length) {
+        CASS_OK => str::from_utf8(slice::from_raw_parts(name as *const u8, name_length as usize)).ok(),
+        _ => None,
     }
 }
 
 impl PreparedStatement {
-    ///Creates a bound statement from a pre-prepared statement.
+    ///Creates a bound statement from a pre-prepared statement, which
+    ///encodes custom types with the codecs of the session that prepared it.
     pub fn bind(&self) -> Statement {
-        unsafe { Statement::build(cass_prepared_bind(self.0)) }
+        let mut statement = unsafe {
+            bound_statement(cass_prepared_bind(self.inner.inner()),
+                            Some(self.inner.clone()),
+                            Some(self.parameters.clone()),
+                            self.query.clone(),
+                            self.keyspace.clone(),
+                            self.partition_key_indices.clone().unwrap_or_else(Vec::new))
+        };
+        if let Some(ref codecs) = self.codecs {
+            statement.set_codec_registry(codecs.clone());
+        }
+        statement
+    }
+
+    ///The indices of the parameters that bind the partition key columns,
+    ///in key order.
+    ///
+    ///Cassandra sends these with the prepared metadata, but the driver's C
+    ///API does not expose them. They are instead worked out when the
+    ///statement is prepared with `Session::prepare`, by matching the table's
+    ///partition key columns against the parameter names. This can only be
+    ///as good as the names: `None` when the table is not known, when a key
+    ///column is not bound by a parameter of its own name (for instance a
+    ///named marker such as `k = :id`), or when it is bound more than once.
+    pub fn partition_key_indices(&self) -> Option<&[u64]> {
+        self.partition_key_indices.as_ref().map(|indices| &indices[..])
+    }
+
+    ///The query this statement was prepared from, if it was prepared with
+    ///`Session::prepare`.
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_ref().map(|query| &query[..])
     }
 
     ///Gets the name of a parameter at the specified index.
@@ -49,7 +223,7 @@ impl PreparedStatement {
         unsafe {
             let mut name = mem::zeroed();
             let mut name_length = mem::zeroed();
-            cass_prepared_parameter_name(self.0, index, &mut name, &mut name_length);
+            cass_prepared_parameter_name(self.inner.inner(), index, &mut name, &mut name_length);
             str::from_utf8(slice::from_raw_parts(name as *const u8, name_length as usize))
         }
     }
@@ -59,7 +233,7 @@ impl PreparedStatement {
     ///Returns a reference to the data type of the parameter. Do not free
     ///this reference as it is bound to the lifetime of the prepared.
     pub fn parameter_data_type(&self, index: u64) -> ConstDataType {
-        unsafe { ConstDataType(cass_prepared_parameter_data_type(self.0, index)) }
+        unsafe { ConstDataType(cass_prepared_parameter_data_type(self.inner.inner(), index)) }
     }
 
     ///Gets the data type of a parameter for the specified name.
@@ -68,10 +242,30 @@ impl PreparedStatement {
     ///this reference as it is bound to the lifetime of the prepared.
     pub fn parameter_data_type_by_name(&self, name: &str) -> ConstDataType {
         unsafe {
-            ConstDataType(cass_prepared_parameter_data_type_by_name(self.0,
+            ConstDataType(cass_prepared_parameter_data_type_by_name(self.inner.inner(),
                                                                     CString::new(name)
                                                                         .expect("must be utf8")
                                                                         .as_ptr()))
         }
     }
 }
+
+#[test]
+fn test_query_table() {
+    assert_eq!(query_table("SELECT * FROM users WHERE id = ?"),
+               Some((None, "users".to_owned())));
+    assert_eq!(query_table("insert into Examples.\"Users\"(id, name) VALUES (?, ?)"),
+               Some((Some("examples".to_owned()), "Users".to_owned())));
+    assert_eq!(query_table("UPDATE ks.t SET v = ? WHERE id = ?;"),
+               Some((Some("ks".to_owned()), "t".to_owned())));
+    assert_eq!(query_table("DELETE v FROM \"a\"\"b\"; "), Some((None, "a\"b".to_owned())));
+    assert_eq!(query_table("CREATE TABLE t (id int PRIMARY KEY)"), None);
+}
+
+#[test]
+fn test_used_keyspace() {
+    assert_eq!(used_keyspace("USE Examples;"), Some("examples".to_owned()));
+    assert_eq!(used_keyspace("use \"My Keyspace\" ;"), Some("My Keyspace".to_owned()));
+    assert_eq!(used_keyspace("USE"), None);
+    assert_eq!(used_keyspace("SELECT * FROM users"), None);
+}
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use cassandra::error::CassError;
+use cassandra::metrics::record_prepared_cache;
+use cassandra::prepared::PreparedStatement;
+use cassandra::util::Protected;
+
+use cassandra_sys::CassError as _CassError;
+use cassandra_sys::CASS_ERROR_LIB_INTERNAL_ERROR;
+
+///Keyspace and query text a statement was prepared for
+type CacheKey = (Option<String>, String);
+
+///A prepare that another caller is already waiting on
+struct InFlight {
+    result: Mutex<Option<Result<Arc<PreparedStatement>, _CassError>>>,
+    done: Condvar,
+}
+
+impl InFlight {
+    fn finish(&self, result: Result<Arc<PreparedStatement>, _CassError>) {
+        *self.result.lock().unwrap() = Some(result);
+        self.done.notify_all();
+    }
+}
+
+///Removes a prepare from the in-flight prepares and fails its waiters if
+///the caller preparing it panics
+struct PrepareGuard<'a> {
+    cache: &'a PreparedCache,
+    key: Option<CacheKey>,
+    in_flight: Arc<InFlight>,
+}
+
+impl<'a> Drop for PrepareGuard<'a> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            if let Ok(mut state) = self.cache.state.lock() {
+                state.in_flight.remove(&key);
+            }
+            self.in_flight.finish(Err(CASS_ERROR_LIB_INTERNAL_ERROR));
+        }
+    }
+}
+
+struct CacheState {
+    keyspace: Option<String>,
+    entries: HashMap<CacheKey, (Arc<PreparedStatement>, u64)>,
+    in_flight: HashMap<CacheKey, Arc<InFlight>>,
+    clock: u64,
+}
+
+///Hit and miss counts of a session's prepared statement cache
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub struct PreparedCacheStats {
+    ///Lookups served from the cache, including those that waited for a
+    ///prepare already in flight
+    pub hits: u64,
+    ///Lookups that had to prepare the statement
+    pub misses: u64,
+    ///Statements currently cached
+    pub size: usize,
+    ///Maximum number of cached statements
+    pub capacity: usize,
+}
+
+///A bounded least-recently-used cache of prepared statements.
+pub struct PreparedCache {
+    capacity: usize,
+    state: Mutex<CacheState>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl PreparedCache {
+    ///Creates a cache holding at most `capacity` statements.
+    pub fn new(capacity: usize) -> Self {
+        PreparedCache {
+            capacity: capacity,
+            state: Mutex::new(CacheState {
+                keyspace: None,
+                entries: HashMap::new(),
+                in_flight: HashMap::new(),
+                clock: 0,
+            }),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    ///Sets the keyspace that subsequent lookups are keyed by.
+    pub fn set_keyspace(&self, keyspace: Option<String>) {
+        self.state.lock().unwrap().keyspace = keyspace;
+    }
+
+    ///The keyspace lookups are keyed by.
+    pub fn keyspace(&self) -> Option<String> {
+        self.state.lock().unwrap().keyspace.clone()
+    }
+
+    ///Returns the cached statement for the query, or prepares it with
+    ///`prepare`. Concurrent lookups of the same query share a single prepare;
+    ///if `prepare` panics, those waiting on it fail with
+    ///`CASS_ERROR_LIB_INTERNAL_ERROR`.
+    pub fn get_or_prepare<F>(&self, query: &str, prepare: F) -> Result<Arc<PreparedStatement>, CassError>
+        where F: FnOnce() -> Result<PreparedStatement, CassError> {
+        let mut guard = {
+            let mut state = self.state.lock().unwrap();
+            let key = (state.keyspace.clone(), query.to_owned());
+            state.clock += 1;
+            let clock = state.clock;
+            if let Some(entry) = state.entries.get_mut(&key) {
+                entry.1 = clock;
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                record_prepared_cache(true);
+                return Ok(entry.0.clone());
+            }
+            if let Some(in_flight) = state.in_flight.get(&key).cloned() {
+                drop(state);
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                record_prepared_cache(true);
+                return wait_for(&in_flight);
+            }
+            let in_flight = Arc::new(InFlight {
+                result: Mutex::new(None),
+                done: Condvar::new(),
+            });
+            state.in_flight.insert(key.clone(), in_flight.clone());
+            PrepareGuard {
+                cache: self,
+                key: Some(key),
+                in_flight: in_flight,
+            }
+        };
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        record_prepared_cache(false);
+
+        let result = prepare().map(Arc::new);
+        let key = guard.key.take().expect("prepare in flight");
+        {
+            let mut state = self.state.lock().unwrap();
+            state.in_flight.remove(&key);
+            if let Ok(ref prepared) = result {
+                let clock = state.clock;
+                state.entries.insert(key, (prepared.clone(), clock));
+                self.evict(&mut state);
+            }
+        }
+        guard.in_flight.finish(match result {
+            Ok(ref prepared) => Ok(prepared.clone()),
+            Err(ref err) => Err(err.inner()),
+        });
+        result
+    }
+
+    ///Removes a query from the cache so that it is prepared again on next use.
+    pub fn invalidate(&self, query: &str) {
+        let mut state = self.state.lock().unwrap();
+        let key = (state.keyspace.clone(), query.to_owned());
+        state.entries.remove(&key);
+    }
+
+    ///Current hit and miss counts.
+    pub fn stats(&self) -> PreparedCacheStats {
+        PreparedCacheStats {
+            hits: self.hits.load(Ordering::Relaxed) as u64,
+            misses: self.misses.load(Ordering::Relaxed) as u64,
+            size: self.state.lock().unwrap().entries.len(),
+            capacity: self.capacity,
+        }
+    }
+
+    fn evict(&self, state: &mut CacheState) {
+        while state.entries.len() > self.capacity {
+            let oldest = state.entries
+                              .iter()
+                              .min_by_key(|&(_, &(_, used))| used)
+                              .map(|(key, _)| key.clone());
+            match oldest {
+                Some(key) => state.entries.remove(&key),
+                None => return,
+            };
+        }
+    }
+}
+
+fn wait_for(in_flight: &InFlight) -> Result<Arc<PreparedStatement>, CassError> {
+    let mut result = in_flight.result.lock().unwrap();
+    while result.is_none() {
+        result = in_flight.done.wait(result).unwrap();
+    }
+    match *result {
+        Some(Ok(ref prepared)) => Ok(prepared.clone()),
+        Some(Err(err)) => Err(CassError::build(err)),
+        None => unreachable!(),
+    }
+}
+
+#[test]
+fn test_prepared_cache_panicking_prepare() {
+    use std::ptr;
+    use std::sync::mpsc;
+    use std::thread;
+
+    let cache = Arc::new(PreparedCache::new(2));
+    let (started, wait_started) = mpsc::channel();
+    let (release, wait_release) = mpsc::channel::<()>();
+    let preparing = {
+        let cache = cache.clone();
+        thread::spawn(move || {
+            cache.get_or_prepare("a", || -> Result<PreparedStatement, CassError> {
+                started.send(()).unwrap();
+                wait_release.recv().unwrap();
+                panic!("prepare failed")
+            })
+        })
+    };
+    wait_started.recv().unwrap();
+    let waiting = {
+        let cache = cache.clone();
+        thread::spawn(move || {
+            cache.get_or_prepare("a", || -> Result<PreparedStatement, CassError> { panic!("not the first caller") })
+                 .map(|_| ())
+                 .map_err(|err| err.inner())
+        })
+    };
+    while cache.stats().hits == 0 {
+        thread::yield_now();
+    }
+    release.send(()).unwrap();
+    assert!(preparing.join().is_err());
+    assert_eq!(waiting.join().unwrap(), Err(CASS_ERROR_LIB_INTERNAL_ERROR));
+
+    // the query is prepared again by the next caller
+    assert!(cache.get_or_prepare("a", || Ok(PreparedStatement::build(ptr::null()))).is_ok());
+    assert_eq!(cache.stats().size, 1);
+}
+
+#[test]
+fn test_prepared_cache_evicts_least_recently_used() {
+    use std::ptr;
+
+    let cache = PreparedCache::new(2);
+    let prepare = || -> Result<PreparedStatement, CassError> { Ok(PreparedStatement::build(ptr::null())) };
+    cache.get_or_prepare("a", &prepare).unwrap();
+    cache.get_or_prepare("b", &prepare).unwrap();
+    cache.get_or_prepare("a", &prepare).unwrap();
+    cache.get_or_prepare("c", &prepare).unwrap();
+    cache.get_or_prepare("a", &prepare).unwrap();
+    cache.get_or_prepare("b", &prepare).unwrap();
+    assert_eq!(cache.stats(),
+               PreparedCacheStats {
+                   hits: 2,
+                   misses: 4,
+                   size: 2,
+                   capacity: 2,
+               });
+}
@@ -0,0 +1,992 @@
+//! A fluent builder for CQL statements.
+//!
+//! Builders escape identifiers, emit a `?` bind marker for every value and
+//! keep the values in marker order, so the resulting `Statement` always has
+//! the right parameter count.
+//!
+//! Tables are named either by a bare table name or by a `(keyspace, table)`
+//! pair. A table name built from the schema metadata also carries the types
+//! of its columns, and every value is then checked against the type of its
+//! column when the statement is built.
+//!
+//! ```ignore
+//! use cassandra::query_builder::{Operator, select, update, TableName, ToStatement};
+//!
+//! let statement = try!(select("users").columns(&["id", "name"]).where_eq("id", 5).limit(10).to_statement());
+//! let statement = try!(update(("stats", "page_views")).increment("views", 1).where_eq("page", "/").to_statement());
+//! let statement = try!(select("users").where_token(&["id"], Operator::Gt, 0i64).to_statement());
+//!
+//! let users = try!(TableName::from_meta("examples", &table_meta));
+//! let statement = try!(select(users).columns(&["count(*)"]).where_eq("id", 5i64).to_statement());
+//! ```
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter, Write};
+use std::fmt;
+use std::sync::Arc;
+
+use cassandra::cql_type::CqlType;
+use cassandra::cql_value::CqlValue;
+use cassandra::error::CassError;
+use cassandra::schema::table_meta::TableMeta;
+use cassandra::statement::Statement;
+
+const RESERVED_KEYWORDS: &'static [&'static str] = &["add", "allow", "alter", "and", "apply", "asc", "authorize",
+                                                     "batch", "begin", "by", "columnfamily", "create", "delete",
+                                                     "desc", "describe", "drop", "entries", "execute", "from",
+                                                     "full", "grant", "if", "in", "index", "infinity", "insert",
+                                                     "into", "keyspace", "limit", "modify", "nan", "norecursive",
+                                                     "not", "null", "of", "on", "or", "order", "primary", "rename",
+                                                     "replace", "revoke", "schema", "select", "set", "table", "to",
+                                                     "token", "truncate", "unlogged", "update", "use", "using",
+                                                     "where", "with"];
+
+///Quotes a column, table or keyspace name when CQL would not accept it as
+///is: names with upper case or special characters, and reserved keywords.
+pub fn escape_identifier(name: &str) -> String {
+    let plain = match name.chars().next() {
+        Some('a'...'z') => {
+            name.chars().all(|c| match c {
+                'a'...'z' | '0'...'9' | '_' => true,
+                _ => false,
+            })
+        }
+        _ => false,
+    };
+    if plain && !RESERVED_KEYWORDS.contains(&name) {
+        name.to_owned()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+///A table, optionally qualified by its keyspace.
+///
+///Converted from a table name, `"users"`, or from a keyspace and table
+///name, `("ks", "users")`. Each name is escaped on its own, so a dot is part
+///of the name rather than a separator.
+///
+///A table name may also know the types of the table's columns, see
+///`from_meta`. Builders then refuse unknown columns and values that do not
+///fit their column, so `where_eq("id", 5)` on a `bigint` column fails rather
+///than binding an `int`.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct TableName {
+    keyspace: Option<String>,
+    table: String,
+    columns: Option<Arc<BTreeMap<String, CqlType>>>,
+}
+
+impl<'a> From<&'a str> for TableName {
+    fn from(table: &'a str) -> TableName {
+        TableName {
+            keyspace: None,
+            table: table.to_owned(),
+            columns: None,
+        }
+    }
+}
+
+impl<'a, 'b> From<(&'a str, &'b str)> for TableName {
+    fn from((keyspace, table): (&'a str, &'b str)) -> TableName {
+        TableName {
+            keyspace: Some(keyspace.to_owned()),
+            table: table.to_owned(),
+            columns: None,
+        }
+    }
+}
+
+impl TableName {
+    ///Names a table of the given keyspace and takes the types of its columns
+    ///from its schema metadata.
+    pub fn from_meta(keyspace: &str, table: &TableMeta) -> Result<TableName, CassError> {
+        let mut columns = vec![];
+        for index in 0..table.column_count() {
+            let column = table.column(index);
+            columns.push((column.name(), try!(column.data_type().cql_type())));
+        }
+        Ok(TableName::from((keyspace, table.get_name().as_str())).with_column_types(columns))
+    }
+
+    ///Sets the types of the table's columns, by column name.
+    pub fn with_column_types(mut self, columns: Vec<(String, CqlType)>) -> TableName {
+        self.columns = Some(Arc::new(columns.into_iter().collect()));
+        self
+    }
+
+    fn check(&self, column: &str, part: Part, value: &CqlValue) -> Result<(), BuildError> {
+        let columns = match self.columns {
+            Some(ref columns) => columns,
+            None => return Ok(()),
+        };
+        let column_type = try!(columns.get(column).ok_or_else(|| BuildError::UnknownColumn(column.to_owned())));
+        match part.of(column_type) {
+            Some(ref expected) if fits(value, expected) => Ok(()),
+            _ => Err(BuildError::WrongType(column.to_owned(), column_type.clone())),
+        }
+    }
+}
+
+///Which part of a column's type a value stands for.
+#[derive(Clone,Copy,Debug)]
+enum Part {
+    Whole,
+    // an element of a list or set, or a value of a map
+    Element,
+    Key,
+    Value,
+    // what `column - ?` takes: a set of keys for a map
+    Removal,
+}
+
+impl Part {
+    fn of(self, column_type: &CqlType) -> Option<CqlType> {
+        match (self, column_type) {
+            (Part::Whole, _) => Some(column_type.clone()),
+            (Part::Element, &CqlType::List(ref item)) |
+            (Part::Element, &CqlType::Set(ref item)) |
+            (Part::Element, &CqlType::Map(_, ref item)) |
+            (Part::Value, &CqlType::Map(_, ref item)) |
+            (Part::Key, &CqlType::Map(ref item, _)) => Some((**item).clone()),
+            (Part::Removal, &CqlType::Map(ref key, _)) => Some(CqlType::Set(key.clone())),
+            (Part::Removal, _) => Some(column_type.clone()),
+            _ => None,
+        }
+    }
+}
+
+///Returns true if the value can be bound where Cassandra expects the type.
+fn fits(value: &CqlValue, cql_type: &CqlType) -> bool {
+    match (value, cql_type) {
+        (&CqlValue::Null, _) => true,
+        (&CqlValue::Boolean(_), &CqlType::Boolean) |
+        (&CqlValue::TinyInt(_), &CqlType::TinyInt) |
+        (&CqlValue::SmallInt(_), &CqlType::SmallInt) |
+        (&CqlValue::Int(_), &CqlType::Int) |
+        (&CqlValue::Bigint(_), &CqlType::Bigint) |
+        (&CqlValue::Bigint(_), &CqlType::Counter) |
+        (&CqlValue::Bigint(_), &CqlType::Timestamp) |
+        (&CqlValue::Bigint(_), &CqlType::Time) |
+        (&CqlValue::Date(_), &CqlType::Date) |
+        (&CqlValue::Float(_), &CqlType::Float) |
+        (&CqlValue::Double(_), &CqlType::Double) |
+        (&CqlValue::Text(_), &CqlType::Ascii) |
+        (&CqlValue::Text(_), &CqlType::Text) |
+        (&CqlValue::Text(_), &CqlType::Varchar) |
+        (&CqlValue::Blob(_), &CqlType::Blob) |
+        (&CqlValue::Blob(_), &CqlType::Varint) |
+        (&CqlValue::Blob(_), &CqlType::Decimal) |
+        (&CqlValue::Blob(_), &CqlType::Custom(_)) |
+        (&CqlValue::Uuid(_), &CqlType::Uuid) |
+        (&CqlValue::Uuid(_), &CqlType::Timeuuid) |
+        (&CqlValue::Inet(_), &CqlType::Inet) => true,
+        (&CqlValue::List(ref items), &CqlType::List(ref item)) |
+        (&CqlValue::Set(ref items), &CqlType::Set(ref item)) => items.iter().all(|value| fits(value, item)),
+        (&CqlValue::Map(ref entries), &CqlType::Map(ref key, ref item)) => {
+            entries.iter().all(|&(ref k, ref v)| fits(k, key) && fits(v, item))
+        }
+        (&CqlValue::Tuple(ref items), &CqlType::Tuple(ref types)) => {
+            items.len() == types.len() && items.iter().zip(types).all(|(value, item)| fits(value, item))
+        }
+        (&CqlValue::Udt(_, ref fields), &CqlType::Udt { fields: ref types, .. }) => {
+            fields.iter().all(|&(ref name, ref value)| {
+                types.iter().any(|&(ref field, ref item)| field == name && fits(value, item))
+            })
+        }
+        _ => false,
+    }
+}
+
+impl Display for TableName {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if let Some(ref keyspace) = self.keyspace {
+            try!(write!(f, "{}.", escape_identifier(keyspace)));
+        }
+        write!(f, "{}", escape_identifier(&self.table))
+    }
+}
+
+fn join_columns(columns: &[String]) -> String {
+    columns.iter().map(|column| escape_identifier(column)).collect::<Vec<_>>().join(", ")
+}
+
+// function calls such as count(*) are selected as written
+fn join_selectors(selectors: &[String]) -> String {
+    selectors.iter()
+        .map(|selector| if selector.contains('(') { selector.clone() } else { escape_identifier(selector) })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+///Why a builder does not make a valid statement
+pub enum BuildError {
+    ///An INSERT sets no column
+    NoValues,
+    ///An UPDATE has no SET clause
+    NoAssignments,
+    ///An UPDATE or DELETE has no WHERE clause
+    NoWhereClause,
+    ///The table has no column of this name
+    UnknownColumn(String),
+    ///A value does not fit the type of its column
+    WrongType(String, CqlType),
+    ///The driver rejected a value
+    Bind(CassError),
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            BuildError::NoValues => write!(f, "an INSERT needs at least one column value"),
+            BuildError::NoAssignments => write!(f, "an UPDATE needs at least one assignment"),
+            BuildError::NoWhereClause => write!(f, "an UPDATE or DELETE needs a WHERE clause"),
+            BuildError::UnknownColumn(ref column) => write!(f, "the table has no column {}", column),
+            BuildError::WrongType(ref column, ref cql_type) => {
+                write!(f, "a value does not fit column {} of type {}", column, cql_type)
+            }
+            BuildError::Bind(ref err) => write!(f, "cannot bind a value: {}", err),
+        }
+    }
+}
+
+impl Debug for BuildError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Error for BuildError {
+    fn description(&self) -> &str {
+        match *self {
+            BuildError::NoValues => "INSERT without values",
+            BuildError::NoAssignments => "UPDATE without assignments",
+            BuildError::NoWhereClause => "missing WHERE clause",
+            BuildError::UnknownColumn(..) => "unknown column",
+            BuildError::WrongType(..) => "value of the wrong type",
+            BuildError::Bind(..) => "cannot bind a value",
+        }
+    }
+}
+
+///Anything that renders to a CQL query with positional bind markers.
+pub trait ToStatement {
+    ///Renders the query and the values for its bind markers, in order, or
+    ///fails if Cassandra would reject the statement.
+    fn build(&self) -> Result<(String, Vec<CqlValue>), BuildError>;
+
+    ///Creates a statement for the query with all of its values bound.
+    fn to_statement(&self) -> Result<Statement, BuildError> {
+        let (query, values) = try!(self.build());
+        let mut statement = Statement::new(&query, values.len() as u64);
+        for (index, value) in values.iter().enumerate() {
+            try!(statement.bind_value(index as u64, value).map_err(BuildError::Bind));
+        }
+        Ok(statement)
+    }
+}
+
+///A comparison operator of a WHERE or IF relation
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Operator {
+    ///=
+    Eq,
+    ///<
+    Lt,
+    ///<=
+    Lte,
+    ///>
+    Gt,
+    ///>=
+    Gte,
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Operator::Eq => "=",
+            Operator::Lt => "<",
+            Operator::Lte => "<=",
+            Operator::Gt => ">",
+            Operator::Gte => ">=",
+        })
+    }
+}
+
+///A column relation in a WHERE or IF clause.
+#[derive(Clone,Debug)]
+enum Relation {
+    Compare(String, Operator, CqlValue),
+    Contains(String, CqlValue),
+    In(String, Vec<CqlValue>),
+    Token(Vec<String>, Operator, CqlValue),
+}
+
+impl Relation {
+    fn check(&self, table: &TableName) -> Result<(), BuildError> {
+        match *self {
+            Relation::Compare(ref column, _, ref value) => table.check(column, Part::Whole, value),
+            Relation::Contains(ref column, ref value) => table.check(column, Part::Element, value),
+            Relation::In(ref column, ref values) => {
+                for value in values {
+                    try!(table.check(column, Part::Whole, value));
+                }
+                Ok(())
+            }
+            // the type of a token depends on the partitioner
+            Relation::Token(..) => Ok(()),
+        }
+    }
+
+    fn render(&self, query: &mut String, values: &mut Vec<CqlValue>) {
+        match *self {
+            Relation::Compare(ref column, operator, ref value) => {
+                write!(query, "{} {} ?", escape_identifier(column), operator).unwrap();
+                values.push(value.clone());
+            }
+            Relation::Contains(ref column, ref value) => {
+                write!(query, "{} CONTAINS ?", escape_identifier(column)).unwrap();
+                values.push(value.clone());
+            }
+            Relation::In(ref column, ref in_values) => {
+                let markers = vec!["?"; in_values.len()].join(", ");
+                write!(query, "{} IN ({})", escape_identifier(column), markers).unwrap();
+                values.extend(in_values.iter().cloned());
+            }
+            Relation::Token(ref columns, operator, ref value) => {
+                write!(query, "token({}) {} ?", join_columns(columns), operator).unwrap();
+                values.push(value.clone());
+            }
+        }
+    }
+}
+
+fn check_relations(table: &TableName, relations: &[Relation]) -> Result<(), BuildError> {
+    for relation in relations {
+        try!(relation.check(table));
+    }
+    Ok(())
+}
+
+fn render_relations(keyword: &str, relations: &[Relation], query: &mut String, values: &mut Vec<CqlValue>) {
+    for (index, relation) in relations.iter().enumerate() {
+        query.push_str(if index == 0 { keyword } else { " AND " });
+        relation.render(query, values);
+    }
+}
+
+///A lightweight transaction condition.
+#[derive(Clone,Debug)]
+enum Condition {
+    None,
+    Exists,
+    Columns(Vec<Relation>),
+}
+
+impl Condition {
+    fn add(&mut self, relation: Relation) {
+        if let Condition::Columns(ref mut relations) = *self {
+            relations.push(relation);
+            return;
+        }
+        *self = Condition::Columns(vec![relation]);
+    }
+
+    fn check(&self, table: &TableName) -> Result<(), BuildError> {
+        match *self {
+            Condition::Columns(ref relations) => check_relations(table, relations),
+            _ => Ok(()),
+        }
+    }
+
+    fn render(&self, query: &mut String, values: &mut Vec<CqlValue>) {
+        match *self {
+            Condition::None => {}
+            Condition::Exists => query.push_str(" IF EXISTS"),
+            Condition::Columns(ref relations) => render_relations(" IF ", relations, query, values),
+        }
+    }
+}
+
+///USING TTL and TIMESTAMP options of a write.
+#[derive(Clone,Debug,Default)]
+struct Using {
+    ttl: Option<i32>,
+    timestamp: Option<i64>,
+}
+
+impl Using {
+    fn render(&self, query: &mut String, values: &mut Vec<CqlValue>) {
+        match (self.ttl, self.timestamp) {
+            (Some(ttl), Some(timestamp)) => {
+                query.push_str(" USING TTL ? AND TIMESTAMP ?");
+                values.push(CqlValue::Int(ttl));
+                values.push(CqlValue::Bigint(timestamp));
+            }
+            (Some(ttl), None) => {
+                query.push_str(" USING TTL ?");
+                values.push(CqlValue::Int(ttl));
+            }
+            (None, Some(timestamp)) => {
+                query.push_str(" USING TIMESTAMP ?");
+                values.push(CqlValue::Bigint(timestamp));
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+macro_rules! where_clauses {
+    () => {
+        ///Adds a `column = ?` relation to the WHERE clause.
+        pub fn where_eq<V: Into<CqlValue>>(mut self, column: &str, value: V) -> Self {
+            self.relations.push(Relation::Compare(column.to_owned(), Operator::Eq, value.into()));
+            self
+        }
+
+        ///Adds a `column < ?` relation to the WHERE clause.
+        pub fn where_lt<V: Into<CqlValue>>(mut self, column: &str, value: V) -> Self {
+            self.relations.push(Relation::Compare(column.to_owned(), Operator::Lt, value.into()));
+            self
+        }
+
+        ///Adds a `column <= ?` relation to the WHERE clause.
+        pub fn where_lte<V: Into<CqlValue>>(mut self, column: &str, value: V) -> Self {
+            self.relations.push(Relation::Compare(column.to_owned(), Operator::Lte, value.into()));
+            self
+        }
+
+        ///Adds a `column > ?` relation to the WHERE clause.
+        pub fn where_gt<V: Into<CqlValue>>(mut self, column: &str, value: V) -> Self {
+            self.relations.push(Relation::Compare(column.to_owned(), Operator::Gt, value.into()));
+            self
+        }
+
+        ///Adds a `column >= ?` relation to the WHERE clause.
+        pub fn where_gte<V: Into<CqlValue>>(mut self, column: &str, value: V) -> Self {
+            self.relations.push(Relation::Compare(column.to_owned(), Operator::Gte, value.into()));
+            self
+        }
+
+        ///Adds a `column IN (?, ...)` relation to the WHERE clause.
+        pub fn where_in<V: Into<CqlValue>>(mut self, column: &str, values: Vec<V>) -> Self {
+            let values = values.into_iter().map(Into::into).collect();
+            self.relations.push(Relation::In(column.to_owned(), values));
+            self
+        }
+
+        ///Adds a `column CONTAINS ?` relation to the WHERE clause.
+        pub fn where_contains<V: Into<CqlValue>>(mut self, column: &str, value: V) -> Self {
+            self.relations.push(Relation::Contains(column.to_owned(), value.into()));
+            self
+        }
+    };
+}
+
+macro_rules! if_conditions {
+    () => {
+        ///Adds an `IF column = ?` condition, making this a lightweight
+        ///transaction.
+        pub fn if_eq<V: Into<CqlValue>>(mut self, column: &str, value: V) -> Self {
+            self.condition.add(Relation::Compare(column.to_owned(), Operator::Eq, value.into()));
+            self
+        }
+
+        ///Applies the write only if the row exists.
+        pub fn if_exists(mut self) -> Self {
+            self.condition = Condition::Exists;
+            self
+        }
+    };
+}
+
+///Sort order of a clustering column
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Order {
+    ///ASC
+    Asc,
+    ///DESC
+    Desc,
+}
+
+///Builds a SELECT statement
+#[derive(Clone,Debug)]
+pub struct Select {
+    table: TableName,
+    columns: Vec<String>,
+    relations: Vec<Relation>,
+    order_by: Vec<(String, Order)>,
+    limit: Option<i32>,
+    allow_filtering: bool,
+}
+
+///Starts a SELECT from the given table.
+pub fn select<T: Into<TableName>>(table: T) -> Select {
+    Select {
+        table: table.into(),
+        columns: vec![],
+        relations: vec![],
+        order_by: vec![],
+        limit: None,
+        allow_filtering: false,
+    }
+}
+
+impl Select {
+    ///Selects the given columns instead of `*`. Function calls such as
+    ///`count(*)` or `writetime(name)` are selected as written.
+    pub fn columns(mut self, columns: &[&str]) -> Self {
+        self.columns.extend(columns.iter().map(|column| (*column).to_owned()));
+        self
+    }
+
+    where_clauses!();
+
+    ///Restricts the `token()` of the partition key columns, as used for
+    ///token range scans.
+    pub fn where_token<V: Into<CqlValue>>(mut self, columns: &[&str], operator: Operator, value: V) -> Self {
+        let columns = columns.iter().map(|column| (*column).to_owned()).collect();
+        self.relations.push(Relation::Token(columns, operator, value.into()));
+        self
+    }
+
+    ///Orders the results by a clustering column.
+    pub fn order_by(mut self, column: &str, order: Order) -> Self {
+        self.order_by.push((column.to_owned(), order));
+        self
+    }
+
+    ///Limits the number of returned rows.
+    pub fn limit(mut self, limit: i32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    ///Adds ALLOW FILTERING.
+    pub fn allow_filtering(mut self) -> Self {
+        self.allow_filtering = true;
+        self
+    }
+}
+
+impl ToStatement for Select {
+    fn build(&self) -> Result<(String, Vec<CqlValue>), BuildError> {
+        try!(check_relations(&self.table, &self.relations));
+        let mut query = String::from("SELECT ");
+        let mut values = vec![];
+        if self.columns.is_empty() {
+            query.push('*');
+        } else {
+            query.push_str(&join_selectors(&self.columns));
+        }
+        write!(query, " FROM {}", self.table).unwrap();
+        render_relations(" WHERE ", &self.relations, &mut query, &mut values);
+        for (index, &(ref column, order)) in self.order_by.iter().enumerate() {
+            query.push_str(if index == 0 { " ORDER BY " } else { ", " });
+            query.push_str(&escape_identifier(column));
+            query.push_str(match order {
+                Order::Asc => " ASC",
+                Order::Desc => " DESC",
+            });
+        }
+        if let Some(limit) = self.limit {
+            query.push_str(" LIMIT ?");
+            values.push(CqlValue::Int(limit));
+        }
+        if self.allow_filtering {
+            query.push_str(" ALLOW FILTERING");
+        }
+        Ok((query, values))
+    }
+}
+
+///Builds an INSERT statement
+#[derive(Clone,Debug)]
+pub struct Insert {
+    table: TableName,
+    values: Vec<(String, CqlValue)>,
+    if_not_exists: bool,
+    using: Using,
+}
+
+///Starts an INSERT into the given table.
+pub fn insert<T: Into<TableName>>(table: T) -> Insert {
+    Insert {
+        table: table.into(),
+        values: vec![],
+        if_not_exists: false,
+        using: Using::default(),
+    }
+}
+
+impl Insert {
+    ///Sets the value of a column.
+    pub fn value<V: Into<CqlValue>>(mut self, column: &str, value: V) -> Self {
+        self.values.push((column.to_owned(), value.into()));
+        self
+    }
+
+    ///Inserts only if no row with the same primary key exists.
+    pub fn if_not_exists(mut self) -> Self {
+        self.if_not_exists = true;
+        self
+    }
+
+    ///Expires the inserted values after the given number of seconds.
+    pub fn using_ttl(mut self, ttl: i32) -> Self {
+        self.using.ttl = Some(ttl);
+        self
+    }
+
+    ///Sets the write timestamp, in microseconds.
+    pub fn using_timestamp(mut self, timestamp: i64) -> Self {
+        self.using.timestamp = Some(timestamp);
+        self
+    }
+}
+
+impl ToStatement for Insert {
+    fn build(&self) -> Result<(String, Vec<CqlValue>), BuildError> {
+        if self.values.is_empty() {
+            return Err(BuildError::NoValues);
+        }
+        for &(ref column, ref value) in &self.values {
+            try!(self.table.check(column, Part::Whole, value));
+        }
+        let columns: Vec<String> = self.values.iter().map(|&(ref column, _)| column.clone()).collect();
+        let mut query = format!("INSERT INTO {} ({}) VALUES ({})",
+                                self.table,
+                                join_columns(&columns),
+                                vec!["?"; columns.len()].join(", "));
+        let mut values: Vec<CqlValue> = self.values.iter().map(|&(_, ref value)| value.clone()).collect();
+        if self.if_not_exists {
+            query.push_str(" IF NOT EXISTS");
+        }
+        self.using.render(&mut query, &mut values);
+        Ok((query, values))
+    }
+}
+
+///An assignment in the SET clause of an UPDATE
+#[derive(Clone,Debug)]
+enum Assignment {
+    Set(String, CqlValue),
+    Append(String, CqlValue),
+    Prepend(String, CqlValue),
+    Remove(String, CqlValue),
+    Put(String, CqlValue, CqlValue),
+}
+
+impl Assignment {
+    fn check(&self, table: &TableName) -> Result<(), BuildError> {
+        match *self {
+            Assignment::Set(ref column, ref value) |
+            Assignment::Append(ref column, ref value) |
+            Assignment::Prepend(ref column, ref value) => table.check(column, Part::Whole, value),
+            Assignment::Remove(ref column, ref value) => table.check(column, Part::Removal, value),
+            Assignment::Put(ref column, ref key, ref value) => {
+                try!(table.check(column, Part::Key, key));
+                table.check(column, Part::Value, value)
+            }
+        }
+    }
+}
+
+///Builds an UPDATE statement
+#[derive(Clone,Debug)]
+pub struct Update {
+    table: TableName,
+    assignments: Vec<Assignment>,
+    relations: Vec<Relation>,
+    condition: Condition,
+    using: Using,
+}
+
+///Starts an UPDATE of the given table.
+pub fn update<T: Into<TableName>>(table: T) -> Update {
+    Update {
+        table: table.into(),
+        assignments: vec![],
+        relations: vec![],
+        condition: Condition::None,
+        using: Using::default(),
+    }
+}
+
+impl Update {
+    ///Assigns a value to a column: `column = ?`.
+    pub fn set<V: Into<CqlValue>>(mut self, column: &str, value: V) -> Self {
+        self.assignments.push(Assignment::Set(column.to_owned(), value.into()));
+        self
+    }
+
+    ///Adds elements to a set or map, or appends them to a list:
+    ///`column = column + ?`.
+    pub fn add<V: Into<CqlValue>>(mut self, column: &str, value: V) -> Self {
+        self.assignments.push(Assignment::Append(column.to_owned(), value.into()));
+        self
+    }
+
+    ///Prepends elements to a list: `column = ? + column`.
+    pub fn prepend<V: Into<CqlValue>>(mut self, column: &str, value: V) -> Self {
+        self.assignments.push(Assignment::Prepend(column.to_owned(), value.into()));
+        self
+    }
+
+    ///Removes elements from a set or list, or keys from a map:
+    ///`column = column - ?`.
+    pub fn remove<V: Into<CqlValue>>(mut self, column: &str, value: V) -> Self {
+        self.assignments.push(Assignment::Remove(column.to_owned(), value.into()));
+        self
+    }
+
+    ///Sets a single map entry: `column[?] = ?`.
+    pub fn put<K: Into<CqlValue>, V: Into<CqlValue>>(mut self, column: &str, key: K, value: V) -> Self {
+        self.assignments.push(Assignment::Put(column.to_owned(), key.into(), value.into()));
+        self
+    }
+
+    ///Increments a counter column.
+    pub fn increment(mut self, column: &str, by: i64) -> Self {
+        self.assignments.push(Assignment::Append(column.to_owned(), CqlValue::Bigint(by)));
+        self
+    }
+
+    ///Decrements a counter column.
+    pub fn decrement(mut self, column: &str, by: i64) -> Self {
+        self.assignments.push(Assignment::Remove(column.to_owned(), CqlValue::Bigint(by)));
+        self
+    }
+
+    where_clauses!();
+    if_conditions!();
+
+    ///Expires the updated values after the given number of seconds.
+    pub fn using_ttl(mut self, ttl: i32) -> Self {
+        self.using.ttl = Some(ttl);
+        self
+    }
+
+    ///Sets the write timestamp, in microseconds.
+    pub fn using_timestamp(mut self, timestamp: i64) -> Self {
+        self.using.timestamp = Some(timestamp);
+        self
+    }
+}
+
+impl ToStatement for Update {
+    fn build(&self) -> Result<(String, Vec<CqlValue>), BuildError> {
+        if self.assignments.is_empty() {
+            return Err(BuildError::NoAssignments);
+        }
+        if self.relations.is_empty() {
+            return Err(BuildError::NoWhereClause);
+        }
+        for assignment in &self.assignments {
+            try!(assignment.check(&self.table));
+        }
+        try!(check_relations(&self.table, &self.relations));
+        try!(self.condition.check(&self.table));
+        let mut query = format!("UPDATE {}", self.table);
+        let mut values = vec![];
+        self.using.render(&mut query, &mut values);
+        for (index, assignment) in self.assignments.iter().enumerate() {
+            query.push_str(if index == 0 { " SET " } else { ", " });
+            match *assignment {
+                Assignment::Set(ref column, ref value) => {
+                    write!(query, "{} = ?", escape_identifier(column)).unwrap();
+                    values.push(value.clone());
+                }
+                Assignment::Append(ref column, ref value) => {
+                    let column = escape_identifier(column);
+                    write!(query, "{} = {} + ?", column, column).unwrap();
+                    values.push(value.clone());
+                }
+                Assignment::Prepend(ref column, ref value) => {
+                    let column = escape_identifier(column);
+                    write!(query, "{} = ? + {}", column, column).unwrap();
+                    values.push(value.clone());
+                }
+                Assignment::Remove(ref column, ref value) => {
+                    let column = escape_identifier(column);
+                    write!(query, "{} = {} - ?", column, column).unwrap();
+                    values.push(value.clone());
+                }
+                Assignment::Put(ref column, ref key, ref value) => {
+                    write!(query, "{}[?] = ?", escape_identifier(column)).unwrap();
+                    values.push(key.clone());
+                    values.push(value.clone());
+                }
+            }
+        }
+        render_relations(" WHERE ", &self.relations, &mut query, &mut values);
+        self.condition.render(&mut query, &mut values);
+        Ok((query, values))
+    }
+}
+
+///Builds a DELETE statement
+#[derive(Clone,Debug)]
+pub struct Delete {
+    table: TableName,
+    columns: Vec<String>,
+    relations: Vec<Relation>,
+    condition: Condition,
+    timestamp: Option<i64>,
+}
+
+///Starts a DELETE from the given table.
+pub fn delete<T: Into<TableName>>(table: T) -> Delete {
+    Delete {
+        table: table.into(),
+        columns: vec![],
+        relations: vec![],
+        condition: Condition::None,
+        timestamp: None,
+    }
+}
+
+impl Delete {
+    ///Deletes only the given columns instead of the whole row.
+    pub fn columns(mut self, columns: &[&str]) -> Self {
+        self.columns.extend(columns.iter().map(|column| (*column).to_owned()));
+        self
+    }
+
+    where_clauses!();
+    if_conditions!();
+
+    ///Sets the write timestamp, in microseconds.
+    pub fn using_timestamp(mut self, timestamp: i64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+}
+
+impl ToStatement for Delete {
+    fn build(&self) -> Result<(String, Vec<CqlValue>), BuildError> {
+        if self.relations.is_empty() {
+            return Err(BuildError::NoWhereClause);
+        }
+        try!(check_relations(&self.table, &self.relations));
+        try!(self.condition.check(&self.table));
+        let mut query = String::from("DELETE ");
+        let mut values = vec![];
+        if !self.columns.is_empty() {
+            query.push_str(&join_columns(&self.columns));
+            query.push(' ');
+        }
+        write!(query, "FROM {}", self.table).unwrap();
+        Using {
+                ttl: None,
+                timestamp: self.timestamp,
+            }
+            .render(&mut query, &mut values);
+        render_relations(" WHERE ", &self.relations, &mut query, &mut values);
+        self.condition.render(&mut query, &mut values);
+        Ok((query, values))
+    }
+}
+
+#[test]
+fn test_query_builder_markers() {
+    let (query, values) = select(("ks", "Users"))
+                              .columns(&["id", "order"])
+                              .where_eq("id", 5)
+                              .where_in("Group", vec!["a", "b"])
+                              .order_by("created", Order::Desc)
+                              .limit(10)
+                              .build()
+                              .unwrap();
+    assert_eq!(query,
+               "SELECT id, \"order\" FROM ks.\"Users\" WHERE id = ? AND \"Group\" IN (?, ?) ORDER BY created \
+                DESC LIMIT ?");
+    assert_eq!(values.len(), 4);
+
+    let (query, values) = update("counters")
+                              .using_ttl(60)
+                              .increment("hits", 1)
+                              .put("tags", "k", "v")
+                              .where_eq("id", 1)
+                              .if_eq("owner", "me")
+                              .build()
+                              .unwrap();
+    assert_eq!(query,
+               "UPDATE counters USING TTL ? SET hits = hits + ?, tags[?] = ? WHERE id = ? IF owner = ?");
+    assert_eq!(values.len(), 6);
+
+    let (query, _) = insert("users")
+                         .value("id", 1)
+                         .value("name", "x")
+                         .if_not_exists()
+                         .using_timestamp(7)
+                         .build()
+                         .unwrap();
+    assert_eq!(query,
+               "INSERT INTO users (id, name) VALUES (?, ?) IF NOT EXISTS USING TIMESTAMP ?");
+
+    let (query, values) = select("events.log")
+                              .where_token(&["id"], Operator::Gt, 0i64)
+                              .where_token(&["id"], Operator::Lte, 10i64)
+                              .where_contains("tags", "x")
+                              .build()
+                              .unwrap();
+    assert_eq!(query,
+               "SELECT * FROM \"events.log\" WHERE token(id) > ? AND token(id) <= ? AND tags CONTAINS ?");
+    assert_eq!(values.len(), 3);
+
+    match insert("users").build() {
+        Err(BuildError::NoValues) => {}
+        other => panic!("{:?}", other),
+    }
+    match update("users").where_eq("id", 1).build() {
+        Err(BuildError::NoAssignments) => {}
+        other => panic!("{:?}", other),
+    }
+    match update("users").set("name", "x").build() {
+        Err(BuildError::NoWhereClause) => {}
+        other => panic!("{:?}", other),
+    }
+    match delete(("ks", "users")).build() {
+        Err(BuildError::NoWhereClause) => {}
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn test_query_builder_column_types() {
+    let users = TableName::from(("ks", "users")).with_column_types(vec![("id".to_owned(), CqlType::Bigint),
+                                                                     ("name".to_owned(), CqlType::Varchar),
+                                                                     ("tags".to_owned(),
+                                                                      CqlType::Set(Box::new(CqlType::Text))),
+                                                                     ("scores".to_owned(),
+                                                                      CqlType::Map(Box::new(CqlType::Text),
+                                                                                   Box::new(CqlType::Int)))]);
+    match select(users.clone()).where_eq("id", 5).build() {
+        Err(BuildError::WrongType(ref column, CqlType::Bigint)) if column == "id" => {}
+        other => panic!("{:?}", other),
+    }
+    match select(users.clone()).where_eq("age", 5).build() {
+        Err(BuildError::UnknownColumn(ref column)) if column == "age" => {}
+        other => panic!("{:?}", other),
+    }
+    select(users.clone()).where_eq("id", 5i64).where_contains("tags", "a").build().unwrap();
+    insert(users.clone()).value("id", 1i64).value("name", "x").build().unwrap();
+    update(users.clone())
+        .put("scores", "a", 1)
+        .remove("scores", CqlValue::Set(vec![CqlValue::from("b")]))
+        .where_in("id", vec![1i64, 2])
+        .build()
+        .unwrap();
+    match update(users.clone()).put("scores", "a", 1i64).where_eq("id", 1i64).build() {
+        Err(BuildError::WrongType(ref column, _)) if column == "scores" => {}
+        other => panic!("{:?}", other),
+    }
+    match delete(users).where_eq("id", 1i64).if_eq("name", 1).build() {
+        Err(BuildError::WrongType(ref column, _)) if column == "name" => {}
+        other => panic!("{:?}", other),
+    }
+
+    let (query, _) = select("users").columns(&["count(*)", "writetime(name)", "Name"]).build().unwrap();
+    assert_eq!(query, "SELECT count(*), writetime(name), \"Name\" FROM users");
+}
@@ -7,16 +7,21 @@ use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fmt;
 use std::mem;
+use std::ptr;
 use std::slice;
 use std::str;
 use std::ffi::CString;
+use std::sync::Arc;
 
+use cassandra_sys::CASS_ERROR_LIB_NO_PAGING_STATE;
+use cassandra_sys::CASS_VALUE_TYPE_UNKNOWN;
 use cassandra_sys::cass_true;
 use cassandra_sys::cass_false;
 
 use cassandra::value::ValueType;
 use cassandra::data_type::ConstDataType;
-use cassandra::row::Row;
+use cassandra::row::{Row, query_row};
+use cassandra::codec::CodecRegistry;
 use cassandra::error::CassError;
 
 use cassandra_sys::CassResult as _CassResult;
@@ -40,7 +45,7 @@ use cassandra::util::Protected;
 ///The result of a query.
 ///A result object is read-only and is thread-safe to read or iterate over
 ///concurrently.
-pub struct CassResult(*const _CassResult);
+pub struct CassResult(*const _CassResult, Option<u64>, Option<Arc<CodecRegistry>>);
 unsafe impl Sync for CassResult {}
 unsafe impl Send for CassResult {}
 
@@ -49,10 +54,30 @@ impl Protected<*const _CassResult> for CassResult {
         self.0
     }
     fn build(inner: *const _CassResult) -> Self {
-        CassResult(inner)
+        CassResult(inner, None, None)
     }
 }
 
+///Wraps the result of a statement with the given fingerprint, decoding its
+///rows with the codecs of the session that executed it.
+pub fn query_result(inner: *const _CassResult,
+                    fingerprint: Option<u64>,
+                    codecs: Option<Arc<CodecRegistry>>)
+                    -> CassResult {
+    CassResult(inner, fingerprint, codecs)
+}
+
+///A result without columns or rows, standing for a failed execution whose
+///error the session's `RetryStrategy` ignored.
+pub fn empty_result(fingerprint: Option<u64>, codecs: Option<Arc<CodecRegistry>>) -> CassResult {
+    CassResult(ptr::null(), fingerprint, codecs)
+}
+
+///The fingerprint of the statement a result was produced by, if known.
+pub fn result_fingerprint(result: &CassResult) -> Option<u64> {
+    result.1
+}
+
 impl Debug for CassResult {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         try!(write!(f, "Result row count: {:?}\n", self.row_count()));
@@ -75,27 +100,39 @@ impl Display for CassResult {
 
 impl Drop for CassResult {
     fn drop(&mut self) {
-        unsafe { cass_result_free(self.0) }
+        if !self.0.is_null() {
+            unsafe { cass_result_free(self.0) }
+        }
     }
 }
 
 impl CassResult {
     ///Gets the number of rows for the specified result.
     pub fn row_count(&self) -> u64 {
+        if self.0.is_null() {
+            return 0;
+        }
         unsafe { cass_result_row_count(self.0) as u64 }
     }
 
     ///Gets the number of columns per row for the specified result.
     pub fn column_count(&self) -> u64 {
+        if self.0.is_null() {
+            return 0;
+        }
         unsafe { cass_result_column_count(self.0) as u64 }
     }
 
-    ///Gets the column name at index for the specified result.
+    ///Gets the column name at index for the specified result, or an empty
+    ///name if there is no such column.
     pub fn column_name(&self, index: u64) -> String {
+        if index >= self.column_count() {
+            return String::new();
+        }
         unsafe {
-            let name = mem::zeroed();
-            let name_length = mem::zeroed();
-            cass_result_column_name(self.0, index, name, name_length);
+            let mut name = mem::zeroed();
+            let mut name_length = mem::zeroed();
+            cass_result_column_name(self.0, index, &mut name, &mut name_length);
             let slice = slice::from_raw_parts(name as *const u8, name_length as usize);
             str::from_utf8(slice).expect("must be utf8").to_owned()
         }
@@ -103,11 +140,17 @@ impl CassResult {
 
     ///Gets the column type at index for the specified result.
     pub fn column_type(&self, index: u64) -> ValueType {
+        if self.0.is_null() {
+            return ValueType::build(CASS_VALUE_TYPE_UNKNOWN);
+        }
         unsafe { ValueType::build(cass_result_column_type(self.0, index)) }
     }
 
     ///Gets the column datatype at index for the specified result.
     pub fn column_data_type(&self, index: u64) -> ConstDataType {
+        if self.0.is_null() {
+            return ConstDataType(ptr::null());
+        }
         unsafe { ConstDataType(cass_result_column_data_type(self.0, index)) }
     }
 
@@ -116,14 +159,14 @@ impl CassResult {
         unsafe {
             match self.row_count() {
                 0 => None,
-                _ => Some(Row::build(cass_result_first_row(self.0))),
+                _ => Some(query_row(cass_result_first_row(self.0), self.2.clone())),
             }
         }
     }
 
     ///Returns true if there are more pages.
     pub fn has_more_pages(&self) -> bool {
-        unsafe { cass_result_has_more_pages(self.0) == cass_true }
+        !self.0.is_null() && unsafe { cass_result_has_more_pages(self.0) == cass_true }
     }
 
     ///Sets the statement's paging state. This can be used to get the next page of
@@ -133,6 +176,9 @@ impl CassResult {
     ///untrusted environments. The paging state could be spoofed and potentially
     // used to gain access to other data.
     pub fn set_paging_state_token(&mut self, paging_state: &str) -> Result<&Self, CassError> {
+        if self.0.is_null() {
+            return Err(CassError::build(CASS_ERROR_LIB_NO_PAGING_STATE));
+        }
         unsafe {
             let state = CString::new(paging_state).expect("must be utf8");
 
@@ -147,22 +193,30 @@ impl CassResult {
     ///Creates a new iterator for the specified result. This can be
     ///used to iterate over rows in the result.
     pub fn iter(&self) -> ResultIterator {
-        unsafe { ResultIterator(cass_iterator_from_result(self.0)) }
+        if self.0.is_null() {
+            return ResultIterator(ptr::null_mut(), self.2.clone());
+        }
+        unsafe { ResultIterator(cass_iterator_from_result(self.0), self.2.clone()) }
     }
 }
 
 ///An iterator over the results of a query
-pub struct ResultIterator(pub *mut _CassIterator);
+pub struct ResultIterator(pub *mut _CassIterator, Option<Arc<CodecRegistry>>);
 
 impl Drop for ResultIterator {
     fn drop(&mut self) {
-        unsafe { cass_iterator_free(self.0) }
+        if !self.0.is_null() {
+            unsafe { cass_iterator_free(self.0) }
+        }
     }
 }
 
 impl Iterator for ResultIterator {
     type Item = Row;
     fn next(&mut self) -> Option<<Self as Iterator>::Item> {
+        if self.0.is_null() {
+            return None;
+        }
         unsafe {
             match cass_iterator_next(self.0) {
                 cass_false => None,
@@ -175,7 +229,7 @@ impl Iterator for ResultIterator {
 impl ResultIterator {
     ///Gets the next row in the result set
     pub fn get_row(&mut self) -> Row {
-        unsafe { Row::build(cass_iterator_get_row(self.0)) }
+        unsafe { query_row(cass_iterator_get_row(self.0), self.1.clone()) }
     }
 }
 
@@ -19,21 +19,30 @@ use std::iter;
 use cassandra_sys::cass_true;
 use cassandra_sys::cass_false;
 
+use std::sync::Arc;
+
 use cassandra::error::CassError;
+use cassandra::codec::{CodecRegistry, default_registry};
 use cassandra::column::Column;
 
 ///A collection of column values.
-pub struct Row(*const _Row);
+pub struct Row(*const _Row, Option<Arc<CodecRegistry>>);
 
 impl Protected<*const _Row> for Row {
     fn inner(&self) -> *const _Row {
         self.0
     }
     fn build(inner: *const _Row) -> Self {
-        Row(inner)
+        Row(inner, None)
     }
 }
 
+///Wraps a row of a result, decoding its values with the codecs of the
+///session that produced it.
+pub fn query_row(inner: *const _Row, codecs: Option<Arc<CodecRegistry>>) -> Row {
+    Row(inner, codecs)
+}
+
 impl Debug for Row {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         for column in self {
@@ -141,6 +150,15 @@ impl AsRustType<i32> for Row {
 }
 
 impl Row {
+    ///The codecs this row's values are decoded with: those of the session
+    ///that produced it, or the built-in ones.
+    pub fn codec_registry(&self) -> &CodecRegistry {
+        match self.1 {
+            Some(ref codecs) => codecs,
+            None => default_registry(),
+        }
+    }
+
     ///Get a particular column by index
     pub fn get_column(&self, index: u64) -> Result<Column, CassError> {
         unsafe {
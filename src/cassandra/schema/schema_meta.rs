@@ -1,3 +1,5 @@
+use std::ffi::CString;
+
 use cassandra_sys::CassSchemaMeta as _CassSchemaMeta;
 use cassandra_sys::cass_schema_meta_free;
 use cassandra_sys::cass_schema_meta_keyspace_by_name;
@@ -36,7 +38,18 @@ impl SchemaMeta {
 
     ///Gets the keyspace metadata for the provided keyspace name.
     pub fn get_keyspace_by_name(&self, keyspace: &str) -> KeyspaceMeta {
-        unsafe { KeyspaceMeta::build(cass_schema_meta_keyspace_by_name(self.0, keyspace.as_ptr() as *const i8)) }
+        let keyspace = CString::new(keyspace).expect("must be utf8");
+        unsafe { KeyspaceMeta::build(cass_schema_meta_keyspace_by_name(self.0, keyspace.as_ptr())) }
+    }
+
+    ///Gets the keyspace metadata for the provided keyspace name, if the
+    ///keyspace exists.
+    pub fn keyspace_by_name(&self, keyspace: &str) -> Option<KeyspaceMeta> {
+        let keyspace = CString::new(keyspace).expect("must be utf8");
+        unsafe {
+            let meta = cass_schema_meta_keyspace_by_name(self.0, keyspace.as_ptr());
+            if meta.is_null() { None } else { Some(KeyspaceMeta::build(meta)) }
+        }
     }
 
     ///Returns an iterator over the keyspaces in this schema
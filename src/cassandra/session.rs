@@ -3,18 +3,35 @@
 #![allow(missing_copy_implementations)]
 
 use std::mem;
+use std::fmt;
+use std::fmt::{Debug, Formatter};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use std::ffi::CString;
 use std::ffi::NulError;
-use cassandra::batch::Batch;
-use cassandra::future::{CloseFuture, Future, PreparedFuture, ResultFuture, SessionFuture};
+use cassandra::batch::{Batch, batch_has_consistency};
+use cassandra::execution::Execution;
+use cassandra::future::{CloseFuture, Future, PreparedFuture, ResultFuture, SessionFuture, failed_future,
+                        prepared_future, result_future, tracked_future};
 use cassandra::error::CassError;
-use cassandra::statement::Statement;
+use cassandra::statement::{Statement, fingerprint, is_read, prepared_query, statement_consistency,
+                           statement_query, statement_snapshot};
+use cassandra::consistency::{Consistency, raw_consistency};
+use cassandra::policy::retry_strategy::{Retrier, RetryStrategy};
 use cassandra::schema::schema_meta::SchemaMeta;
-use cassandra::metrics::SessionMetrics;
+use cassandra::metrics::{SessionMetrics, record_request, session_metrics};
 use cassandra::cluster::Cluster;
+use cassandra::codec::CodecRegistry;
+use cassandra::prepared::{PreparedStatement, query_table, used_keyspace};
+use cassandra::prepared_cache::{PreparedCache, PreparedCacheStats};
+use cassandra::result::CassResult;
+use cassandra::shutdown::{Requests, ShutdownReport, ShuttingDown};
 use cassandra::util::Protected;
 
+use cassandra_sys::CASS_ERROR_LIB_NO_HOSTS_AVAILABLE;
+use cassandra_sys::CASS_ERROR_LIB_UNABLE_TO_CLOSE;
 use cassandra_sys::CassSession as _Session;
 use cassandra_sys::cass_session_free;
 use cassandra_sys::cass_session_close;
@@ -25,6 +42,10 @@ use cassandra_sys::cass_session_execute_batch;
 use cassandra_sys::cass_session_get_schema_meta;
 use cassandra_sys::cass_session_connect_keyspace;
 use cassandra_sys::cass_session_get_metrics;
+use cassandra_sys::cass_statement_set_consistency;
+use cassandra_sys::cass_batch_set_consistency;
+
+use time::Duration;
 
 ///A session object is used to execute queries and maintains cluster state through
 ///the control connection. The control connection is used to auto-discover nodes and
@@ -32,8 +53,7 @@ use cassandra_sys::cass_session_get_metrics;
 /// /pools of connections to cluster nodes which are used to query the cluster.
 ///
 ///Instances of the session object are thread-safe to execute queries.
-#[derive(Debug)]
-pub struct Session(pub *mut _Session);
+pub struct Session(pub *mut _Session, SessionState);
 unsafe impl Sync for Session {}
 unsafe impl Send for Session {}
 
@@ -42,11 +62,84 @@ impl Protected<*mut _Session> for Session {
         self.0
     }
     fn build(inner: *mut _Session) -> Self {
-        Session(inner)
+        new_session(inner, SessionOptions::default())
+    }
+}
+
+impl Debug for Session {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Session({:?})", self.0)
+    }
+}
+
+///Settings kept on the Rust side of a cluster and handed to each session it
+///connects.
+#[derive(Clone,Debug)]
+pub struct SessionOptions {
+    ///Codecs for custom Rust types
+    pub codecs: Arc<CodecRegistry>,
+    ///Maximum number of statements kept by `Session::prepared`
+    pub prepared_cache_capacity: usize,
+    ///Keyspace the session connects to
+    pub keyspace: Option<String>,
+    ///Consistency of `SELECT` statements that do not set their own
+    pub read_consistency: Option<Consistency>,
+    ///Consistency of other statements and batches that do not set their own
+    pub write_consistency: Option<Consistency>,
+    ///Decides whether failed statements are executed again
+    pub retry_strategy: Option<Arc<RetryStrategy>>,
+}
+
+impl Default for SessionOptions {
+    fn default() -> SessionOptions {
+        SessionOptions {
+            codecs: Arc::new(CodecRegistry::new()),
+            prepared_cache_capacity: 1000,
+            keyspace: None,
+            read_consistency: None,
+            write_consistency: None,
+            retry_strategy: None,
+        }
+    }
+}
+
+///What a session keeps on the Rust side of the driver session
+struct SessionState {
+    handle: Arc<SessionHandle>,
+    options: SessionOptions,
+    prepared_cache: Arc<PreparedCache>,
+    requests: Arc<Requests>,
+    partition_keys: Mutex<HashMap<(String, String), Arc<Vec<String>>>>,
+}
+
+///Wraps a driver session with the options of the cluster it was created from.
+pub fn new_session(inner: *mut _Session, options: SessionOptions) -> Session {
+    let prepared_cache = PreparedCache::new(options.prepared_cache_capacity);
+    prepared_cache.set_keyspace(options.keyspace.clone());
+    Session(inner,
+            SessionState {
+                handle: Arc::new(SessionHandle(inner)),
+                prepared_cache: Arc::new(prepared_cache),
+                options: options,
+                requests: Requests::new(),
+                partition_keys: Mutex::new(HashMap::new()),
+            })
+}
+
+///Owns a driver session, which is freed once the `Session` and any
+///requests it is retrying are gone.
+pub struct SessionHandle(*mut _Session);
+unsafe impl Sync for SessionHandle {}
+unsafe impl Send for SessionHandle {}
+
+impl SessionHandle {
+    ///The driver session
+    pub fn inner(&self) -> *mut _Session {
+        self.0
     }
 }
 
-impl Drop for Session {
+impl Drop for SessionHandle {
     /// Frees a session instance. If the session is still connected it will be synchronously
     /// closed before being deallocated.
     fn drop(&mut self) {
@@ -74,6 +167,7 @@ impl Session {
 
     ///Connects a session and sets the keyspace.
     pub fn connect_keyspace(&self, cluster: &Cluster, keyspace: &str) -> Result<Future, NulError> {
+        self.1.prepared_cache.set_keyspace(Some(keyspace.to_owned()));
         unsafe {
             Ok(Future::build(cass_session_connect_keyspace(self.0,
                                                            cluster.inner(),
@@ -88,11 +182,109 @@ impl Session {
         unsafe { CloseFuture::build(cass_session_close(self.0)) }
     }
 
+    ///Shuts the session down gracefully.
+    ///
+    ///New executions are refused from then on: `try_execute` returns
+    ///`ShuttingDown` and `execute` a failed future. Requests already in
+    ///flight are given until `deadline` to finish, then the session is
+    ///closed; the report counts those that had not finished by then.
+    ///
+    ///Fails with `CASS_ERROR_LIB_UNABLE_TO_CLOSE` if the session is already
+    ///shutting down.
+    pub fn shutdown(&self, deadline: Duration) -> Result<ShutdownReport, CassError> {
+        let deadline = Instant::now() + deadline.to_std().unwrap_or(::std::time::Duration::from_millis(0));
+        let report = try!(self.1.requests
+                              .drain(deadline)
+                              .map_err(|_| CassError::build(CASS_ERROR_LIB_UNABLE_TO_CLOSE)));
+        if report.abandoned > 0 {
+            warn!("closing the session with {} requests in flight", report.abandoned);
+        }
+        try!(unsafe { Future::build(cass_session_close(self.0)) }.wait());
+        Ok(report)
+    }
+
+    ///Whether `shutdown` has been called
+    pub fn is_shutting_down(&self) -> bool {
+        self.1.requests.is_shutting_down()
+    }
+
+    ///The number of executions that have not completed yet
+    pub fn in_flight(&self) -> usize {
+        self.1.requests.in_flight()
+    }
+
     ///Create a prepared statement.
     pub fn prepare(&self, query: &str) -> Result<PreparedFuture, CassError> {
+        let partition_key = self.partition_key_of(query);
         unsafe {
-            Ok(PreparedFuture::build(cass_session_prepare(self.0, CString::new(query).expect("must be utf8").as_ptr())))
+            Ok(prepared_future(cass_session_prepare(self.0, CString::new(query).expect("must be utf8").as_ptr()),
+                               query,
+                               self.1.prepared_cache.keyspace(),
+                               partition_key,
+                               self.1.options.codecs.clone()))
+        }
+    }
+
+    ///The partition key columns of the table a query uses.
+    ///
+    ///They are read from the schema metadata the first time a table is
+    ///prepared against and remembered from then on, as the primary key of a
+    ///table cannot be altered. Tables that are not found are looked up again
+    ///on the next prepare.
+    fn partition_key_of(&self, query: &str) -> Arc<Vec<String>> {
+        let (keyspace, table) = match query_table(query) {
+            Some(table) => table,
+            None => return Arc::new(vec![]),
+        };
+        let keyspace = match keyspace.or_else(|| self.1.prepared_cache.keyspace()) {
+            Some(keyspace) => keyspace,
+            None => return Arc::new(vec![]),
+        };
+        let key = (keyspace, table);
+        if let Some(columns) = self.1.partition_keys.lock().expect("partition keys lock").get(&key) {
+            return columns.clone();
         }
+        let schema = self.get_schema_meta();
+        let columns: Vec<String> = match schema.keyspace_by_name(&key.0).and_then(|meta| meta.table_by_name(&key.1)) {
+            Some(table_meta) => {
+                (0..table_meta.partition_key_count())
+                    .filter_map(|index| table_meta.partition_key(index))
+                    .map(|column| column.name())
+                    .collect()
+            }
+            None => return Arc::new(vec![]),
+        };
+        let columns = Arc::new(columns);
+        self.1.partition_keys.lock().expect("partition keys lock").insert(key, columns.clone());
+        columns
+    }
+
+    ///Gets a prepared statement for the query, preparing it on first use.
+    ///
+    ///Statements are cached per query text and the session's current
+    ///keyspace, which follows `USE` statements executed on the session, with
+    ///the least recently used ones evicted once the cache is full. Concurrent calls for
+    ///a query that is being prepared wait for that prepare instead of
+    ///issuing their own.
+    pub fn prepared(&self, query: &str) -> Result<Arc<PreparedStatement>, CassError> {
+        self.1.prepared_cache.get_or_prepare(query, || try!(self.prepare(query)).wait())
+    }
+
+    ///Binds and executes a cached prepared statement, waiting for the result.
+    ///
+    ///As with any bound statement, if the node no longer knows the statement
+    ///it is prepared again and the execution retried once.
+    pub fn execute_prepared<F>(&self, query: &str, bind: F) -> Result<CassResult, CassError>
+        where F: Fn(&mut Statement) -> Result<(), CassError> {
+        let prepared = try!(self.prepared(query));
+        let mut statement = prepared.bind();
+        try!(bind(&mut statement));
+        self.execute(&statement).wait()
+    }
+
+    ///Hit and miss counts of the prepared statement cache.
+    pub fn prepared_cache_stats(&self) -> PreparedCacheStats {
+        self.1.prepared_cache.stats()
     }
 
     //    ///Execute a query or bound statement.
@@ -104,13 +296,97 @@ impl Session {
     //    }
 
     /// Execute a batch statement.
+    ///
+    ///Batches are not retried by the session's `RetryStrategy`. If the
+    ///session is shutting down, the future fails with
+    ///`CASS_ERROR_LIB_NO_HOSTS_AVAILABLE`, as for a closed session; see
+    ///`try_execute_batch`.
     pub fn execute_batch(&self, batch: Batch) -> ResultFuture {
-        ResultFuture::build(unsafe { cass_session_execute_batch(self.0, batch.inner()) })
+        self.try_execute_batch(batch).unwrap_or_else(|_| self.refused())
+    }
+
+    ///Executes a batch statement, unless the session is shutting down.
+    pub fn try_execute_batch(&self, batch: Batch) -> Result<ResultFuture, ShuttingDown> {
+        let guard = try!(Requests::start(&self.1.requests));
+        if let (false, Some(consistency)) = (batch_has_consistency(&batch), self.1.options.write_consistency) {
+            unsafe {
+                cass_batch_set_consistency(batch.inner(), raw_consistency(consistency));
+            }
+        }
+        let future = ResultFuture::build(unsafe { cass_session_execute_batch(self.0, batch.inner()) });
+        Ok(tracked_future(future, guard))
     }
 
     /// Execute a statement.
+    ///
+    ///If the session is shutting down, the future fails with
+    ///`CASS_ERROR_LIB_NO_HOSTS_AVAILABLE`, as for a closed session; see
+    ///`try_execute`.
     pub fn execute(&self, statement: &Statement) -> ResultFuture {
-        unsafe { ResultFuture::build(cass_session_execute(self.0, statement.inner())) }
+        self.try_execute(statement).unwrap_or_else(|_| self.refused())
+    }
+
+    ///The future of an execution refused because the session is shutting
+    ///down
+    fn refused(&self) -> ResultFuture {
+        record_request(Some(&CassError::build(CASS_ERROR_LIB_NO_HOSTS_AVAILABLE)), false);
+        failed_future(CASS_ERROR_LIB_NO_HOSTS_AVAILABLE, self.1.options.codecs.clone())
+    }
+
+    ///Executes a statement, unless the session is shutting down.
+    ///
+    ///A statement that may be retried, because the session has a retry
+    ///strategy or the statement was bound from a prepared statement, is
+    ///copied first and every attempt sends the copy, so the statement may be
+    ///changed as soon as this returns. A statement that cannot be copied
+    ///(see `bind_user_type`) is sent as it is and not retried.
+    pub fn try_execute(&self, statement: &Statement) -> Result<ResultFuture, ShuttingDown> {
+        let guard = try!(Requests::start(&self.1.requests));
+        let default = if is_read(statement) {
+            self.1.options.read_consistency
+        } else {
+            self.1.options.write_consistency
+        };
+        let consistency = match (statement_consistency(statement), default) {
+            (None, Some(consistency)) => {
+                unsafe {
+                    cass_statement_set_consistency(statement.inner(), raw_consistency(consistency));
+                }
+                Some(consistency)
+            }
+            (consistency, _) => consistency,
+        };
+        let prepared_query = prepared_query(statement).map(ToOwned::to_owned);
+        let mut retrier = if self.1.options.retry_strategy.is_some() || prepared_query.is_some() {
+            // retries send a copy, as the caller may change the statement
+            // while the request is still going on
+            match statement_snapshot(statement) {
+                Some(copy) => {
+                    Some(Retrier::new(self.1.handle.clone(),
+                                      copy,
+                                      self.1.options.retry_strategy.clone(),
+                                      prepared_query,
+                                      consistency,
+                                      statement.is_idempotent() || is_read(statement)))
+                }
+                None => {
+                    debug!("statement cannot be copied, so it is not retried");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let future = match retrier {
+            Some(ref mut retrier) => retrier.send(),
+            None => unsafe { cass_session_execute(self.0, statement.inner()) },
+        };
+        let keyspace = statement_query(statement)
+                           .and_then(used_keyspace)
+                           .map(|keyspace| (self.1.prepared_cache.clone(), keyspace));
+        let execution = Execution::start(future, retrier, keyspace);
+        let future = result_future(execution, fingerprint(statement), self.1.options.codecs.clone());
+        Ok(tracked_future(future, guard))
     }
 
     ///Gets a snapshot of this session's schema metadata. The returned
@@ -126,8 +402,24 @@ impl Session {
         unsafe {
             let mut metrics = mem::zeroed();
             cass_session_get_metrics(self.0, &mut metrics);
-            SessionMetrics::build(&metrics)
+            session_metrics(&metrics)
+        }
+    }
+
+    ///The codecs used to convert custom Rust types for this session.
+    pub fn codec_registry(&self) -> &CodecRegistry {
+        &self.1.options.codecs
+    }
+
+    ///Creates a query statement that binds custom Rust types with this
+    ///session's codecs, in the session's current keyspace.
+    pub fn statement(&self, query: &str, parameter_count: u64) -> Statement {
+        let mut statement = Statement::new(query, parameter_count);
+        statement.set_codec_registry(self.1.options.codecs.clone());
+        if let Some(keyspace) = self.1.prepared_cache.keyspace() {
+            statement.set_keyspace(keyspace).expect("keyspace names are utf8");
         }
+        statement
     }
 
     //    pub fn get_schema(&self) -> Schema {
@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use time::Duration;
+
+use cassandra::cluster::{Cluster, PendingSession, start_connect};
+use cassandra::error::CassError;
+use cassandra::session::Session;
+use cassandra::statement::Statement;
+use cassandra::util::Protected;
+
+use cassandra_sys::CassError as _CassError;
+use cassandra_sys::CASS_ERROR_LIB_INTERNAL_ERROR;
+
+///The health of a `SessionManager`
+#[derive(Clone,Debug,Default)]
+pub struct Health {
+    ///Whether the session on the cluster's keyspace is connected and
+    ///answered the last check
+    pub ready: bool,
+    ///The number of connected sessions
+    pub sessions: usize,
+    ///Failed checks and connection attempts since the last success
+    pub consecutive_failures: u32,
+    ///The last failure, until the next success
+    pub last_error: Option<String>,
+    ///When the sessions were last checked
+    pub last_check: Option<Instant>,
+}
+
+///Owns a `Cluster` and shares one session per keyspace between threads,
+///connecting on first use.
+///
+///Failed connection attempts are retried with exponential backoff. Each
+///keyspace is connected by one thread at a time; others asking for it
+///meanwhile wait for that thread's result. A health check runs a
+///lightweight query on every session, dropping those that fail so that
+///they are connected again; its result is the readiness reported to
+///orchestration probes. `spawn_health_checks` runs it periodically.
+///
+/// # Examples
+/// ```no_run
+/// use std::sync::Arc;
+/// use cassandra::{Cluster, ContactPoints, SessionManager};
+///
+/// let mut cluster = Cluster::new();
+/// cluster.set_contact_points("127.0.0.1".parse::<ContactPoints>().unwrap()).unwrap();
+/// let manager = Arc::new(SessionManager::new(cluster));
+/// let session = manager.session().unwrap();
+/// assert!(manager.is_ready());
+/// ```
+pub struct SessionManager {
+    cluster: Mutex<Cluster>,
+    sessions: Mutex<HashMap<Option<String>, Slot>>,
+    health: Mutex<Health>,
+    health_query: String,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    max_attempts: u32,
+}
+
+///A keyspace's session, or the connection attempts that will give it
+#[derive(Clone)]
+enum Slot {
+    Connected(Arc<Session>),
+    Connecting(Arc<Connecting>),
+}
+
+///The outcome of a thread's attempts to connect a keyspace, shared with
+///the threads waiting for it
+#[derive(Default)]
+struct Connecting {
+    result: Mutex<Option<Result<Arc<Session>, _CassError>>>,
+    done: Condvar,
+}
+
+impl Connecting {
+    fn wait(&self) -> Result<Arc<Session>, CassError> {
+        let mut result = self.result.lock().expect("poisoned connection");
+        loop {
+            match *result {
+                Some(Ok(ref session)) => return Ok(session.clone()),
+                Some(Err(code)) => return Err(CassError::build(code)),
+                None => result = self.done.wait(result).expect("poisoned connection"),
+            }
+        }
+    }
+
+    fn finish(&self, result: &Result<Arc<Session>, CassError>) {
+        let result = match *result {
+            Ok(ref session) => Ok(session.clone()),
+            Err(ref err) => Err(err.inner()),
+        };
+        *self.result.lock().expect("poisoned connection") = Some(result);
+        self.done.notify_all();
+    }
+}
+
+///Gives up a keyspace's connection attempts if the thread making them
+///panics, so that the threads waiting for them fail instead of waiting
+///forever and the next one to ask connects again
+struct ConnectGuard<'a> {
+    manager: &'a SessionManager,
+    key: Option<Option<String>>,
+    connecting: Arc<Connecting>,
+}
+
+impl<'a> Drop for ConnectGuard<'a> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            if let Ok(mut sessions) = self.manager.sessions.lock() {
+                sessions.remove(&key);
+            }
+            self.connecting.finish(&Err(CassError::build(CASS_ERROR_LIB_INTERNAL_ERROR)));
+        }
+    }
+}
+
+impl SessionManager {
+    ///Manages the sessions of `cluster`
+    pub fn new(cluster: Cluster) -> SessionManager {
+        SessionManager {
+            cluster: Mutex::new(cluster),
+            sessions: Mutex::new(HashMap::new()),
+            health: Mutex::new(Health::default()),
+            health_query: "SELECT release_version FROM system.local".to_owned(),
+            initial_backoff: Duration::milliseconds(100),
+            max_backoff: Duration::seconds(10),
+            max_attempts: 5,
+        }
+    }
+
+    ///Sets the query run by health checks.
+    ///
+    ///<b>Default:</b> `SELECT release_version FROM system.local`
+    pub fn set_health_query(&mut self, query: &str) -> &mut Self {
+        self.health_query = query.to_owned();
+        self
+    }
+
+    ///Sets the delay after the first failed connection attempt, doubled
+    ///after each further one up to `max`.
+    ///
+    ///<b>Default:</b> 100ms, up to 10s
+    pub fn set_reconnect_backoff(&mut self, initial: Duration, max: Duration) -> &mut Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    ///Sets how many times connecting is attempted before failing.
+    ///
+    ///<b>Default:</b> 5
+    pub fn set_max_connect_attempts(&mut self, attempts: u32) -> &mut Self {
+        self.max_attempts = attempts;
+        self
+    }
+
+    ///The session on the cluster's keyspace, connecting it if needed
+    pub fn session(&self) -> Result<Arc<Session>, CassError> {
+        self.connect(None)
+    }
+
+    ///The session on `keyspace`, connecting it if needed
+    pub fn keyspace_session(&self, keyspace: &str) -> Result<Arc<Session>, CassError> {
+        self.connect(Some(keyspace))
+    }
+
+    fn connect(&self, keyspace: Option<&str>) -> Result<Arc<Session>, CassError> {
+        self.share(keyspace, || self.attempt(keyspace))
+    }
+
+    ///The keyspace's session, or else the one `attempt` connects, which the
+    ///threads asking for the keyspace meanwhile wait for. If `attempt`
+    ///panics, they fail with `CASS_ERROR_LIB_INTERNAL_ERROR`.
+    fn share<F>(&self, keyspace: Option<&str>, attempt: F) -> Result<Arc<Session>, CassError>
+        where F: FnOnce() -> Result<Arc<Session>, CassError> {
+        let key = keyspace.map(|keyspace| keyspace.to_owned());
+        let mut guard = {
+            let mut sessions = self.sessions.lock().expect("poisoned sessions");
+            match sessions.get(&key).cloned() {
+                Some(Slot::Connected(session)) => return Ok(session),
+                Some(Slot::Connecting(connecting)) => {
+                    drop(sessions);
+                    return connecting.wait();
+                }
+                None => {
+                    let connecting = Arc::new(Connecting::default());
+                    sessions.insert(key.clone(), Slot::Connecting(connecting.clone()));
+                    ConnectGuard {
+                        manager: self,
+                        key: Some(key),
+                        connecting: connecting,
+                    }
+                }
+            }
+        };
+        let result = attempt();
+        let key = guard.key.take().expect("connection in progress");
+        {
+            let mut sessions = self.sessions.lock().expect("poisoned sessions");
+            match result {
+                Ok(ref session) => sessions.insert(key, Slot::Connected(session.clone())),
+                Err(_) => sessions.remove(&key),
+            };
+        }
+        guard.connecting.finish(&result);
+        result
+    }
+
+    ///Connects to the keyspace, retrying with backoff
+    fn attempt(&self, keyspace: Option<&str>) -> Result<Arc<Session>, CassError> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            // the cluster is only locked to start connecting, so that a slow
+            // connection does not hold up those to other keyspaces
+            let pending = start_connect(&self.cluster.lock().expect("poisoned cluster"), keyspace);
+            let result = pending.and_then(PendingSession::wait);
+            match result {
+                Ok(session) => {
+                    if keyspace.is_none() {
+                        self.succeeded();
+                    }
+                    return Ok(Arc::new(session));
+                }
+                Err(err) => {
+                    self.failed(&err, keyspace.is_none());
+                    if attempt >= self.max_attempts {
+                        return Err(err);
+                    }
+                    let delay = self.backoff(attempt);
+                    warn!("connecting to {} failed: {}; retrying in {}ms",
+                          keyspace.unwrap_or("the cluster"),
+                          err,
+                          delay.num_milliseconds());
+                    thread::sleep(delay.to_std().unwrap_or(::std::time::Duration::from_millis(0)));
+                }
+            }
+        }
+    }
+
+    ///The delay after a number of failed connection attempts
+    fn backoff(&self, attempt: u32) -> Duration {
+        let initial = self.initial_backoff.num_milliseconds().max(0) as u64;
+        let delay = initial.saturating_mul(1 << (attempt.max(1) - 1).min(32));
+        Duration::milliseconds(delay.min(self.max_backoff.num_milliseconds().max(0) as u64) as i64)
+    }
+
+    fn succeeded(&self) {
+        let mut health = self.health.lock().expect("poisoned health");
+        health.ready = true;
+        health.consecutive_failures = 0;
+        health.last_error = None;
+    }
+
+    fn failed(&self, err: &CassError, default_keyspace: bool) {
+        let mut health = self.health.lock().expect("poisoned health");
+        if default_keyspace {
+            health.ready = false;
+        }
+        health.consecutive_failures += 1;
+        health.last_error = Some(err.to_string());
+    }
+
+    ///Runs the health query on every session, drops the sessions it fails
+    ///on and connects the cluster's keyspace again if needed.
+    pub fn check_health(&self) -> Health {
+        let sessions: Vec<(Option<String>, Arc<Session>)> = {
+            let sessions = self.sessions.lock().expect("poisoned sessions");
+            sessions.iter()
+                    .filter_map(|(key, slot)| {
+                        match *slot {
+                            Slot::Connected(ref session) => Some((key.clone(), session.clone())),
+                            Slot::Connecting(_) => None,
+                        }
+                    })
+                    .collect()
+        };
+        let mut failures = vec![];
+        for (keyspace, session) in sessions {
+            let result = session.execute(&Statement::new(&self.health_query, 0)).wait();
+            if result.is_ok() && keyspace.is_none() {
+                self.succeeded();
+            }
+            if let Err(err) = result {
+                warn!("health check of {} failed: {}", keyspace.as_ref().map_or("the cluster", |ks| &ks[..]), err);
+                let mut sessions = self.sessions.lock().expect("poisoned sessions");
+                let checked = match sessions.get(&keyspace) {
+                    Some(&Slot::Connected(ref current)) => Arc::ptr_eq(current, &session),
+                    _ => false,
+                };
+                if checked {
+                    sessions.remove(&keyspace);
+                }
+                failures.push((keyspace, err));
+            }
+        }
+        // reconnects, or fails readiness
+        let _ = self.session();
+        for (keyspace, err) in failures {
+            if keyspace.is_some() {
+                self.failed(&err, false);
+            }
+        }
+        self.health.lock().expect("poisoned health").last_check = Some(Instant::now());
+        self.health()
+    }
+
+    ///The health as of the last check or connection attempt
+    pub fn health(&self) -> Health {
+        let mut health = self.health.lock().expect("poisoned health").clone();
+        health.sessions = self.sessions
+                              .lock()
+                              .expect("poisoned sessions")
+                              .values()
+                              .filter(|slot| {
+                                  match **slot {
+                                      Slot::Connected(_) => true,
+                                      Slot::Connecting(_) => false,
+                                  }
+                              })
+                              .count();
+        health
+    }
+
+    ///Whether the session on the cluster's keyspace is connected and
+    ///answered the last check
+    pub fn is_ready(&self) -> bool {
+        self.health.lock().expect("poisoned health").ready
+    }
+
+    ///Checks the manager's health every `interval` on a new thread, which
+    ///ends once the manager is dropped.
+    pub fn spawn_health_checks(manager: &Arc<SessionManager>, interval: Duration) -> thread::JoinHandle<()> {
+        let manager = Arc::downgrade(manager);
+        let interval = interval.to_std().unwrap_or(::std::time::Duration::from_secs(1));
+        thread::spawn(move || {
+            loop {
+                thread::sleep(interval);
+                match manager.upgrade() {
+                    Some(manager) => {
+                        manager.check_health();
+                    }
+                    None => return,
+                }
+            }
+        })
+    }
+}
+
+#[test]
+fn test_session_manager_reconnect_backoff() {
+    use std::net::TcpListener;
+    use std::str::FromStr;
+    use cassandra::contact_points::ContactPoints;
+
+    // a port nothing listens on
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let mut cluster = Cluster::new();
+    cluster.set_contact_points(ContactPoints::from_str("127.0.0.1").unwrap()).unwrap();
+    cluster.set_port(port).unwrap();
+    let mut manager = SessionManager::new(cluster);
+    manager.set_reconnect_backoff(Duration::milliseconds(1), Duration::milliseconds(3)).set_max_connect_attempts(3);
+    assert_eq!(manager.backoff(1), Duration::milliseconds(1));
+    assert_eq!(manager.backoff(2), Duration::milliseconds(2));
+    assert_eq!(manager.backoff(3), Duration::milliseconds(3));
+    assert_eq!(manager.backoff(40), Duration::milliseconds(3));
+
+    assert!(manager.session().is_err());
+    let health = manager.health();
+    assert!(!health.ready);
+    assert_eq!(health.sessions, 0);
+    assert_eq!(health.consecutive_failures, 3);
+    assert!(health.last_error.is_some());
+
+    let health = manager.check_health();
+    assert!(!manager.is_ready());
+    assert_eq!(health.consecutive_failures, 6);
+    assert!(health.last_check.is_some());
+
+    // threads asking for a keyspace while it is being connected share the
+    // attempts
+    manager.set_reconnect_backoff(Duration::milliseconds(100), Duration::milliseconds(100));
+    let manager = Arc::new(manager);
+    let threads: Vec<_> = (0..2)
+                              .map(|_| {
+                                  let manager = manager.clone();
+                                  thread::spawn(move || manager.keyspace_session("ks").is_err())
+                              })
+                              .collect();
+    for thread in threads {
+        assert!(thread.join().unwrap());
+    }
+    assert_eq!(manager.health().consecutive_failures, 9);
+}
+
+#[test]
+fn test_session_manager_panicking_connect() {
+    use std::sync::mpsc;
+    use cassandra_sys::CASS_ERROR_LIB_UNABLE_TO_CONNECT;
+
+    let manager = Arc::new(SessionManager::new(Cluster::new()));
+    let (started, wait_started) = mpsc::channel();
+    let (release, wait_release) = mpsc::channel::<()>();
+    let connecting = {
+        let manager = manager.clone();
+        thread::spawn(move || {
+            manager.share(Some("ks"), || -> Result<Arc<Session>, CassError> {
+                started.send(()).unwrap();
+                wait_release.recv().unwrap();
+                panic!("connect failed")
+            })
+        })
+    };
+    wait_started.recv().unwrap();
+    let waiting = {
+        let manager = manager.clone();
+        thread::spawn(move || {
+            manager.share(Some("ks"), || -> Result<Arc<Session>, CassError> { panic!("not the first caller") })
+                   .map(|_| ())
+                   .map_err(|err| err.inner())
+        })
+    };
+    // the waiting thread holds the attempts too once it waits for them
+    let waiters = || {
+        match manager.sessions.lock().unwrap().get(&Some("ks".to_owned())) {
+            Some(&Slot::Connecting(ref connecting)) => Arc::strong_count(connecting),
+            _ => 0,
+        }
+    };
+    while waiters() < 3 {
+        thread::yield_now();
+    }
+    release.send(()).unwrap();
+    assert!(connecting.join().is_err());
+    assert_eq!(waiting.join().unwrap(), Err(CASS_ERROR_LIB_INTERNAL_ERROR));
+
+    // the keyspace is connected again by the next caller
+    assert!(manager.sessions.lock().unwrap().is_empty());
+    let refused = manager.share(Some("ks"), || Err(CassError::build(CASS_ERROR_LIB_UNABLE_TO_CONNECT)));
+    assert_eq!(refused.map(|_| ()).map_err(|err| err.inner()), Err(CASS_ERROR_LIB_UNABLE_TO_CONNECT));
+}
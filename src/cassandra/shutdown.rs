@@ -0,0 +1,156 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Instant;
+
+///Returned instead of a future by `Session::try_execute` once the session
+///has started shutting down
+#[derive(Clone,Copy,PartialEq,Eq)]
+pub struct ShuttingDown;
+
+impl Display for ShuttingDown {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "the session is shutting down")
+    }
+}
+
+impl Debug for ShuttingDown {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Error for ShuttingDown {
+    fn description(&self) -> &str {
+        "the session is shutting down"
+    }
+}
+
+///What happened to the requests in flight when `Session::shutdown` was
+///called
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct ShutdownReport {
+    ///Requests that finished before the deadline
+    pub completed: usize,
+    ///Requests still in flight at the deadline, left to the driver's close
+    pub abandoned: usize,
+}
+
+#[derive(Debug)]
+struct State {
+    in_flight: usize,
+    shutting_down: bool,
+}
+
+///Counts the requests of a session that are still in flight, and refuses
+///new ones once the session shuts down.
+///
+///A request is in flight from its execution until it completes.
+#[derive(Debug)]
+pub struct Requests {
+    state: Mutex<State>,
+    finished: Condvar,
+}
+
+impl Requests {
+    ///Tracks the requests of a new session
+    pub fn new() -> Arc<Requests> {
+        Arc::new(Requests {
+            state: Mutex::new(State {
+                in_flight: 0,
+                shutting_down: false,
+            }),
+            finished: Condvar::new(),
+        })
+    }
+
+    ///Counts a new request until the returned guard is dropped
+    pub fn start(requests: &Arc<Requests>) -> Result<RequestGuard, ShuttingDown> {
+        let mut state = requests.state.lock().expect("poisoned requests");
+        if state.shutting_down {
+            return Err(ShuttingDown);
+        }
+        state.in_flight += 1;
+        Ok(RequestGuard(requests.clone()))
+    }
+
+    ///The number of requests in flight
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().expect("poisoned requests").in_flight
+    }
+
+    ///Whether new requests are refused
+    pub fn is_shutting_down(&self) -> bool {
+        self.state.lock().expect("poisoned requests").shutting_down
+    }
+
+    ///Refuses new requests, then waits until those in flight finish or the
+    ///deadline passes.
+    ///
+    ///Fails if the session was already shutting down.
+    pub fn drain(&self, deadline: Instant) -> Result<ShutdownReport, ShuttingDown> {
+        let mut state = self.state.lock().expect("poisoned requests");
+        if state.shutting_down {
+            return Err(ShuttingDown);
+        }
+        state.shutting_down = true;
+        let started = state.in_flight;
+        while state.in_flight > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            state = self.finished.wait_timeout(state, deadline - now).expect("poisoned requests").0;
+        }
+        debug!("{} of {} requests in flight finished before the shutdown deadline",
+               started - state.in_flight,
+               started);
+        Ok(ShutdownReport {
+            completed: started - state.in_flight,
+            abandoned: state.in_flight,
+        })
+    }
+}
+
+///Keeps a request counted as in flight
+#[derive(Debug)]
+pub struct RequestGuard(Arc<Requests>);
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().expect("poisoned requests");
+        state.in_flight -= 1;
+        if state.in_flight == 0 {
+            self.0.finished.notify_all();
+        }
+    }
+}
+
+#[test]
+fn test_requests_drain() {
+    use std::thread;
+    use std::time::Duration;
+
+    let requests = Requests::new();
+    let quick = Requests::start(&requests).unwrap();
+    let stuck = Requests::start(&requests).unwrap();
+    assert_eq!(requests.in_flight(), 2);
+
+    let finisher = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        drop(quick);
+    });
+    let report = requests.drain(Instant::now() + Duration::from_millis(200)).unwrap();
+    finisher.join().unwrap();
+    assert_eq!(report,
+               ShutdownReport {
+                   completed: 1,
+                   abandoned: 1,
+               });
+    assert_eq!(Requests::start(&requests).unwrap_err(), ShuttingDown);
+    assert_eq!(requests.drain(Instant::now()).unwrap_err(), ShuttingDown);
+
+    drop(stuck);
+    assert_eq!(requests.in_flight(), 0);
+}
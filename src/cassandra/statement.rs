@@ -1,17 +1,29 @@
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::CString;
+use std::{mem, slice};
+use std::rc::Rc;
+use std::sync::Arc;
 // use decimal::d128;
 use cassandra::collection::Set;
 use cassandra::collection::Map;
 use cassandra::collection::List;
 use cassandra::error::CassError;
+use cassandra::partitioner::composite_key;
 use cassandra::uuid::Uuid;
 use cassandra::inet::Inet;
 use cassandra::result::CassResult;
-use cassandra::consistency::Consistency;
+use cassandra::consistency::{Consistency, raw_consistency};
 use cassandra::user_type::UserType;
 use cassandra::batch::CustomPayload;
 use cassandra::policy::retry::RetryPolicy;
 use cassandra::tuple::Tuple;
+use cassandra::codec::{CodecRegistry, default_registry};
+use cassandra::prepared::PreparedHandle;
+use cassandra_sys::CASS_OK;
+use cassandra_sys::CASS_ERROR_LIB_BAD_PARAMS;
+use cassandra_sys::CASS_ERROR_LIB_NAME_DOES_NOT_EXIST;
+use cassandra_sys::CassCollection as _CassCollection;
+use cassandra_sys::CassError as _CassError;
 use cassandra_sys::cass_true;
 use cassandra_sys::cass_false;
 use cassandra_sys::CassStatement as _Statement;
@@ -22,7 +34,6 @@ use cassandra_sys::cass_statement_set_keyspace;
 use cassandra_sys::cass_statement_set_consistency;
 use cassandra_sys::cass_statement_set_serial_consistency;
 use cassandra_sys::cass_statement_set_paging_size;
-use cassandra_sys::cass_statement_set_paging_state;
 use cassandra_sys::cass_statement_bind_null;
 use cassandra_sys::cass_statement_bind_null_by_name;
 use cassandra_sys::cass_statement_bind_uint32;
@@ -63,6 +74,9 @@ use cassandra_sys::cass_statement_set_custom_payload;
 use cassandra_sys::cass_statement_set_paging_state_token;
 use cassandra_sys::cass_statement_set_retry_policy;
 use cassandra_sys::cass_statement_set_timestamp;
+use cassandra_sys::cass_statement_set_tracing;
+use cassandra_sys::cass_prepared_bind;
+use cassandra_sys::cass_result_paging_state_token;
 use cassandra::util::Protected;
 ///A statement object is an executable query. It represents either a regular
 ///(adhoc) statement or a prepared statement. It maintains the queries' parameter
@@ -70,15 +84,341 @@ use cassandra::util::Protected;
 ///
 ///<b>Note:</b> Parameters for regular queries are not supported by the binary protocol
 ///version 1.
-pub struct Statement(*mut _Statement);
+///
+///Regular statements may use `:name` placeholders. They are rewritten to
+///positional markers when the statement is created, so the `bind_*_by_name`
+///functions work the same way as for prepared statements.
+pub struct Statement {
+    inner: *mut _Statement,
+    source: Option<Source>,
+    settings: Vec<(&'static str, Change)>,
+    values: BTreeMap<u64, Option<Change>>,
+    query: Option<String>,
+    prepared_query: Option<String>,
+    parameters: Option<HashMap<String, Vec<u64>>>,
+    keyspace: Option<String>,
+    bound: Vec<Option<BoundValue>>,
+    key_values: HashMap<u64, Option<Vec<u8>>>,
+    key_indices: Vec<u64>,
+    consistency: Option<Consistency>,
+    idempotent: bool,
+    codecs: Option<Arc<CodecRegistry>>,
+}
+
+///Where the statement's driver statement came from, to create another one
+enum Source {
+    Query(CString, u64),
+    Prepared(Arc<PreparedHandle>),
+}
+
+///Makes a setting or binds a value again on another driver statement
+type Change = Box<Fn(*mut _Statement) -> _CassError>;
+
+///Owns a copy of a driver statement, which a request sends again on retries.
+pub struct StatementHandle(*mut _Statement);
+unsafe impl Sync for StatementHandle {}
+unsafe impl Send for StatementHandle {}
+
+impl StatementHandle {
+    ///The driver statement
+    pub fn inner(&self) -> *mut _Statement {
+        self.0
+    }
+}
+
+impl Drop for StatementHandle {
+    fn drop(&mut self) {
+        unsafe { cass_statement_free(self.0) }
+    }
+}
+
+///A copy of the statement's driver statement, taken when it is executed,
+///with the same settings and values, so that a request can be sent again
+///whatever is done to the statement afterwards. The driver cannot copy a
+///statement, so one is created the way the statement was and every change
+///made to it is made again.
+///
+///`None` if the statement was not created by this crate, if a value was
+///bound by name on the driver's side or if a `UserType` was bound by
+///reference with `bind_user_type`, as the statement does not own it; bind
+///it with `bind_value` instead.
+pub fn statement_snapshot(statement: &Statement) -> Option<StatementHandle> {
+    let copy = match statement.source {
+        Some(Source::Query(ref query, parameter_count)) => unsafe {
+            StatementHandle(cass_statement_new(query.as_ptr(), parameter_count))
+        },
+        Some(Source::Prepared(ref prepared)) => unsafe { StatementHandle(cass_prepared_bind(prepared.inner())) },
+        None => return None,
+    };
+    for &(_, ref change) in &statement.settings {
+        if change(copy.0) != CASS_OK {
+            return None;
+        }
+    }
+    for value in statement.values.values() {
+        match *value {
+            Some(ref bind) if bind(copy.0) == CASS_OK => {}
+            _ => return None,
+        }
+    }
+    Some(copy)
+}
 
 impl Protected<*mut _Statement> for Statement {
     fn inner(&self) -> *mut _Statement {
-        self.0
+        self.inner
     }
     fn build(inner: *mut _Statement) -> Self {
-        Statement(inner)
+        bound_statement(inner, None, None, None, None, vec![])
+    }
+}
+
+impl Drop for Statement {
+    ///Frees a statement instance. Statements can be immediately freed after
+    ///being prepared, executed or added to a batch.
+    fn drop(&mut self) {
+        unsafe { cass_statement_free(self.inner) }
+    }
+}
+
+///Wraps a statement bound from a prepared statement, with the prepared
+///statement, the indices of its named parameters, the query it was
+///prepared from, the keyspace it was prepared in and the indices of its
+///partition key parameters.
+pub fn bound_statement(inner: *mut _Statement,
+                       prepared: Option<Arc<PreparedHandle>>,
+                       parameters: Option<HashMap<String, Vec<u64>>>,
+                       prepared_query: Option<String>,
+                       keyspace: Option<String>,
+                       key_indices: Vec<u64>)
+                       -> Statement {
+    Statement {
+        inner: inner,
+        source: prepared.map(Source::Prepared),
+        settings: vec![],
+        values: BTreeMap::new(),
+        query: None,
+        prepared_query: prepared_query,
+        parameters: parameters,
+        keyspace: keyspace,
+        bound: vec![],
+        key_values: HashMap::new(),
+        key_indices: key_indices,
+        consistency: None,
+        idempotent: false,
+        codecs: None,
+    }
+}
+
+///Identifies the query a statement runs, so that paging states can be
+///checked against it. Stable across processes and releases: a 64-bit
+///FNV-1a hash of the query text, which differs between regular and bound
+///statements, the statement's keyspace, if known, and the values bound to
+///it, so that the same query over different partitions or token ranges
+///gets a different fingerprint. Values bound through a `List`, `Set`,
+///`Map`, `Tuple`, `UserType` or `Inet` are opaque to the client and only
+///count as bound; bind them with `bind_value` to include them.
+///
+///`None` for statements bound from a prepared statement whose query is not
+///known: the driver does not expose the prepared id that would identify
+///them.
+pub fn fingerprint(statement: &Statement) -> Option<u64> {
+    let (kind, query) = match (&statement.query, &statement.prepared_query) {
+        (&Some(ref query), _) => (b'Q', &query[..]),
+        (&None, &Some(ref query)) => (b'P', &query[..]),
+        (&None, &None) => return None,
+    };
+    let mut hash = Fnv::new();
+    hash.write(&[kind]);
+    hash.write_str(query);
+    match statement.keyspace {
+        Some(ref keyspace) => {
+            hash.write(&[1]);
+            hash.write_str(keyspace);
+        }
+        None => hash.write(&[0]),
+    }
+    for value in &statement.bound {
+        match *value {
+            Some(ref value) => hash.write_u64(value.hash),
+            None => hash.write(&[0]),
+        }
+    }
+    Some(hash.finish())
+}
+
+///A 64-bit FNV-1a hash
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Fnv {
+        Fnv(0xcbf29ce484222325)
     }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    ///Writes the length of the string before it, so that consecutive
+    ///strings cannot run into each other
+    fn write_str(&mut self, text: &str) {
+        self.write_u64(text.len() as u64);
+        self.write(text.as_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        for shift in (0..8).rev() {
+            self.write(&[(value >> (shift * 8)) as u8]);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+///The query of a statement bound from a prepared statement, if known.
+pub fn prepared_query(statement: &Statement) -> Option<&str> {
+    statement.prepared_query.as_ref().map(|query| &query[..])
+}
+
+///The query a statement runs, if known.
+pub fn statement_query(statement: &Statement) -> Option<&str> {
+    statement.query.as_ref().or(statement.prepared_query.as_ref()).map(|query| &query[..])
+}
+
+///The statement's consistency level, if it was set explicitly rather than
+///left to the session default.
+pub fn statement_consistency(statement: &Statement) -> Option<Consistency> {
+    statement.consistency
+}
+
+///Whether the statement reads rather than writes: its query is a
+///`SELECT`. Statements whose query is unknown count as writes.
+pub fn is_read(statement: &Statement) -> bool {
+    statement_query(statement).and_then(|query| query.split_whitespace().next())
+         .map_or(false, |keyword| keyword.to_lowercase() == "select")
+}
+
+///The marker indices bound to a named parameter, if the names of the
+///statement's parameters are known on the client side.
+///
+///As in CQL, names are case-insensitive unless enclosed in double quotes.
+pub fn named_indices(statement: &Statement, name: &str) -> Option<Vec<u64>> {
+    let key = if name.len() >= 2 && name.starts_with('"') && name.ends_with('"') {
+        name[1..name.len() - 1].replace("\"\"", "\"")
+    } else {
+        name.to_lowercase()
+    };
+    statement.parameters.as_ref().and_then(|names| names.get(&key)).cloned()
+}
+
+///A value as the client sees it when binding it
+pub enum Bound<'a> {
+    ///A null
+    Null,
+    ///The last `width` bytes of the array
+    Fixed([u8; 16], usize),
+    ///A value in serialized form
+    Bytes(&'a [u8]),
+    ///A value bound through an opaque driver object
+    Opaque,
+}
+
+///The size and hash of the serialized form of a bound value
+#[derive(Clone,Copy)]
+struct BoundValue {
+    size: usize,
+    hash: u64,
+}
+
+impl<'a> Bound<'a> {
+    fn fixed(value: u64, width: usize) -> Bound<'a> {
+        let mut bytes = [0u8; 16];
+        for i in 0..width {
+            bytes[16 - width + i] = (value >> (8 * (width - 1 - i))) as u8;
+        }
+        Bound::Fixed(bytes, width)
+    }
+
+    ///The serialized form, unless the value is null or opaque
+    fn bytes(&self) -> Option<&[u8]> {
+        match *self {
+            Bound::Null | Bound::Opaque => None,
+            Bound::Fixed(ref bytes, width) => Some(&bytes[16 - width..]),
+            Bound::Bytes(bytes) => Some(bytes),
+        }
+    }
+
+    fn hash(&self) -> u64 {
+        let mut hash = Fnv::new();
+        match *self {
+            Bound::Null => hash.write(&[1]),
+            Bound::Opaque => hash.write(&[2]),
+            _ => {
+                let bytes = self.bytes().unwrap_or(&[]);
+                hash.write(&[3]);
+                hash.write_u64(bytes.len() as u64);
+                hash.write(bytes);
+            }
+        }
+        hash.finish()
+    }
+}
+
+///Records the size and hash of a value bound at `index`, and the value
+///itself if `index` is a key index.
+pub fn record_value(statement: &mut Statement, index: u64, value: Bound) {
+    let slot = index as usize;
+    if statement.bound.len() <= slot {
+        statement.bound.resize(slot + 1, None);
+    }
+    let bytes = value.bytes();
+    statement.bound[slot] = Some(BoundValue {
+        size: bytes.map_or(0, |bytes| bytes.len()),
+        hash: value.hash(),
+    });
+    if statement.key_indices.contains(&index) {
+        statement.key_values.insert(index, bytes.map(|bytes| bytes.to_vec()));
+    }
+}
+
+///Sets the statement's paging state from its token, as a result or a saved
+///`PagingState` gives it.
+pub fn set_paging_token(statement: &mut Statement, token: &[u8]) -> Result<(), CassError> {
+    let token = token.to_vec();
+    statement.change("paging_state", move |inner| unsafe {
+        cass_statement_set_paging_state_token(inner, token.as_ptr() as *const i8, token.len() as u64)
+    })
+}
+
+///Binds a user defined type the statement takes, so that copies of it bind
+///the value again, unlike `Statement::bind_user_type`.
+pub fn bind_owned_user_type(statement: &mut Statement,
+                            index: u64,
+                            value: UserType)
+                            -> Result<&mut Statement, CassError> {
+    let value = Rc::new(value);
+    statement.bind_with(index,
+                        Bound::Opaque,
+                        move |inner| unsafe { cass_statement_bind_user_type(inner, index, value.inner()) })
+}
+
+///The serialized values bound at the statement's key indices, in order.
+pub fn key_values(statement: &Statement) -> Option<Vec<&[u8]>> {
+    if statement.key_indices.is_empty() {
+        return None;
+    }
+    statement.key_indices
+             .iter()
+             .map(|index| match statement.key_values.get(index) {
+                 Some(&Some(ref bytes)) => Some(&bytes[..]),
+                 _ => None,
+             })
+             .collect()
 }
 
 #[macro_export]
@@ -87,8 +427,7 @@ macro_rules! stmt {
         {
             $(
         	let query = $x;
-        	let param_count = query.matches("?").count();
-        	let statement = Statement::new(query, param_count as u64);
+        	let statement = Statement::new(query, Statement::parameter_count(query));
             )*
             statement
         }
@@ -104,14 +443,6 @@ macro_rules! stmt {
 // 	i as i64 * 100);
 
 
-impl Drop for Statement {
-    ///Frees a statement instance. Statements can be immediately freed after
-    ///being prepared, executed or added to a batch.
-    fn drop(&mut self) {
-        unsafe { self.free() }
-    }
-}
-
 ///All Rust types that can be bound to a cassandra statement
 /// //FIXME not yet implemented
 /// pub enum CassBindable {
@@ -216,17 +547,239 @@ impl BindRustType<Map> for Statement {
 }
 
 
+///Rewrites `:name` and `:"Name"` placeholders to positional `?` markers,
+///leaving string literals, quoted identifiers and comments untouched.
+///Returns the rewritten query, the number of markers and the marker indices
+///of each name: lower-cased unless quoted, as CQL does.
+fn rewrite_named_parameters(query: &str) -> (String, u64, HashMap<String, Vec<u64>>) {
+    let chars: Vec<char> = query.chars().collect();
+    let mut rewritten = String::with_capacity(query.len());
+    let mut names: HashMap<String, Vec<u64>> = HashMap::new();
+    let mut markers = 0;
+    let mut i = 0;
+
+    fn is_identifier(c: char) -> bool {
+        c.is_alphanumeric() || c == '_'
+    }
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).cloned();
+        let end = match (c, next) {
+            ('\'', _) | ('"', _) => {
+                // quotes are escaped by doubling them, which this handles as two adjacent literals
+                let mut end = i + 1;
+                while end < chars.len() && chars[end] != c {
+                    end += 1;
+                }
+                end + 1
+            }
+            ('$', Some('$')) => {
+                let mut end = i + 2;
+                while end + 1 < chars.len() && !(chars[end] == '$' && chars[end + 1] == '$') {
+                    end += 1;
+                }
+                end + 2
+            }
+            ('-', Some('-')) | ('/', Some('/')) => {
+                let mut end = i + 2;
+                while end < chars.len() && chars[end] != '\n' {
+                    end += 1;
+                }
+                end
+            }
+            ('/', Some('*')) => {
+                let mut end = i + 2;
+                while end + 1 < chars.len() && !(chars[end] == '*' && chars[end + 1] == '/') {
+                    end += 1;
+                }
+                end + 2
+            }
+            ('?', _) => {
+                markers += 1;
+                i + 1
+            }
+            (':', Some('"')) if !(i > 0 && (is_identifier(chars[i - 1]) || chars[i - 1] == '\'')) => {
+                let mut name = String::new();
+                let mut end = i + 2;
+                let mut closed = false;
+                while end < chars.len() {
+                    if chars[end] == '"' {
+                        if chars.get(end + 1) == Some(&'"') {
+                            end += 1;
+                        } else {
+                            closed = true;
+                            break;
+                        }
+                    }
+                    name.push(chars[end]);
+                    end += 1;
+                }
+                if !closed || name.is_empty() {
+                    i + 1
+                } else {
+                    names.entry(name).or_insert_with(Vec::new).push(markers);
+                    markers += 1;
+                    rewritten.push('?');
+                    i = end + 1;
+                    continue;
+                }
+            }
+            (':', Some(n)) if (n.is_alphabetic() || n == '_') &&
+                              !(i > 0 && (is_identifier(chars[i - 1]) || chars[i - 1] == '\'')) => {
+                // a colon right after a name or literal separates a map or UDT literal entry
+                let mut end = i + 1;
+                while end < chars.len() && is_identifier(chars[end]) {
+                    end += 1;
+                }
+                let name: String = chars[i + 1..end].iter().collect();
+                names.entry(name.to_lowercase()).or_insert_with(Vec::new).push(markers);
+                markers += 1;
+                rewritten.push('?');
+                i = end;
+                continue;
+            }
+            _ => i + 1,
+        };
+        let end = if end > chars.len() { chars.len() } else { end };
+        rewritten.extend(&chars[i..end]);
+        i = end;
+    }
+    (rewritten, markers, names)
+}
+
+///The query to send for a statement and its parameter names, once any
+///named placeholders have been rewritten. Fails if the query uses named
+///placeholders and `parameter_count` is not its number of markers.
+fn parse_query(query: &str, parameter_count: u64) -> Result<(String, Option<HashMap<String, Vec<u64>>>), CassError> {
+    let (rewritten, markers, names) = rewrite_named_parameters(query);
+    if names.is_empty() {
+        Ok((query.to_owned(), None))
+    } else if markers != parameter_count {
+        debug!("{} has {} parameters, not {}", query, markers, parameter_count);
+        Err(CassError::build(CASS_ERROR_LIB_BAD_PARAMS))
+    } else {
+        Ok((rewritten, Some(names)))
+    }
+}
+
 impl Statement {
     ///Creates a new query statement.
+    ///
+    ///If the query uses `:name` or `:"Name"` placeholders, they are
+    ///replaced with positional markers. If `parameter_count` is not the
+    ///number of markers, the names are not known and the query is sent as
+    ///is; use `try_new` to check the count.
     pub fn new(query: &str, parameter_count: u64) -> Self {
-        unsafe {
-            Statement(cass_statement_new(CString::new(query).expect("must be utf8").as_ptr(),
-                                         parameter_count))
+        let parameters = parse_query(query, parameter_count).ok();
+        let (query, parameters) = match parameters {
+            Some((query, parameters)) => (query, parameters),
+            None => (query.to_owned(), None),
+        };
+        Statement::build_new(query, parameters, parameter_count)
+    }
+
+    ///Creates a new query statement like `new`, failing with
+    ///`CASS_ERROR_LIB_BAD_PARAMS` if the query uses named placeholders and
+    ///`parameter_count` is not the number of markers in it.
+    pub fn try_new(query: &str, parameter_count: u64) -> Result<Self, CassError> {
+        let (query, parameters) = try!(parse_query(query, parameter_count));
+        Ok(Statement::build_new(query, parameters, parameter_count))
+    }
+
+    ///The number of `?` markers and named placeholders in a query, outside
+    ///of string literals, quoted identifiers and comments.
+    pub fn parameter_count(query: &str) -> u64 {
+        rewrite_named_parameters(query).1
+    }
+
+    fn build_new(query: String, parameters: Option<HashMap<String, Vec<u64>>>, parameter_count: u64) -> Self {
+        let text = CString::new(&query[..]).expect("must be utf8");
+        let inner = unsafe { cass_statement_new(text.as_ptr(), parameter_count) };
+        let mut statement = bound_statement(inner, None, parameters, None, None, vec![]);
+        statement.source = Some(Source::Query(text, parameter_count));
+        statement.query = Some(query);
+        statement
+    }
+
+    ///Makes a setting on the driver statement, kept to make it again on
+    ///copies. A setting replaces the one of the same name.
+    fn change<F>(&mut self, setting: &'static str, change: F) -> Result<(), CassError>
+        where F: Fn(*mut _Statement) -> _CassError + 'static {
+        try!(CassError::build(change(self.inner)).wrap(()));
+        self.settings.retain(|&(name, _)| name != setting);
+        self.settings.push((setting, Box::new(change)));
+        Ok(())
+    }
+
+    ///Binds a value on the driver statement, kept to bind it again on
+    ///copies, and records it once the driver has accepted it
+    fn bind_with<F>(&mut self, index: u64, value: Bound, bind: F) -> Result<&mut Self, CassError>
+        where F: Fn(*mut _Statement) -> _CassError + 'static {
+        try!(CassError::build(bind(self.inner)).wrap(()));
+        record_value(self, index, value);
+        self.values.insert(index, Some(Box::new(bind)));
+        Ok(self)
+    }
+
+    ///Records a value bound by name on the driver's side, which cannot be
+    ///bound again on copies
+    fn bound_by_name(&mut self, result: _CassError) -> Result<&mut Self, CassError> {
+        try!(CassError::build(result).wrap(()));
+        self.source = None;
+        Ok(self)
+    }
+
+    ///Sets the codecs custom Rust types are bound with. Statements created by
+    ///`Session::statement` or bound from a prepared statement use those of
+    ///their session.
+    pub fn set_codec_registry(&mut self, codecs: Arc<CodecRegistry>) -> &mut Self {
+        self.codecs = Some(codecs);
+        self
+    }
+
+    ///The codecs custom Rust types are bound with: those of the session the
+    ///statement came from, or the built-in ones.
+    pub fn codec_registry(&self) -> &CodecRegistry {
+        match self.codecs {
+            Some(ref codecs) => codecs,
+            None => default_registry(),
         }
     }
 
-    unsafe fn free(&mut self) {
-        cass_statement_free(self.0)
+    ///The query text of a regular statement, after any `:name` placeholders
+    ///have been rewritten. Bound statements created from a prepared
+    ///statement have no query text.
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_ref().map(|query| &query[..])
+    }
+
+    ///An estimate of the number of bytes this statement adds to a request:
+    ///the query text (or prepared id) and every bound value.
+    ///
+    ///Values bound through a `List`, `Set`, `Map`, `Tuple`, `UserType` or
+    ///`Inet` are opaque to the client and only their length header is
+    ///counted; bind them with `bind_value` for an exact size.
+    pub fn estimated_size(&self) -> usize {
+        let query = match self.query {
+            Some(ref query) => 4 + query.len(),
+            None => 2 + 16,
+        };
+        let values: usize = self.bound.iter().filter_map(|value| value.map(|value| 4 + value.size)).sum();
+        1 + query + 2 + values
+    }
+
+    ///Binds a value at every index of a client-side named parameter
+    fn bind_named<F>(&mut self, name: &str, mut bind: F) -> Result<&mut Self, CassError>
+        where F: FnMut(&mut Statement, u64) -> Result<&mut Statement, CassError> {
+        let indices = match named_indices(self, name) {
+            Some(indices) => indices,
+            None => return Err(CassError::build(CASS_ERROR_LIB_NAME_DOES_NOT_EXIST)),
+        };
+        for index in indices {
+            try!(bind(self, index));
+        }
+        Ok(self)
     }
 
     //    ///Binds an arbitrary CassBindable type to a cassandra statement
@@ -245,8 +798,26 @@ impl Statement {
     ///
     ///This is not necessary for prepared statements, as the key
     ///parameters are determined in the metadata processed in the prepare phase.
+    ///
+    ///Only values bound after their index was added are kept for
+    ///`routing_key`, so add the key indices before binding.
     pub fn add_key_index(&mut self, index: u64) -> Result<&Self, CassError> {
-        unsafe { CassError::build(cass_statement_add_key_index(self.0, index)).wrap(self) }
+        let add = move |inner: *mut _Statement| unsafe { cass_statement_add_key_index(inner, index) };
+        try!(CassError::build(add(self.inner)).wrap(()));
+        // every key index is kept, unlike the settings a later one replaces
+        self.settings.push(("key_index", Box::new(add)));
+        self.key_indices.push(index);
+        Ok(self)
+    }
+
+    ///The routing key of the statement: the serialized values bound at its
+    ///key indices, in the composite key format if there are several. `None`
+    ///if the key indices are not known or not all of them are bound.
+    ///
+    ///Statements bound from a prepared statement take their key indices from
+    ///`PreparedStatement::partition_key_indices`.
+    pub fn routing_key(&self) -> Option<Vec<u8>> {
+        key_values(self).map(|values| composite_key(&values))
     }
 
     ///Sets the statement's keyspace for use with token-aware routing.
@@ -254,43 +825,74 @@ impl Statement {
     ///This is not necessary for prepared statements, as the keyspace
     ///is determined in the metadata processed in the prepare phase.
     pub fn set_keyspace(&mut self, keyspace: String) -> Result<&Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_set_keyspace(self.0,
-                                                         (CString::new(keyspace).expect("must be utf8").as_ptr())))
-                .wrap(self)
-        }
+        let name = CString::new(&keyspace[..]).expect("must be utf8");
+        try!(self.change("keyspace", move |inner| unsafe { cass_statement_set_keyspace(inner, name.as_ptr()) }));
+        self.keyspace = Some(keyspace);
+        Ok(self)
     }
 
     ///Sets the statement's consistency level.
     ///
-    ///<b>Default:</b> CASS_CONSISTENCY_LOCAL_ONE
+    ///<b>Default:</b> the session's read or write consistency (see
+    ///`Cluster::set_read_consistency`), or `LOCAL_ONE`
     pub fn set_consistency(&mut self, consistency: Consistency) -> Result<&Self, CassError> {
-        unsafe { CassError::build(cass_statement_set_consistency(self.0, consistency.inner())).wrap(self) }
+        let raw = raw_consistency(consistency);
+        try!(self.change("consistency", move |inner| unsafe { cass_statement_set_consistency(inner, raw) }));
+        self.consistency = Some(consistency);
+        Ok(self)
     }
 
     /// Sets the statement's serial consistency level.
     ///
     ///<b>Default:</b> Not set
     pub fn set_serial_consistency(&mut self, serial_consistency: Consistency) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_set_serial_consistency(self.0, serial_consistency.inner())).wrap(self)
-        }
+        let raw = raw_consistency(serial_consistency);
+        try!(self.change("serial_consistency",
+                         move |inner| unsafe { cass_statement_set_serial_consistency(inner, raw) }));
+        Ok(self)
+    }
+
+    ///Marks the statement as idempotent: executing it more than once has
+    ///the same effect as executing it once, so it may be retried after a
+    ///failure that leaves its outcome unknown, such as a write timeout.
+    ///`SELECT` statements are always treated as idempotent.
+    ///
+    ///<b>Default:</b> false
+    pub fn set_idempotent(&mut self, idempotent: bool) -> &mut Self {
+        self.idempotent = idempotent;
+        self
+    }
+
+    ///Whether the statement was marked as idempotent
+    pub fn is_idempotent(&self) -> bool {
+        self.idempotent
     }
 
     ///Sets the statement's page size.
     ///
     ///<b>Default:</b> -1 (Disabled)
     pub fn set_paging_size(&mut self, page_size: i32) -> Result<&mut Self, CassError> {
-        unsafe { CassError::build(cass_statement_set_paging_size(self.0, page_size)).wrap(self) }
+        try!(self.change("paging_size", move |inner| unsafe { cass_statement_set_paging_size(inner, page_size) }));
+        Ok(self)
     }
 
     /// Sets the statement's paging state. This can be used to get the next page of
     ///data in a multi-page query.
     pub fn set_paging_state(&mut self, result: CassResult) -> Result<&mut Self, CassError> {
-        unsafe {
-            try!(CassError::build(cass_statement_set_paging_state(self.0, result.inner())).wrap(()));
-            Ok(self)
-        }
+        // the state is kept as its token, as copies cannot refer to the result
+        let token = if result.has_more_pages() {
+            unsafe {
+                let mut token = mem::zeroed();
+                let mut token_length = mem::zeroed();
+                try!(CassError::build(cass_result_paging_state_token(result.inner(), &mut token, &mut token_length))
+                         .wrap(()));
+                slice::from_raw_parts(token as *const u8, token_length as usize).to_vec()
+            }
+        } else {
+            vec![]
+        };
+        try!(set_paging_token(self, &token));
+        Ok(self)
     }
 
     ///Sets the statement's paging state. This can be used to get the next page of
@@ -300,265 +902,309 @@ impl Statement {
     ///untrusted environments. The paging state could be spoofed and potentially
     ///used to gain access to other data.
     pub fn set_paging_state_token(&mut self, paging_state: &str) -> Result<&Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_set_paging_state_token(self.0,
-                                                                   paging_state.as_ptr() as *const i8,
-                                                                   paging_state.len() as u64))
-                .wrap(self)
-        }
+        try!(set_paging_token(self, paging_state.as_bytes()));
+        Ok(self)
+    }
+
+    ///Enables or disables tracing of this statement. The id of the trace
+    ///is available from the result future once the request completes.
+    ///
+    ///<b>Default:</b> false
+    pub fn set_tracing(&mut self, enabled: bool) -> Result<&mut Self, CassError> {
+        let enabled = if enabled { cass_true } else { cass_false };
+        try!(self.change("tracing", move |inner| unsafe { cass_statement_set_tracing(inner, enabled) }));
+        Ok(self)
     }
 
     ///Sets the statement's timestamp.
     pub fn set_timestamp(&mut self, timestamp: i64) -> Result<&mut Self, CassError> {
-        unsafe {
-            try!(CassError::build(cass_statement_set_timestamp(self.0, timestamp)).wrap(()));
-            Ok(self)
-        }
+        try!(self.change("timestamp", move |inner| unsafe { cass_statement_set_timestamp(inner, timestamp) }));
+        Ok(self)
     }
 
     /// Sets the statement's retry policy.
     pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) -> Result<&Self, CassError> {
-        unsafe { CassError::build(cass_statement_set_retry_policy(self.0, retry_policy.inner())).wrap(self) }
+        try!(self.change("retry_policy",
+                         move |inner| unsafe { cass_statement_set_retry_policy(inner, retry_policy.inner()) }));
+        Ok(self)
     }
 
     ///Sets the statement's custom payload.
     pub fn set_custom_payload(&mut self, payload: CustomPayload) -> Result<&Self, CassError> {
-        unsafe { CassError::build(cass_statement_set_custom_payload(self.0, payload.inner())).wrap(self) }
+        try!(self.change("custom_payload",
+                         move |inner| unsafe { cass_statement_set_custom_payload(inner, payload.inner()) }));
+        Ok(self)
     }
 
     ///Binds null to a query or bound statement at the specified index.
     pub fn bind_null(&mut self, index: u64) -> Result<&mut Self, CassError> {
-        unsafe { CassError::build(cass_statement_bind_null(self.0, index)).wrap(self) }
+        self.bind_with(index, Bound::Null, move |inner| unsafe { cass_statement_bind_null(inner, index) })
     }
 
     ///Binds a null to all the values with the specified name.
-    ///
-    ///This can only be used with statements created by
-    ///cass_prepared_bind().
     pub fn bind_null_by_name(&mut self, name: &str) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_bind_null_by_name(self.0,
-                                                              CString::new(name).expect("must be utf8").as_ptr()))
-                .wrap(self)
+        if self.parameters.is_some() {
+            return self.bind_named(name, |statement, index| statement.bind_null(index));
         }
+        let result = unsafe {
+            cass_statement_bind_null_by_name(self.inner,
+                                             CString::new(name).expect("must be utf8").as_ptr())
+        };
+        self.bound_by_name(result)
     }
 
     ///Binds a "tinyint" to a query or bound statement at the specified index.
     pub fn bind_int8(&mut self, index: u64, value: i8) -> Result<&mut Self, CassError> {
-        unsafe { CassError::build(cass_statement_bind_int8(self.0, index, value)).wrap(self) }
+        self.bind_with(index,
+                       Bound::fixed(value as u8 as u64, 1),
+                       move |inner| unsafe { cass_statement_bind_int8(inner, index, value) })
     }
 
     ///Binds a "tinyint" to all the values with the specified name.
     pub fn bind_int8_by_name(&mut self, name: &str, value: i8) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_bind_int8_by_name(self.0,
-                                                              CString::new(name).expect("must be utf8").as_ptr(),
-                                                              value))
-                .wrap(self)
+        if self.parameters.is_some() {
+            return self.bind_named(name, |statement, index| statement.bind_int8(index, value));
         }
+        let result = unsafe {
+            cass_statement_bind_int8_by_name(self.inner,
+                                             CString::new(name).expect("must be utf8").as_ptr(),
+                                             value)
+        };
+        self.bound_by_name(result)
     }
 
     ///Binds an "smallint" to a query or bound statement at the specified index.
     pub fn bind_int16(&mut self, index: u64, value: i16) -> Result<&mut Self, CassError> {
-        unsafe { CassError::build(cass_statement_bind_int16(self.0, index, value)).wrap(self) }
+        self.bind_with(index,
+                       Bound::fixed(value as u16 as u64, 2),
+                       move |inner| unsafe { cass_statement_bind_int16(inner, index, value) })
     }
 
     ///Binds a "smallint" to all the values with the specified name.
     pub fn bind_int16_by_name(&mut self, name: &str, value: i16) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_bind_int16_by_name(self.0,
-                                                               CString::new(name).expect("must be utf8").as_ptr(),
-                                                               value))
-                .wrap(self)
+        if self.parameters.is_some() {
+            return self.bind_named(name, |statement, index| statement.bind_int16(index, value));
         }
+        let result = unsafe {
+            cass_statement_bind_int16_by_name(self.inner,
+                                              CString::new(name).expect("must be utf8").as_ptr(),
+                                              value)
+        };
+        self.bound_by_name(result)
     }
 
     ///Binds an "int" to a query or bound statement at the specified index.
     pub fn bind_int32(&mut self, index: u64, value: i32) -> Result<&mut Self, CassError> {
-        unsafe { CassError::build(cass_statement_bind_int32(self.0, index, value)).wrap(self) }
+        self.bind_with(index,
+                       Bound::fixed(value as u32 as u64, 4),
+                       move |inner| unsafe { cass_statement_bind_int32(inner, index, value) })
     }
 
     ///Binds an "int" to all the values with the specified name.
     pub fn bind_int32_by_name(&mut self, name: &str, value: i32) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_bind_int32_by_name(self.0,
-                                                               CString::new(name).expect("must be utf8").as_ptr(),
-                                                               value))
-                .wrap(self)
+        if self.parameters.is_some() {
+            return self.bind_named(name, |statement, index| statement.bind_int32(index, value));
         }
+        let result = unsafe {
+            cass_statement_bind_int32_by_name(self.inner,
+                                              CString::new(name).expect("must be utf8").as_ptr(),
+                                              value)
+        };
+        self.bound_by_name(result)
     }
 
     ///Binds a "date" to a query or bound statement at the specified index.
     pub fn bind_uint32(&mut self, index: u64, value: u32) -> Result<&mut Self, CassError> {
-        unsafe { CassError::build(cass_statement_bind_uint32(self.0, index, value)).wrap(self) }
+        self.bind_with(index,
+                       Bound::fixed(value as u64, 4),
+                       move |inner| unsafe { cass_statement_bind_uint32(inner, index, value) })
     }
 
     ///Binds a "date" to all the values with the specified name.
-    ///
-    ///This can only be used with statements created by
-    ///cass_prepared_bind().
     pub fn bind_uint32_by_name(&mut self, name: &str, value: u32) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_bind_uint32_by_name(self.0,
-                                                                CString::new(name).expect("must be utf8").as_ptr(),
-                                                                value))
-                .wrap(self)
+        if self.parameters.is_some() {
+            return self.bind_named(name, |statement, index| statement.bind_uint32(index, value));
         }
+        let result = unsafe {
+            cass_statement_bind_uint32_by_name(self.inner,
+                                               CString::new(name).expect("must be utf8").as_ptr(),
+                                               value)
+        };
+        self.bound_by_name(result)
     }
 
     ///Binds a "bigint", "counter", "timestamp" or "time" to a query or
     ///bound statement at the specified index.
     pub fn bind_int64(&mut self, index: u64, value: i64) -> Result<&mut Self, CassError> {
-        unsafe { CassError::build(cass_statement_bind_int64(self.0, index, value)).wrap(self) }
+        self.bind_with(index,
+                       Bound::fixed(value as u64, 8),
+                       move |inner| unsafe { cass_statement_bind_int64(inner, index, value) })
     }
 
     ///Binds a "bigint", "counter", "timestamp" or "time" to all values
     ///with the specified name.
     pub fn bind_int64_by_name(&mut self, name: &str, value: i64) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_bind_int64_by_name(self.0,
-                                                               CString::new(name).expect("must be utf8").as_ptr(),
-                                                               value))
-                .wrap(self)
+        if self.parameters.is_some() {
+            return self.bind_named(name, |statement, index| statement.bind_int64(index, value));
         }
+        let result = unsafe {
+            cass_statement_bind_int64_by_name(self.inner,
+                                              CString::new(name).expect("must be utf8").as_ptr(),
+                                              value)
+        };
+        self.bound_by_name(result)
     }
 
     ///Binds a "float" to a query or bound statement at the specified index.
     pub fn bind_float(&mut self, index: u64, value: f32) -> Result<&mut Self, CassError> {
-        unsafe { CassError::build(cass_statement_bind_float(self.0, index, value)).wrap(self) }
+        self.bind_with(index,
+                       Bound::fixed(value.to_bits() as u64, 4),
+                       move |inner| unsafe { cass_statement_bind_float(inner, index, value) })
     }
 
     /// Binds a "float" to all the values with the specified name.
-    ///
-    ///This can only be used with statements created by
-    ///cass_prepared_bind().
     pub fn bind_float_by_name(&mut self, name: &str, value: f32) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_bind_float_by_name(self.0,
-                                                               CString::new(name).expect("must be utf8").as_ptr(),
-                                                               value))
-                .wrap(self)
+        if self.parameters.is_some() {
+            return self.bind_named(name, |statement, index| statement.bind_float(index, value));
         }
+        let result = unsafe {
+            cass_statement_bind_float_by_name(self.inner,
+                                              CString::new(name).expect("must be utf8").as_ptr(),
+                                              value)
+        };
+        self.bound_by_name(result)
     }
 
     ///Binds a "double" to a query or bound statement at the specified index.
     pub fn bind_double(&mut self, index: u64, value: f64) -> Result<&mut Self, CassError> {
-        unsafe { CassError::build(cass_statement_bind_double(self.0, index, value)).wrap(self) }
+        self.bind_with(index,
+                       Bound::fixed(value.to_bits(), 8),
+                       move |inner| unsafe { cass_statement_bind_double(inner, index, value) })
     }
 
     ///Binds a "double" to all the values with the specified name.
-    ///
-    ///This can only be used with statements created by
-    ///cass_prepared_bind().
     pub fn bind_double_by_name(&mut self, name: &str, value: f64) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_bind_double_by_name(self.0,
-                                                                CString::new(name).expect("must be utf8").as_ptr(),
-                                                                value))
-                .wrap(self)
+        if self.parameters.is_some() {
+            return self.bind_named(name, |statement, index| statement.bind_double(index, value));
         }
+        let result = unsafe {
+            cass_statement_bind_double_by_name(self.inner,
+                                               CString::new(name).expect("must be utf8").as_ptr(),
+                                               value)
+        };
+        self.bound_by_name(result)
     }
 
     ///Binds a "boolean" to a query or bound statement at the specified index.
     pub fn bind_bool(&mut self, index: u64, value: bool) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_bind_bool(self.0, index, if value { cass_true } else { cass_false }))
-                .wrap(self)
-        }
+        let raw = if value { cass_true } else { cass_false };
+        self.bind_with(index,
+                       Bound::fixed(value as u64, 1),
+                       move |inner| unsafe { cass_statement_bind_bool(inner, index, raw) })
     }
 
     /// Binds a "boolean" to all the values with the specified name.
-    ///
-    ///This can only be used with statements created by
-    /// cass_prepared_bind().
     pub fn bind_bool_by_name(&mut self, name: &str, value: bool) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_bind_bool_by_name(self.0,
-                                                              CString::new(name).expect("must be utf8").as_ptr(),
-                                                              if value { cass_true } else { cass_false }))
-                .wrap(self)
+        if self.parameters.is_some() {
+            return self.bind_named(name, |statement, index| statement.bind_bool(index, value));
         }
+        let result = unsafe {
+            cass_statement_bind_bool_by_name(self.inner,
+                                             CString::new(name).expect("must be utf8").as_ptr(),
+                                             if value { cass_true } else { cass_false })
+        };
+        self.bound_by_name(result)
     }
 
     ///Binds an "ascii", "text" or "varchar" to a query or bound statement
     ///at the specified index.
     pub fn bind_string(&mut self, index: u64, value: &str) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_bind_string(self.0,
-                                                        index,
-                                                        CString::new(value).expect("must be utf8").as_ptr()))
-                .wrap(self)
-        }
+        let text = CString::new(value).expect("must be utf8");
+        self.bind_with(index,
+                       Bound::Bytes(value.as_bytes()),
+                       move |inner| unsafe { cass_statement_bind_string(inner, index, text.as_ptr()) })
     }
 
     ///Binds an "ascii", "text" or "varchar" to all the values
     ///with the specified name.
-    ///
-    ///This can only be used with statements created by
-    ///cass_prepared_bind().
     pub fn bind_string_by_name(&mut self, name: &str, value: &str) -> Result<&mut Self, CassError> {
+        if self.parameters.is_some() {
+            return self.bind_named(name, |statement, index| statement.bind_string(index, value));
+        }
         unsafe {
-            let result = cass_statement_bind_string_by_name(self.0,
+            let result = cass_statement_bind_string_by_name(self.inner,
                                                             CString::new(name).expect("must be utf8").as_ptr(),
                                                             CString::new(value).expect("must be utf8").as_ptr());
-            CassError::build(result).wrap(self)
+            self.bound_by_name(result)
         }
     }
 
     ///Binds a "blob", "varint" or "custom" to a query or bound statement at the specified index.
     pub fn bind_bytes(&mut self, index: u64, value: Vec<u8>) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_bind_bytes(self.0, index, value.as_ptr(), value.len() as u64)).wrap(self)
-        }
+        let value = Rc::new(value);
+        let bytes = value.clone();
+        self.bind_with(index, Bound::Bytes(&value), move |inner| unsafe {
+            cass_statement_bind_bytes(inner, index, bytes.as_ptr(), bytes.len() as u64)
+        })
     }
 
     ///Binds a "blob", "varint" or "custom" to all the values with the
     ///specified name.
-    ///
-    ///This can only be used with statements created by
-    ///cass_prepared_bind().
     pub fn bind_bytes_by_name(&mut self, name: &str, mut value: Vec<u8>) -> Result<&mut Self, CassError> {
+        if self.parameters.is_some() {
+            return self.bind_named(name, |statement, index| statement.bind_bytes(index, value.clone()));
+        }
         unsafe {
-            let result = cass_statement_bind_bytes_by_name(self.0,
+            let result = cass_statement_bind_bytes_by_name(self.inner,
                                                            CString::new(name).expect("must be utf8").as_ptr(),
                                                            value.as_mut_ptr(),
                                                            value.len() as u64);
-            CassError::build(result).wrap(self)
+            self.bound_by_name(result)
         }
     }
 
     ///Binds a "uuid" or "timeuuid" to a query or bound statement at the specified index.
     pub fn bind_uuid(&mut self, index: u64, value: Uuid) -> Result<&mut Self, CassError> {
-        unsafe { CassError::build(cass_statement_bind_uuid(self.0, index, value.inner())).wrap(self) }
+        self.bind_with(index,
+                       Bound::Fixed(value.to_bytes(), 16),
+                       move |inner| unsafe { cass_statement_bind_uuid(inner, index, value.inner()) })
     }
 
     ///Binds a "uuid" or "timeuuid" to all the values
     ///with the specified name.
-    ///
-    ///This can only be used with statements created by
-    ///cass_prepared_bind().
     pub fn bind_uuid_by_name(&mut self, name: &str, value: Uuid) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_bind_uuid_by_name(self.0,
-                                                              CString::new(name).expect("must be utf8").as_ptr(),
-                                                              value.inner()))
-                .wrap(self)
+        if self.parameters.is_some() {
+            return self.bind_named(name, |statement, index| statement.bind_uuid(index, value));
         }
+        let result = unsafe {
+            cass_statement_bind_uuid_by_name(self.inner,
+                                             CString::new(name).expect("must be utf8").as_ptr(),
+                                             value.inner())
+        };
+        self.bound_by_name(result)
     }
 
     ///Binds an "inet" to a query or bound statement at the specified index.
     pub fn bind_inet(&mut self, index: u64, value: Inet) -> Result<&mut Self, CassError> {
-        unsafe { CassError::build(cass_statement_bind_inet(self.0, index, value.inner())).wrap(self) }
+        self.bind_inet_ref(index, &value)
+    }
+
+    fn bind_inet_ref(&mut self, index: u64, value: &Inet) -> Result<&mut Self, CassError> {
+        let address = value.inner();
+        self.bind_with(index, Bound::Opaque, move |inner| unsafe { cass_statement_bind_inet(inner, index, address) })
     }
 
     ///Binds an "inet" to all the values with the specified name.
     pub fn bind_inet_by_name(&mut self, name: &str, value: Inet) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_bind_inet_by_name(self.0,
-                                                              CString::new(name).expect("must be utf8").as_ptr(),
-                                                              value.inner()))
-                .wrap(self)
+        if self.parameters.is_some() {
+            return self.bind_named(name, |statement, index| statement.bind_inet_ref(index, &value));
         }
+        let result = unsafe {
+            cass_statement_bind_inet_by_name(self.inner,
+                                             CString::new(name).expect("must be utf8").as_ptr(),
+                                             value.inner())
+        };
+        self.bound_by_name(result)
     }
 
 
@@ -570,7 +1216,7 @@ impl Statement {
     //            unsafe {
     //                CassError::build(
     //                    cass_statement_bind_decimal(
-    //                        self.0,
+    //                        self.inner,
     //                        index,
     //                        value
     //                    )
@@ -590,7 +1236,7 @@ impl Statement {
     //            let name = CString::new(name).unwrap();
     //            CassError::build(
     //            cass_statement_bind_decimal_by_name(
-    //                self.0,
+    //                self.inner,
     //                name.as_ptr(),
     //                value
     //            )
@@ -600,92 +1246,177 @@ impl Statement {
 
     ///Bind a "map" to a query or bound statement at the specified index.
     pub fn bind_map(&mut self, index: u64, map: Map) -> Result<&mut Self, CassError> {
-        unsafe { CassError::build(cass_statement_bind_collection(self.0, index, map.inner())).wrap(self) }
+        self.bind_collection(index, Rc::new(map))
+    }
+
+    fn bind_collection<C>(&mut self, index: u64, collection: Rc<C>) -> Result<&mut Self, CassError>
+        where C: Protected<*mut _CassCollection> + 'static {
+        self.bind_with(index,
+                       Bound::Opaque,
+                       move |inner| unsafe { cass_statement_bind_collection(inner, index, collection.inner()) })
     }
 
     ///Bind a "map" to all the values with the
     ///specified name.
-    ///
-    ///This can only be used with statements created by
-    ///cass_prepared_bind().
     pub fn bind_map_by_name(&mut self, name: &str, map: Map) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_bind_collection_by_name(self.0,
-                                                                    CString::new(name).expect("must be utf8").as_ptr(),
-                                                                    map.inner()))
-                .wrap(self)
+        if self.parameters.is_some() {
+            let map = Rc::new(map);
+            return self.bind_named(name, |statement, index| statement.bind_collection(index, map.clone()));
         }
+        let result = unsafe {
+            cass_statement_bind_collection_by_name(self.inner,
+                                                   CString::new(name).expect("must be utf8").as_ptr(),
+                                                   map.inner())
+        };
+        self.bound_by_name(result)
     }
     ///Bind a "set" to a query or bound statement at the specified index.
     pub fn bind_set(&mut self, index: u64, collection: Set) -> Result<&mut Self, CassError> {
-        unsafe { CassError::build(cass_statement_bind_collection(self.0, index, collection.inner())).wrap(self) }
+        self.bind_collection(index, Rc::new(collection))
     }
 
     ///Bind a "set" to all the values with the
     ///specified name.
-    ///
-    ///This can only be used with statements created by
-    ///cass_prepared_bind().
     pub fn bind_set_by_name(&mut self, name: &str, collection: Set) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_bind_collection_by_name(self.0,
-                                                                    CString::new(name).expect("must be utf8").as_ptr(),
-                                                                    collection.inner()))
-                .wrap(self)
+        if self.parameters.is_some() {
+            let collection = Rc::new(collection);
+            return self.bind_named(name, |statement, index| statement.bind_collection(index, collection.clone()));
         }
+        let result = unsafe {
+            cass_statement_bind_collection_by_name(self.inner,
+                                                   CString::new(name).expect("must be utf8").as_ptr(),
+                                                   collection.inner())
+        };
+        self.bound_by_name(result)
     }
 
     ///Bind a "list" to a query or bound statement at the specified index.
     pub fn bind_list(&mut self, index: u64, collection: List) -> Result<&mut Self, CassError> {
-        unsafe { CassError::build(cass_statement_bind_collection(self.0, index, collection.inner())).wrap(self) }
+        self.bind_collection(index, Rc::new(collection))
     }
 
     ///Bind a "list" to all the values with the
     ///specified name.
-    ///
-    ///This can only be used with statements created by
-    ///cass_prepared_bind().
     pub fn bind_list_by_name(&mut self, name: &str, collection: List) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_bind_collection_by_name(self.0,
-                                                                    CString::new(name).expect("must be utf8").as_ptr(),
-                                                                    collection.inner()))
-                .wrap(self)
+        if self.parameters.is_some() {
+            let collection = Rc::new(collection);
+            return self.bind_named(name, |statement, index| statement.bind_collection(index, collection.clone()));
         }
+        let result = unsafe {
+            cass_statement_bind_collection_by_name(self.inner,
+                                                   CString::new(name).expect("must be utf8").as_ptr(),
+                                                   collection.inner())
+        };
+        self.bound_by_name(result)
     }
 
     ///Bind a "tuple" to a query or bound statement at the specified index.
     pub fn bind_tuple(&mut self, index: u64, value: Tuple) -> Result<&mut Self, CassError> {
-        unsafe { CassError::build(cass_statement_bind_tuple(self.0, index, value.inner())).wrap(self) }
+        self.bind_shared_tuple(index, Rc::new(value))
+    }
+
+    fn bind_shared_tuple(&mut self, index: u64, value: Rc<Tuple>) -> Result<&mut Self, CassError> {
+        self.bind_with(index,
+                       Bound::Opaque,
+                       move |inner| unsafe { cass_statement_bind_tuple(inner, index, value.inner()) })
     }
 
     ///Bind a "tuple" to all the values with the specified name.
-    ///
-    ///This can only be used with statements created by
-    ///cass_prepared_bind().
     pub fn bind_tuple_by_name(&mut self, name: &str, value: Tuple) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_bind_tuple_by_name(self.0,
-                                                               CString::new(name).expect("must be utf8").as_ptr(),
-                                                               value.inner()))
-                .wrap(self)
+        if self.parameters.is_some() {
+            let value = Rc::new(value);
+            return self.bind_named(name, |statement, index| statement.bind_shared_tuple(index, value.clone()));
         }
+        let result = unsafe {
+            cass_statement_bind_tuple_by_name(self.inner,
+                                              CString::new(name).expect("must be utf8").as_ptr(),
+                                              value.inner())
+        };
+        self.bound_by_name(result)
     }
 
     ///Bind a user defined type to a query or bound statement at the
     ///specified index.
     pub fn bind_user_type(&mut self, index: u64, value: &UserType) -> Result<&mut Self, CassError> {
-        unsafe { CassError::build(cass_statement_bind_user_type(self.0, index, value.inner())).wrap(self) }
+        let result = unsafe { cass_statement_bind_user_type(self.inner, index, value.inner()) };
+        try!(CassError::build(result).wrap(()));
+        record_value(self, index, Bound::Opaque);
+        // not owned by the statement, so copies cannot bind it again
+        self.values.insert(index, None);
+        Ok(self)
     }
 
     ///Bind a user defined type to a query or bound statement with the
     ///specified name.
     pub fn bind_user_type_by_name(&mut self, name: &str, value: &UserType) -> Result<&mut Self, CassError> {
-        unsafe {
-            CassError::build(cass_statement_bind_user_type_by_name(self.0,
-                                                                   CString::new(name).expect("must be utf8").as_ptr(),
-                                                                   value.inner()))
-                .wrap(self)
+        if self.parameters.is_some() {
+            return self.bind_named(name, |statement, index| statement.bind_user_type(index, value));
         }
+        let result = unsafe {
+            cass_statement_bind_user_type_by_name(self.inner,
+                                                  CString::new(name).expect("must be utf8").as_ptr(),
+                                                  value.inner())
+        };
+        self.bound_by_name(result)
     }
 }
+
+#[test]
+fn test_rewrite_named_parameters() {
+    let (query, markers, names) = rewrite_named_parameters("SELECT * FROM t WHERE a = :a AND b = ? AND c IN \
+                                                             (:Id, 'it''s :not') /* :nor */ AND m = {'k':v} \
+                                                             AND d = :a -- :nor\n");
+    assert_eq!(query,
+               "SELECT * FROM t WHERE a = ? AND b = ? AND c IN (?, 'it''s :not') /* :nor */ AND m = {'k':v} AND d \
+                = ? -- :nor\n");
+    assert_eq!(markers, 4);
+    assert_eq!(names.get("a"), Some(&vec![0, 3]));
+    assert_eq!(names.get("id"), Some(&vec![2]));
+    assert_eq!(names.len(), 2);
+
+    let (query, markers, names) = rewrite_named_parameters("UPDATE t SET \"Val\" = :\"Val\" WHERE k = :\"a\"\"b\" \
+                                                             AND j = :K AND l = :\"\" AND m = :\"open");
+    assert_eq!(query,
+               "UPDATE t SET \"Val\" = ? WHERE k = ? AND j = ? AND l = :\"\" AND m = :\"open");
+    assert_eq!(markers, 3);
+    assert_eq!(names.get("Val"), Some(&vec![0]));
+    assert_eq!(names.get("a\"b"), Some(&vec![1]));
+    assert_eq!(names.get("k"), Some(&vec![2]));
+
+    assert!(parse_query("SELECT * FROM t WHERE a = :a AND b = ?", 2).is_ok());
+    assert!(parse_query("SELECT * FROM t WHERE a = :a AND b = ?", 1).is_err());
+    assert!(parse_query("SELECT * FROM t WHERE a = ? AND b = ?", 1).is_ok());
+    assert_eq!(Statement::parameter_count("SELECT * FROM t WHERE id = :id AND s = '?' AND b = ?"), 2);
+}
+
+#[test]
+fn test_routing_key() {
+    let mut statement = Statement::new("INSERT INTO t (a, b, v) VALUES (?, ?, ?)", 3);
+    assert_eq!(statement.routing_key(), None);
+    statement.add_key_index(0).unwrap();
+    statement.bind_int32(0, 1).unwrap();
+    assert_eq!(statement.routing_key(), Some(vec![0, 0, 0, 1]));
+    statement.add_key_index(1).unwrap();
+    statement.bind_string(1, "ab").unwrap();
+    statement.bind_int64(2, -1).unwrap();
+    assert_eq!(statement.routing_key(),
+               Some(vec![0, 4, 0, 0, 0, 1, 0, 0, 2, b'a', b'b', 0]));
+    assert_eq!(statement.estimated_size(), 1 + (4 + 40) + 2 + (4 + 4) + (4 + 2) + (4 + 8));
+
+    // a null key value matches CqlValue::Null and leaves the key unknown
+    statement.bind_null(1).unwrap();
+    assert_eq!(statement.routing_key(), None);
+}
+
+#[test]
+fn test_statement_snapshot() {
+    let mut statement = Statement::new("SELECT * FROM t WHERE a = :a AND b = ?", 2);
+    statement.set_paging_size(10).unwrap();
+    statement.bind_int32_by_name("a", 1).unwrap();
+    statement.bind_bytes(1, vec![1, 2]).unwrap();
+    assert!(statement_snapshot(&statement).is_some());
+
+    // the driver statement was not created by the crate
+    let raw = Statement::build(unsafe { cass_statement_new(CString::new("SELECT * FROM t").unwrap().as_ptr(), 0) });
+    assert!(statement_snapshot(&raw).is_none());
+}
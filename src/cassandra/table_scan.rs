@@ -0,0 +1,350 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fmt;
+
+use cassandra::error::CassError;
+use cassandra::future::ResultFuture;
+use cassandra::paging::{PagedRow, page_rows};
+use cassandra::paging_state::{PagingState, PagingStateError};
+use cassandra::query_builder::{self, BuildError, Operator, TableName, ToStatement};
+use cassandra::session::Session;
+use cassandra::statement::Statement;
+use cassandra::util::Protected;
+
+use cassandra_sys::CASS_ERROR_LIB_BAD_PARAMS;
+use cassandra_sys::CASS_ERROR_LIB_NAME_DOES_NOT_EXIST;
+
+///A range of Murmur3 tokens, exclusive of `start` and inclusive of `end`
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub struct TokenRange {
+    ///The token just before the range
+    pub start: i64,
+    ///The last token in the range
+    pub end: i64,
+}
+
+impl TokenRange {
+    ///Splits the whole Murmur3 token ring into `count` ranges of (nearly)
+    ///equal width.
+    pub fn split_ring(count: usize) -> Vec<TokenRange> {
+        let count = if count == 0 { 1 } else { count as u64 };
+        let width = u64::max_value() / count;
+        let boundary = |index: u64| (i64::min_value() as u64).wrapping_add(width * index) as i64;
+        (0..count)
+            .map(|index| {
+                TokenRange {
+                    start: boundary(index),
+                    end: if index + 1 == count { i64::max_value() } else { boundary(index + 1) },
+                }
+            })
+            .collect()
+    }
+}
+
+impl Display for TokenRange {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "({}, {}]", self.start, self.end)
+    }
+}
+
+///How far the scan of a token range got
+#[derive(Clone,Debug)]
+pub struct RangeProgress {
+    ///The token range
+    pub range: TokenRange,
+    ///Where to continue reading the range, or `None` to read it from the
+    ///start
+    pub paging_state: Option<PagingState>,
+}
+
+///A token range that could not be read
+#[derive(Debug)]
+pub struct ScanError {
+    ///Where the range scan stopped. Pass it to `Session::resume_scan` to
+    ///try again.
+    pub progress: RangeProgress,
+    ///The last error reading the range
+    pub error: CassError,
+}
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "scan of token range {} failed: {}", self.progress.range, self.error)
+    }
+}
+
+impl Error for ScanError {
+    fn description(&self) -> &str {
+        "token range scan failed"
+    }
+}
+
+struct RangeScan {
+    progress: RangeProgress,
+    attempts: u32,
+}
+
+///Reads every row of a table by splitting the token ring into ranges and
+///querying several ranges at once.
+///
+///Rows come back in no particular order. A range that keeps failing is
+///reported as a `ScanError` and the scan carries on with the other ranges;
+///`remaining` tells what is left to read, so an interrupted scan can be
+///resumed with `Session::resume_scan`.
+///
+///Created by `Session::scan_table`.
+pub struct TableScan<'a> {
+    session: &'a Session,
+    table: TableName,
+    partition_key: Vec<String>,
+    concurrency: usize,
+    page_size: i32,
+    max_retries: u32,
+    restart_invalid: bool,
+    queued: VecDeque<RangeScan>,
+    in_flight: Vec<(RangeScan, ResultFuture)>,
+    rows: VecDeque<PagedRow>,
+    page: Option<(RangeScan, Option<PagingState>)>,
+}
+
+impl<'a> TableScan<'a> {
+    ///Sets how many token ranges are queried at once.
+    ///
+    ///<b>Default:</b> 4
+    pub fn set_concurrency(&mut self, concurrency: usize) -> &mut Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    ///Sets the number of rows fetched per request.
+    ///
+    ///<b>Default:</b> 1000
+    pub fn set_paging_size(&mut self, page_size: i32) -> &mut Self {
+        self.page_size = page_size;
+        self
+    }
+
+    ///Sets how many times a failed request is retried before its range is
+    ///reported as failed.
+    ///
+    ///<b>Default:</b> 3
+    pub fn set_max_retries(&mut self, max_retries: u32) -> &mut Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    ///Sets whether a range whose saved paging state the statement rejects
+    ///(see `Statement::resume_from`) is read again from its start. Rows of
+    ///the range that were already returned are then returned again.
+    ///
+    ///If not set, the range is reported as a `ScanError` whose progress
+    ///keeps the rejected paging state.
+    ///
+    ///<b>Default:</b> false
+    pub fn set_restart_invalid_ranges(&mut self, restart: bool) -> &mut Self {
+        self.restart_invalid = restart;
+        self
+    }
+
+    ///The partition key columns the ring is split on
+    pub fn partition_key(&self) -> &[String] {
+        &self.partition_key
+    }
+
+    ///The ranges that have not been read completely, including the range
+    ///whose rows are currently being returned.
+    pub fn remaining(&self) -> Vec<RangeProgress> {
+        let current = self.page.iter().map(|&(ref scan, ref state)| {
+            RangeProgress {
+                range: scan.progress.range,
+                paging_state: state.clone(),
+            }
+        });
+        current.chain(self.in_flight.iter().map(|&(ref scan, _)| scan.progress.clone()))
+               .chain(self.queued.iter().map(|scan| scan.progress.clone()))
+               .collect()
+    }
+
+    fn statement(&self, progress: &RangeProgress) -> Result<Statement, CassError> {
+        let columns: Vec<&str> = self.partition_key.iter().map(|column| &column[..]).collect();
+        let statement = query_builder::select(self.table.clone())
+                            .where_token(&columns, Operator::Gt, progress.range.start)
+                            .where_token(&columns, Operator::Lte, progress.range.end)
+                            .to_statement();
+        let mut statement = try!(statement.map_err(|err| {
+            match err {
+                BuildError::Bind(err) => err,
+                _ => CassError::build(CASS_ERROR_LIB_BAD_PARAMS),
+            }
+        }));
+        try!(statement.set_paging_size(self.page_size));
+        if let Some(ref state) = progress.paging_state {
+            if let Err(err) = statement.resume_from(state) {
+                if !self.restart_invalid {
+                    return Err(match err {
+                        PagingStateError::Cass(err) => err,
+                        _ => CassError::build(CASS_ERROR_LIB_BAD_PARAMS),
+                    });
+                }
+                warn!("restarting token range {}: {}", progress.range, err);
+            }
+        }
+        Ok(statement)
+    }
+
+    ///Starts requests for queued ranges until the concurrency limit is hit
+    fn fill(&mut self) -> Option<ScanError> {
+        let concurrency = if self.concurrency == 0 { 1 } else { self.concurrency };
+        while self.in_flight.len() < concurrency {
+            let scan = match self.queued.pop_front() {
+                Some(scan) => scan,
+                None => break,
+            };
+            match self.statement(&scan.progress) {
+                Ok(statement) => {
+                    let future = self.session.execute(&statement);
+                    self.in_flight.push((scan, future));
+                }
+                Err(err) => {
+                    return Some(ScanError {
+                        progress: scan.progress,
+                        error: err,
+                    })
+                }
+            }
+        }
+        None
+    }
+
+    ///Waits for a request, preferring one that has already completed
+    fn receive(&mut self) -> Option<ScanError> {
+        let index = self.in_flight.iter().position(|&(_, ref future)| future.ready()).unwrap_or(0);
+        let (mut scan, mut future) = self.in_flight.remove(index);
+        match future.wait().and_then(|result| page_rows(&result).map(|rows| (result, rows))) {
+            Ok((result, rows)) => {
+                let resumed_from = scan.progress.paging_state.take();
+                scan.progress.paging_state = result.paging_state();
+                scan.attempts = 0;
+                self.rows = rows;
+                self.page = Some((scan, resumed_from));
+                None
+            }
+            Err(err) => {
+                scan.attempts += 1;
+                // a session shutting down refuses every retry
+                if scan.attempts <= self.max_retries && !self.session.is_shutting_down() {
+                    debug!("retrying token range {} after {}", scan.progress.range, err);
+                    self.queued.push_back(scan);
+                    None
+                } else {
+                    Some(ScanError {
+                        progress: scan.progress,
+                        error: err,
+                    })
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for TableScan<'a> {
+    type Item = Result<PagedRow, ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.rows.pop_front() {
+                return Some(Ok(row));
+            }
+            if let Some((scan, _)) = self.page.take() {
+                // the range is continued once its previous page has been read,
+                // so that `remaining` never skips rows that were not returned
+                if scan.progress.paging_state.is_some() {
+                    self.queued.push_front(scan);
+                }
+            }
+            if let Some(err) = self.fill() {
+                return Some(Err(err));
+            }
+            if self.in_flight.is_empty() {
+                return None;
+            }
+            if let Some(err) = self.receive() {
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+impl Session {
+    ///Scans a whole table, splitting the token ring into `ranges` ranges of
+    ///its partition key.
+    pub fn scan_table(&self, keyspace: &str, table: &str, ranges: usize) -> Result<TableScan, CassError> {
+        let progress = TokenRange::split_ring(ranges)
+                           .into_iter()
+                           .map(|range| {
+                               RangeProgress {
+                                   range: range,
+                                   paging_state: None,
+                               }
+                           })
+                           .collect();
+        self.resume_scan(keyspace, table, progress)
+    }
+
+    ///Continues a table scan from the ranges an earlier scan had not
+    ///finished (see `TableScan::remaining` and `ScanError::progress`).
+    ///
+    ///A range whose paging state no longer matches its statement is reported
+    ///as a `ScanError`, unless `TableScan::set_restart_invalid_ranges` is set.
+    pub fn resume_scan(&self, keyspace: &str, table: &str, progress: Vec<RangeProgress>)
+                       -> Result<TableScan, CassError> {
+        let schema = self.get_schema_meta();
+        let table_meta = match schema.keyspace_by_name(keyspace).and_then(|meta| meta.table_by_name(table)) {
+            Some(table_meta) => table_meta,
+            None => return Err(CassError::build(CASS_ERROR_LIB_NAME_DOES_NOT_EXIST)),
+        };
+        let partition_key = (0..table_meta.partition_key_count())
+                                .filter_map(|index| table_meta.partition_key(index))
+                                .map(|column| column.name())
+                                .collect();
+        Ok(TableScan {
+            session: self,
+            table: TableName::from((keyspace, table)),
+            partition_key: partition_key,
+            concurrency: 4,
+            page_size: 1000,
+            max_retries: 3,
+            restart_invalid: false,
+            queued: progress.into_iter()
+                            .map(|progress| {
+                                RangeScan {
+                                    progress: progress,
+                                    attempts: 0,
+                                }
+                            })
+                            .collect(),
+            in_flight: vec![],
+            rows: VecDeque::new(),
+            page: None,
+        })
+    }
+}
+
+#[test]
+fn test_split_ring() {
+    let ranges = TokenRange::split_ring(4);
+    assert_eq!(ranges.len(), 4);
+    assert_eq!(ranges[0].start, i64::min_value());
+    assert_eq!(ranges[3].end, i64::max_value());
+    for pair in ranges.windows(2) {
+        assert_eq!(pair[0].end, pair[1].start);
+    }
+    assert_eq!(ranges[2].start, -2);
+
+    assert_eq!(TokenRange::split_ring(1),
+               vec![TokenRange {
+                        start: i64::min_value(),
+                        end: i64::max_value(),
+                    }]);
+}
@@ -0,0 +1,281 @@
+use std::fmt::{Display, Formatter};
+use std::fmt;
+use std::net::IpAddr;
+use std::thread;
+
+use time::Duration;
+
+use cassandra::cql_value::CqlValue;
+use cassandra::error::CassError;
+use cassandra::row::Row;
+use cassandra::session::Session;
+use cassandra::statement::Statement;
+use cassandra::util::Protected;
+use cassandra::uuid::Uuid;
+
+use cassandra_sys::CASS_ERROR_LIB_BAD_PARAMS;
+use cassandra_sys::CASS_ERROR_LIB_INVALID_VALUE_TYPE;
+use cassandra_sys::CASS_ERROR_LIB_REQUEST_TIMED_OUT;
+
+const SESSION_QUERY: &'static str = "SELECT coordinator, duration, started_at, request FROM system_traces.sessions \
+                                     WHERE session_id = ?";
+const EVENTS_QUERY: &'static str = "SELECT activity, source, source_elapsed, thread FROM system_traces.events WHERE \
+                                    session_id = ?";
+
+///A single step of a traced request
+#[derive(Debug,Clone)]
+pub struct TraceEvent {
+    ///What the node was doing
+    pub activity: String,
+    ///The node that recorded the event
+    pub source: IpAddr,
+    ///Time since the request reached `source`
+    pub source_elapsed: Duration,
+    ///The thread that recorded the event
+    pub thread: String,
+}
+
+///A completed query trace read from `system_traces`
+#[derive(Debug,Clone)]
+pub struct Trace {
+    ///The tracing session id
+    pub id: Uuid,
+    ///The node that coordinated the request
+    pub coordinator: IpAddr,
+    ///A description of the request, usually the query
+    pub request: String,
+    ///When the coordinator received the request, in milliseconds since the epoch
+    pub started_at: i64,
+    ///Total time the coordinator spent on the request
+    pub duration: Duration,
+    ///The events recorded by every node involved, in order
+    pub events: Vec<TraceEvent>,
+}
+
+impl Display for Trace {
+    ///Renders the trace as a timeline, one event per line.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        try!(writeln!(f,
+                      "Trace {} on {}: {} ({} µs)",
+                      self.id,
+                      self.coordinator,
+                      self.request,
+                      micros(self.duration)));
+        for event in &self.events {
+            try!(writeln!(f,
+                          "{:>10} µs  {:<15}  {} [{}]",
+                          micros(event.source_elapsed),
+                          event.source.to_string(),
+                          event.activity,
+                          event.thread));
+        }
+        Ok(())
+    }
+}
+
+fn micros(duration: Duration) -> i64 {
+    duration.num_microseconds().unwrap_or(i64::max_value())
+}
+
+fn invalid_type() -> CassError {
+    CassError::build(CASS_ERROR_LIB_INVALID_VALUE_TYPE)
+}
+
+fn text(value: CqlValue) -> Result<String, CassError> {
+    match value {
+        CqlValue::Text(text) => Ok(text),
+        _ => Err(invalid_type()),
+    }
+}
+
+fn inet(value: CqlValue) -> Result<IpAddr, CassError> {
+    match value {
+        CqlValue::Inet(address) => Ok(address),
+        _ => Err(invalid_type()),
+    }
+}
+
+///A duration in microseconds, or `None` if it has not been written yet
+fn elapsed(value: CqlValue) -> Result<Option<Duration>, CassError> {
+    match value {
+        CqlValue::Int(micros) => Ok(Some(Duration::microseconds(micros as i64))),
+        CqlValue::Null => Ok(None),
+        _ => Err(invalid_type()),
+    }
+}
+
+///The values of a row's first `count` columns
+fn values(row: &Row, count: u64) -> Result<Vec<CqlValue>, CassError> {
+    (0..count).map(|index| row.get_value(index)).collect()
+}
+
+///Decodes a row of `SESSION_QUERY`, or returns `None` if the trace is not
+///complete yet. The trace has no events.
+fn decode_session(id: Uuid, values: Vec<CqlValue>) -> Result<Option<Trace>, CassError> {
+    let mut values = values.into_iter();
+    let mut next = || values.next().ok_or_else(invalid_type);
+    let coordinator = try!(inet(try!(next())));
+    // the coordinator writes the duration last, once the request has completed
+    let duration = match try!(elapsed(try!(next()))) {
+        Some(duration) => duration,
+        None => return Ok(None),
+    };
+    let started_at = match try!(next()) {
+        CqlValue::Bigint(started_at) => started_at,
+        _ => return Err(invalid_type()),
+    };
+    Ok(Some(Trace {
+        id: id,
+        coordinator: coordinator,
+        request: try!(text(try!(next()))),
+        started_at: started_at,
+        duration: duration,
+        events: vec![],
+    }))
+}
+
+///Decodes a row of `EVENTS_QUERY`.
+fn decode_event(values: Vec<CqlValue>) -> Result<TraceEvent, CassError> {
+    let mut values = values.into_iter();
+    let mut next = || values.next().ok_or_else(invalid_type);
+    Ok(TraceEvent {
+        activity: try!(text(try!(next()))),
+        source: try!(inet(try!(next()))),
+        source_elapsed: try!(try!(elapsed(try!(next()))).ok_or_else(invalid_type)),
+        thread: try!(text(try!(next()))),
+    })
+}
+
+///Calls `fetch` up to `attempts` times, `interval` apart, until it returns
+///a value. Fails with `CASS_ERROR_LIB_REQUEST_TIMED_OUT` if it never does, or
+///`CASS_ERROR_LIB_BAD_PARAMS` if `interval` is negative.
+fn poll<T, F>(attempts: u32, interval: Duration, mut fetch: F) -> Result<T, CassError>
+    where F: FnMut() -> Result<Option<T>, CassError> {
+    let interval = try!(interval.to_std().map_err(|_| CassError::build(CASS_ERROR_LIB_BAD_PARAMS)));
+    for attempt in 0..attempts {
+        if attempt > 0 {
+            thread::sleep(interval);
+        }
+        if let Some(value) = try!(fetch()) {
+            return Ok(value);
+        }
+    }
+    Err(CassError::build(CASS_ERROR_LIB_REQUEST_TIMED_OUT))
+}
+
+impl Session {
+    ///Fetches the trace of a request executed with `Statement::set_tracing`,
+    ///waiting up to two seconds for the nodes to finish writing it.
+    pub fn fetch_trace(&self, id: Uuid) -> Result<Trace, CassError> {
+        self.fetch_trace_with(id, 10, Duration::milliseconds(200))
+    }
+
+    ///Fetches a trace, polling `system_traces.sessions` up to `attempts`
+    ///times, `interval` apart, until the trace is complete.
+    ///
+    ///Fails with `CASS_ERROR_LIB_REQUEST_TIMED_OUT` if the trace is still
+    ///incomplete after the last attempt, `CASS_ERROR_LIB_BAD_PARAMS` if
+    ///`interval` is negative and `CASS_ERROR_LIB_INVALID_VALUE_TYPE` if a
+    ///column of the trace does not have the expected type.
+    pub fn fetch_trace_with(&self, id: Uuid, attempts: u32, interval: Duration) -> Result<Trace, CassError> {
+        let mut trace = try!(poll(attempts, interval, || {
+            let mut statement = Statement::new(SESSION_QUERY, 1);
+            try!(statement.bind_uuid(0, id));
+            let result = try!(self.execute(&statement).wait());
+            match result.first_row() {
+                Some(row) => decode_session(id, try!(values(&row, 4))),
+                None => Ok(None),
+            }
+        }));
+        trace.events = try!(self.trace_events(id));
+        Ok(trace)
+    }
+
+    fn trace_events(&self, id: Uuid) -> Result<Vec<TraceEvent>, CassError> {
+        let mut statement = Statement::new(EVENTS_QUERY, 1);
+        try!(statement.bind_uuid(0, id));
+        let result = try!(self.execute(&statement).wait());
+        let mut events = vec![];
+        for row in result.iter() {
+            events.push(try!(decode_event(try!(values(&row, 4)))));
+        }
+        Ok(events)
+    }
+}
+
+#[test]
+fn test_trace_decoding() {
+    use std::net::Ipv4Addr;
+
+    let source = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+    let session = |duration: CqlValue| {
+        vec![CqlValue::Inet(source), duration, CqlValue::Bigint(1000), CqlValue::Text("Execute CQL3 query".to_owned())]
+    };
+    let trace = decode_session(Uuid::default(), session(CqlValue::Int(1500))).unwrap().unwrap();
+    assert_eq!(trace.coordinator, source);
+    assert_eq!(trace.duration, Duration::microseconds(1500));
+    assert_eq!(trace.started_at, 1000);
+    assert_eq!(trace.request, "Execute CQL3 query");
+    // incomplete until the duration is written
+    assert!(decode_session(Uuid::default(), session(CqlValue::Null)).unwrap().is_none());
+    let mut bad_coordinator = session(CqlValue::Int(1500));
+    bad_coordinator[0] = CqlValue::Text("127.0.0.1".to_owned());
+    assert!(decode_session(Uuid::default(), bad_coordinator).is_err());
+    let mut bad_request = session(CqlValue::Int(1500));
+    bad_request[3] = CqlValue::Null;
+    assert!(decode_session(Uuid::default(), bad_request).is_err());
+
+    let event = decode_event(vec![CqlValue::Text("Parsing".to_owned()),
+                                  CqlValue::Inet(source),
+                                  CqlValue::Int(42),
+                                  CqlValue::Text("Native-Transport-Requests-1".to_owned())])
+                    .unwrap();
+    assert_eq!(event.source_elapsed, Duration::microseconds(42));
+    assert_eq!(event.thread, "Native-Transport-Requests-1");
+    assert!(decode_event(vec![CqlValue::Text("Parsing".to_owned()), CqlValue::Inet(source)]).is_err());
+}
+
+#[test]
+fn test_trace_polling() {
+    let mut attempts = 0;
+    let result: Result<(), CassError> = poll(3, Duration::milliseconds(1), || {
+        attempts += 1;
+        Ok(None)
+    });
+    assert_eq!(result.unwrap_err().inner(), CASS_ERROR_LIB_REQUEST_TIMED_OUT);
+    assert_eq!(attempts, 3);
+
+    let mut attempts = 0;
+    let result = poll(3, Duration::milliseconds(1), || {
+        attempts += 1;
+        Ok(if attempts == 2 { Some(attempts) } else { None })
+    });
+    assert_eq!(result.unwrap(), 2);
+
+    let result: Result<(), CassError> = poll(3, Duration::milliseconds(-1), || Ok(None));
+    assert_eq!(result.unwrap_err().inner(), CASS_ERROR_LIB_BAD_PARAMS);
+}
+
+#[test]
+fn test_trace_timeline() {
+    use std::net::Ipv4Addr;
+
+    let source = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+    let trace = Trace {
+        id: Uuid::default(),
+        coordinator: source,
+        request: "Execute CQL3 query".to_owned(),
+        started_at: 0,
+        duration: Duration::microseconds(1500),
+        events: vec![TraceEvent {
+                         activity: "Parsing SELECT * FROM t".to_owned(),
+                         source: source,
+                         source_elapsed: Duration::microseconds(42),
+                         thread: "Native-Transport-Requests-1".to_owned(),
+                     }],
+    };
+    let timeline = trace.to_string();
+    assert!(timeline.contains("on 127.0.0.1: Execute CQL3 query (1500 µs)"));
+    assert!(timeline.contains("        42 µs  127.0.0.1        Parsing SELECT * FROM t \
+                               [Native-Transport-Requests-1]"));
+}
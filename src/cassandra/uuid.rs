@@ -42,6 +42,15 @@ impl Protected<_Uuid> for Uuid {
     }
 }
 
+impl PartialEq for Uuid {
+    fn eq(&self, other: &Uuid) -> bool {
+        self.0.time_and_version == other.0.time_and_version &&
+        self.0.clock_seq_and_node == other.0.clock_seq_and_node
+    }
+}
+
+impl Eq for Uuid {}
+
 impl Default for Uuid {
     fn default() -> Uuid {
         unsafe { ::std::mem::zeroed() }
@@ -104,6 +113,21 @@ impl Uuid {
     pub fn version(&self) -> u8 {
         unsafe { cass_uuid_version(self.0) }
     }
+
+    ///The 16 bytes of the UUID in network byte order, as sent over the wire
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let time = self.0.time_and_version;
+        let node = self.0.clock_seq_and_node;
+        let mut bytes = [0u8; 16];
+        // time_low, time_mid and time_hi_and_version are packed from the least significant end
+        for (i, shift) in [24, 16, 8, 0, 40, 32, 56, 48].iter().enumerate() {
+            bytes[i] = (time >> *shift) as u8;
+        }
+        for i in 0..8 {
+            bytes[8 + i] = (node >> (56 - 8 * i)) as u8;
+        }
+        bytes
+    }
 }
 
 impl str::FromStr for Uuid {
@@ -5,6 +5,7 @@ use cassandra_sys::CassWriteType;
 
 
 ///The write type of a request
+#[derive(Clone,Copy)]
 pub struct WriteType(pub CassWriteType);
 
 impl WriteType {
@@ -35,30 +35,15 @@ fn insert_into_paging(session: &mut Session /* , uuid_gen:&mut UuidGen */)
 }
 
 fn select_from_paging(session: &mut Session) -> Result<(), CassError> {
-    let has_more_pages = true;
     let mut statement = Statement::new(SELECT_QUERY, 0);
     statement.set_paging_size(100).unwrap();
 
-    // FIXME must understand statement lifetime better for paging
-    while has_more_pages {
-        let result = try!(session.execute(&statement).wait());
-        // println!("{:?}", result);
-        for row in result.iter() {
-            match try!(row.get_column(0)).get_string() {
-                Ok(key) => {
-                    let key_str = key.to_string();
-                    let value = try!(row.get_column(1));
-                    print!("key: '{:?}' value: '{:?}'\n",
-                           key_str,
-                           &value.get_string().unwrap());
-                }
-                Err(err) => panic!(err),
-            }
+    for row in session.execute_paged(statement) {
+        let row = try!(row);
+        match (row.get(0), row.get(1)) {
+            (Some(&CqlValue::Text(ref key)), Some(value)) => print!("key: '{:?}' value: '{:?}'\n", key, value),
+            _ => panic!("unexpected row {:?}", row),
         }
-        //        if result.has_more_pages() {
-        //            try!(statement.set_paging_state(&result));
-        //        }
-        //        has_more_pages = result.has_more_pages();
     }
     Ok(())
 }
@@ -0,0 +1,69 @@
+#[macro_use(stmt)]
+extern crate cassandra;
+use cassandra::*;
+use std::env;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+///Short-lived tokens, fetched again shortly before they expire and shared
+///by every connection
+struct TokenSource {
+    cached: Mutex<Option<(String, Instant)>>,
+}
+
+impl TokenSource {
+    fn token(&self) -> Result<String, String> {
+        let mut cached = self.cached.lock().unwrap();
+        if let Some((ref token, expires)) = *cached {
+            if Instant::now() < expires {
+                return Ok(token.clone());
+            }
+        }
+        let token = try!(fetch_token());
+        *cached = Some((token.clone(), Instant::now() + Duration::from_secs(240)));
+        Ok(token)
+    }
+}
+
+///Stands in for a call to an identity service issuing five minute tokens
+fn fetch_token() -> Result<String, String> {
+    env::var("CASSANDRA_TOKEN").map_err(|_| "CASSANDRA_TOKEN is not set".to_owned())
+}
+
+struct TokenAuthProvider(Arc<TokenSource>);
+
+impl AuthProvider for TokenAuthProvider {
+    fn new_authenticator(&self, _host: &AuthHost) -> Box<Authenticator> {
+        Box::new(TokenAuthenticator(self.0.clone()))
+    }
+}
+
+struct TokenAuthenticator(Arc<TokenSource>);
+
+impl Authenticator for TokenAuthenticator {
+    fn initial_response(&mut self) -> Result<Vec<u8>, String> {
+        self.0.token().map(String::into_bytes)
+    }
+
+    fn evaluate_challenge(&mut self, _challenge: &[u8]) -> Result<Vec<u8>, String> {
+        Err("the token was not accepted".to_owned())
+    }
+}
+
+fn main() {
+    let query = stmt!("SELECT release_version FROM system.local;");
+    let tokens = Arc::new(TokenSource { cached: Mutex::new(None) });
+
+    let mut cluster = Cluster::new();
+    cluster.set_contact_points(ContactPoints::from_str("127.0.0.1").unwrap()).unwrap();
+    cluster.set_auth_provider(TokenAuthProvider(tokens)).unwrap();
+
+    match cluster.connect() {
+        Ok(ref mut session) => {
+            let result = session.execute(&query).wait().unwrap();
+            println!("{}", result);
+        }
+        err => println!("{:?}", err),
+    }
+}
@@ -14,24 +14,54 @@ extern crate chrono;
 extern crate time;
 extern crate ip;
 extern crate uuid;
+extern crate futures;
+extern crate num;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde;
+#[macro_use]
+extern crate lazy_static;
+#[cfg(feature = "prometheus")]
+extern crate prometheus;
+#[cfg(test)]
+extern crate toml;
+#[cfg(test)]
+extern crate serde_json;
+#[cfg(test)]
+extern crate openssl;
 
 
-pub use cassandra::consistency::Consistency;
+pub use cassandra::consistency::{Consistency, ParseConsistencyError};
 // pub use cassandra::inet::{Inet};
 pub use cassandra_sys::CASS_BATCH_TYPE_LOGGED;
 pub use cassandra::batch::{Batch, BatchType, CustomPayload};
+pub use cassandra::batch_planner::{BatchOutcome, BatchPlanner, PlannedBatch};
 pub use cassandra::uuid::{Uuid, UuidGen};
-pub use cassandra::cluster::{Cluster, ContactPoints, CqlProtocol};
+pub use cassandra::authenticator::{AuthHost, AuthProvider, Authenticator, PlainTextAuthProvider};
+pub use cassandra::cluster::{Cluster, CqlProtocol};
+pub use cassandra::credentials::{Credentials, CredentialsProvider, EnvCredentials, FileCredentials};
+pub use cassandra::contact_points::{ContactPoint, ContactPointError, ContactPoints, Host};
+pub use cassandra::cluster_builder::{BuildError, ClusterBuilder, LoadBalancing};
+pub use cassandra::cluster_config::{ClusterConfig, ConfigError};
 pub use cassandra::session::Session;
+pub use cassandra::session_manager::{Health, SessionManager};
+pub use cassandra::shutdown::{ShutdownReport, ShuttingDown};
 pub use cassandra::statement::Statement;
 pub use cassandra_sys::CassBatchType;
 pub use cassandra::future::{CloseFuture, Future, FutureCallback, PreparedFuture, ResultFuture, SessionFuture};
 pub use cassandra::prepared::PreparedStatement;
+pub use cassandra::prepared_cache::PreparedCacheStats;
 pub use cassandra::result::CassResult;
+pub use cassandra::paging::{PagedRow, PagedRows, PagedStream};
+pub use cassandra::paging_state::{PagingState, PagingStateError};
+pub use cassandra::partitioner::{Murmur3Partitioner, Partitioner, RandomPartitioner, composite_key};
+pub use cassandra::table_scan::{RangeProgress, ScanError, TableScan, TokenRange};
+pub use cassandra::lwt::{LwtError, LwtOutcome, LwtResult};
+pub use cassandra::tracing::{Trace, TraceEvent};
 pub use cassandra::row::Row;
 pub use cassandra::value::{Value, ValueType}; //FIXME this should not be exported
 pub use cassandra::collection::{CassCollection, List, Map, Set};
-pub use cassandra::ssl::Ssl;
+pub use cassandra::ssl::{ParseVerifyModeError, Ssl, SslConfig, SslError, VerifyMode};
 pub use cassandra::schema::keyspace_meta::KeyspaceMeta;
 pub use cassandra::schema::column_meta::ColumnMeta;
 pub use cassandra::schema::schema_meta::SchemaMeta;
@@ -47,11 +77,18 @@ pub use cassandra::tuple::Tuple;
 pub use cassandra::inet::Inet;
 pub use cassandra::user_type::UserType;
 pub use cassandra::data_type::DataType;
+pub use cassandra::cql_type::CqlType;
+pub use cassandra::cql_value::CqlValue;
+pub use cassandra::codec::{CodecRegistry, CustomDecoder, TypeCodec, default_registry};
+pub use cassandra::query_builder;
 pub use cassandra::policy::retry::RetryPolicy;
+pub use cassandra::policy::retry_strategy::{ExponentialBackoff, RetryCause, RetryContext, RetryDecision, RetryStrategy};
 // pub use cassandra::custom_payload::CustomPayload;
 pub use cassandra::time::TimestampGen;
 // pub use cassandra::util::*;
-// pub use cassandra::metrics::*;
+pub use cassandra::metrics::{ConnectionStats, DriverCounters, ErrorStats, RequestLatency, RequestRates, SessionMetrics,
+                             driver_counters};
+pub use cassandra::exposition;
 pub use cassandra::iterator::{AggregateIterator, ColumnIterator, FieldIterator, FunctionIterator, KeyspaceIterator,
                               MapIterator, SetIterator, TableIterator, UserTypeIterator};
 // pub use cassandra::write_type::*;
@@ -65,13 +102,29 @@ mod cassandra {
     pub mod field;
     pub mod inet;
     pub mod uuid;
+    pub mod authenticator;
     pub mod cluster;
+    pub mod cluster_builder;
+    pub mod cluster_config;
+    pub mod contact_points;
+    pub mod credentials;
     pub mod session;
+    pub mod session_manager;
+    pub mod shutdown;
     pub mod statement;
     pub mod batch;
+    pub mod batch_planner;
+    pub mod execution;
     pub mod future;
     pub mod prepared;
+    pub mod prepared_cache;
     pub mod result;
+    pub mod paging;
+    pub mod paging_state;
+    pub mod table_scan;
+    pub mod partitioner;
+    pub mod lwt;
+    pub mod tracing;
     pub mod iterator;
     pub mod row;
     pub mod value;
@@ -84,12 +137,17 @@ mod cassandra {
     pub mod column;
     pub mod user_type;
     pub mod data_type;
+    pub mod cql_type;
+    pub mod cql_value;
+    pub mod codec;
+    pub mod query_builder;
     pub mod tuple;
     pub mod policy;
     pub mod custom_payload;
     pub mod time;
     pub mod util;
     pub mod metrics;
+    pub mod exposition;
     pub mod write_type;
 }
 